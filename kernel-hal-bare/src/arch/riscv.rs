@@ -60,7 +60,10 @@ impl PageTableImpl {
     pub fn protect(&mut self, vaddr: riscv::addr::VirtAddr, flags: MMUFlags) -> Result<(), ()> {
         let mut pt = self.get();
         let page = Page::of_addr(vaddr);
-        pt.update_flags(page, flags.to_ptf()).unwrap().flush();
+        // fail if not mapped
+        pt.update_flags(page, flags.to_ptf())
+            .map_err(|_| ())?
+            .flush();
         trace!("protect: {:x?}, flags={:?}", vaddr, flags);
         Ok(())
     }
@@ -144,4 +147,21 @@ impl FrameDeallocator for FrameAllocatorImpl {
     }
 }
 
+#[export_name = "hal_cpu_id"]
+pub fn cpu_id() -> u8 {
+    // the hart ID is kept in `tp`
+    let hart_id: usize;
+    unsafe {
+        asm!("mv $0, tp" : "=r"(hart_id));
+    }
+    hart_id as u8
+}
+
+/// Page faults on user memory are not resolved on riscv yet, so user pages
+/// are committed and mapped before they are accessed.
+#[export_name = "hal_user_page_fault_supported"]
+pub fn user_page_fault_supported() -> bool {
+    false
+}
+
 pub fn init() {}
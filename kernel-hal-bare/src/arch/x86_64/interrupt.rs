@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 #![allow(non_upper_case_globals)]
+use kernel_hal::MMUFlags;
 use trapframe::TrapFrame;
 
 pub fn init() {
@@ -24,10 +25,12 @@ pub fn irq_handle(irq: u8) {
     use super::{phys_to_virt, LocalApic, XApic, LAPIC_ADDR};
     let mut lapic = unsafe { XApic::new(phys_to_virt(LAPIC_ADDR)) };
     lapic.eoi();
+    kernel_hal::CpuStats::current().inc_interrupts();
     match irq {
         Timer => timer(),
         COM1 => com1(),
         Keyboard => keyboard(),
+        TlbShootdown => kernel_hal::handle_tlb_shootdown(),
         _ => panic!("unhandled external IRQ number: {}", irq),
     }
 }
@@ -40,10 +43,26 @@ fn double_fault(tf: &TrapFrame) {
     panic!("\nEXCEPTION: Double Fault\n{:#x?}", tf);
 }
 
+/// Resolve a page fault on user memory raised in the kernel, e.g. by a syscall
+/// accessing a user buffer not committed yet. Other page faults are fatal.
 fn page_fault(tf: &mut TrapFrame) {
-    panic!("\nEXCEPTION: Page Fault\n{:#x?}", tf);
+    let vaddr = super::fetch_fault_vaddr();
+    if vaddr < USER_SPACE_END {
+        let flags = if tf.error_code & 0x2 == 0 {
+            MMUFlags::READ
+        } else {
+            MMUFlags::WRITE
+        };
+        if kernel_hal::user::handle_page_fault(vaddr, flags) {
+            return;
+        }
+    }
+    panic!("\nEXCEPTION: Page Fault @ {:#x}\n{:#x?}", vaddr, tf);
 }
 
+/// The end of the lower half of the address space, where user memory is.
+const USER_SPACE_END: usize = 0x0000_8000_0000_0000;
+
 fn timer() {
     super::timer_tick();
 }
@@ -99,11 +118,15 @@ const SecurityException: u8 = 30;
 
 const IRQ0: u8 = 32;
 
+/// The interrupt vector of TLB shootdown IPIs.
+pub(super) const TLB_SHOOTDOWN_VECTOR: u8 = IRQ0 + TlbShootdown;
+
 // IRQ
 const Timer: u8 = 0;
 const Keyboard: u8 = 1;
 const COM2: u8 = 3;
 const COM1: u8 = 4;
 const IDE: u8 = 14;
+const TlbShootdown: u8 = 16;
 const Error: u8 = 19;
 const Spurious: u8 = 31;
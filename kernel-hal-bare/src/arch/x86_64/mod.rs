@@ -76,6 +76,7 @@ impl PageTableImpl {
         let page = Page::<Size4KiB>::from_start_address(vaddr).unwrap();
         if let Ok(pte) = pt.unmap(page) {
             pte.1.flush();
+            kernel_hal::queue_tlb_shootdown(self.root_paddr);
         }
         //pt.unmap(page).unwrap().1.flush();
         trace!("unmap: {:x?}", vaddr);
@@ -87,12 +88,13 @@ impl PageTableImpl {
     pub fn protect(&mut self, vaddr: x86_64::VirtAddr, flags: MMUFlags) -> Result<(), ()> {
         let mut pt = self.get();
         let page = Page::<Size4KiB>::from_start_address(vaddr).unwrap();
-        if let Ok(flush) = unsafe { pt.update_flags(page, flags.to_ptf()) } {
-            if flags.contains(MMUFlags::USER) {
-                self.allow_user_access(vaddr);
-            }
-            flush.flush();
+        // fail if not mapped
+        let flush = unsafe { pt.update_flags(page, flags.to_ptf()) }.map_err(|_| ())?;
+        if flags.contains(MMUFlags::USER) {
+            self.allow_user_access(vaddr);
         }
+        flush.flush();
+        kernel_hal::queue_tlb_shootdown(self.root_paddr);
         trace!("protect: {:x?}, flags={:?}", vaddr, flags);
         Ok(())
     }
@@ -301,8 +303,31 @@ fn tsc_frequency() -> u16 {
 
 #[export_name = "hal_timer_now"]
 pub fn timer_now() -> Duration {
-    let tsc = unsafe { core::arch::x86_64::_rdtsc() };
-    Duration::from_nanos(tsc * 1000 / tsc_frequency() as u64)
+    let tsc = timer_ticks();
+    // in case of overflow after a few months
+    Duration::from_nanos((tsc as u128 * 1000 / tsc_frequency() as u128) as u64)
+}
+
+#[export_name = "hal_timer_ticks"]
+pub fn timer_ticks() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[export_name = "hal_cpu_id"]
+pub fn cpu_id() -> u8 {
+    // initial local APIC ID
+    (unsafe { core::arch::x86_64::__cpuid(1) }.ebx >> 24) as u8
+}
+
+#[export_name = "hal_send_tlb_shootdown_ipi"]
+pub fn send_tlb_shootdown_ipi(cpu_id: u8) {
+    let mut lapic = unsafe { XApic::new(phys_to_virt(LAPIC_ADDR)) };
+    lapic.send_ipi(cpu_id, interrupt::TLB_SHOOTDOWN_VECTOR);
+}
+
+#[export_name = "hal_flush_tlb"]
+pub fn flush_tlb() {
+    x86_64::instructions::tlb::flush_all();
 }
 
 fn timer_init() {
@@ -354,3 +379,8 @@ pub fn init() {
 pub fn fetch_fault_vaddr() -> VirtAddr {
     Cr2::read().as_u64() as _
 }
+
+#[export_name = "hal_user_page_fault_supported"]
+pub fn user_page_fault_supported() -> bool {
+    true
+}
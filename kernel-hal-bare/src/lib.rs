@@ -32,6 +32,7 @@ use core::{
     arch::x86_64::{__cpuid, _mm_clflush, _mm_mfence},
     future::Future,
     pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
     task::{Context, Poll},
 };
 use kernel_hal::defs::*;
@@ -49,8 +50,9 @@ extern "C" {
     fn hal_pt_map_kernel(pt: *mut u8, current: *const u8);
     fn hal_frame_alloc() -> Option<PhysAddr>;
     fn hal_frame_dealloc(paddr: &PhysAddr);
+    /// It is set by the kernel at boot, before any physical memory access.
     #[link_name = "hal_pmem_base"]
-    static PMEM_BASE: usize;
+    static PMEM_BASE: AtomicUsize;
 }
 
 #[repr(C)]
@@ -74,6 +76,7 @@ impl Thread {
                 unsafe {
                     arch::set_page_table(self.vmtoken);
                 }
+                kernel_hal::set_active_page_table(self.vmtoken);
                 self.inner.lock().as_mut().poll(cx)
             }
         }
@@ -127,15 +130,30 @@ impl Frame {
 
     #[export_name = "hal_zero_frame_paddr"]
     pub fn zero_frame_addr() -> PhysAddr {
-        #[repr(align(0x1000))]
-        struct Page([u8; PAGE_SIZE]);
-        static ZERO_PAGE: Page = Page([0u8; PAGE_SIZE]);
-        unsafe { ZERO_PAGE.0.as_ptr() as usize - PMEM_BASE }
+        *ZERO_FRAME
     }
 }
 
+lazy_static! {
+    /// A frame filled with zeros, shared by all read-only zero pages.
+    ///
+    /// It is taken from the frame allocator in `init`, since the kernel image
+    /// is not in the physical memory window.
+    static ref ZERO_FRAME: PhysAddr = {
+        let paddr = unsafe { hal_frame_alloc() }.expect("failed to alloc zero frame");
+        unsafe {
+            core::ptr::write_bytes(phys_to_virt(paddr) as *mut u8, 0, PAGE_SIZE);
+        }
+        paddr
+    };
+}
+
+fn pmem_base() -> VirtAddr {
+    unsafe { PMEM_BASE.load(Ordering::Relaxed) }
+}
+
 fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
-    unsafe { PMEM_BASE + paddr }
+    pmem_base() + paddr
 }
 
 /// Read physical memory from `paddr` to `buf`.
@@ -214,6 +232,7 @@ pub fn init() {
         trapframe::init();
     }
     arch::init();
+    lazy_static::initialize(&ZERO_FRAME);
 }
 
 #[cfg(test)]
@@ -236,5 +255,5 @@ mod tests {
     }
 
     #[export_name = "hal_pmem_base"]
-    static PMEM_BASE: usize = 0;
+    static PMEM_BASE: AtomicUsize = AtomicUsize::new(0);
 }
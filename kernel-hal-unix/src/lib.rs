@@ -109,7 +109,10 @@ impl PageTable {
         debug_assert!(page_aligned(vaddr));
         let prot = flags.to_mmap_prot();
         let ret = unsafe { libc::mprotect(vaddr as _, PAGE_SIZE, prot) };
-        assert_eq!(ret, 0, "failed to mprotect: {:?}", Error::last_os_error());
+        // fail with ENOMEM if not mapped
+        if ret != 0 {
+            return Err(());
+        }
         Ok(())
     }
 
@@ -173,6 +176,37 @@ impl Drop for PhysFrame {
     }
 }
 
+/// Get physical memory usage. The heap is managed by the host.
+#[export_name = "hal_kmem_stats"]
+pub fn kmem_stats() -> KmemStats {
+    KmemStats {
+        total_bytes: PMEM_SIZE - PAGE_SIZE,
+        free_bytes: AVAILABLE_FRAMES.lock().unwrap().len() * PAGE_SIZE,
+        ..Default::default()
+    }
+}
+
+/// Count the runs of free frames by length.
+#[export_name = "hal_frame_fragmentation"]
+pub fn frame_fragmentation() -> FragReport {
+    let mut frames: Vec<usize> = AVAILABLE_FRAMES.lock().unwrap().iter().copied().collect();
+    frames.sort_unstable();
+    let mut report = FragReport::default();
+    let mut i = 0;
+    while i < frames.len() {
+        let mut run = 1;
+        while i + run < frames.len() && frames[i + run] == frames[i] + run * PAGE_SIZE {
+            run += 1;
+        }
+        let bucket = (core::mem::size_of::<usize>() * 8 - 1 - run.leading_zeros() as usize)
+            .min(FRAG_BUCKETS - 1);
+        report.free_runs[bucket] += 1;
+        report.largest_free_run = report.largest_free_run.max(run);
+        i += run;
+    }
+    report
+}
+
 fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
     /// Map physical memory from here.
     const PMEM_BASE: VirtAddr = 0x8_00000000;
@@ -359,6 +393,12 @@ pub fn timer_now() -> Duration {
         .unwrap()
 }
 
+/// Get the raw ticks of the TSC.
+#[export_name = "hal_timer_ticks"]
+pub fn timer_ticks() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
 /// Set a new timer.
 ///
 /// After `deadline`, the `callback` will be called.
@@ -373,6 +413,18 @@ pub fn timer_set(deadline: Duration, callback: Box<dyn FnOnce(Duration) + Send +
     });
 }
 
+/// Get the ID of the current CPU.
+#[export_name = "hal_cpu_id"]
+pub fn cpu_id() -> u8 {
+    0
+}
+
+/// User code runs in this process, so its page faults can not be caught.
+#[export_name = "hal_user_page_fault_supported"]
+pub fn user_page_fault_supported() -> bool {
+    false
+}
+
 #[export_name = "hal_vdso_constants"]
 pub fn vdso_constants() -> VdsoConstants {
     let tsc_frequency = 3000u16;
@@ -433,4 +485,13 @@ mod tests {
 
         pt.unmap(VBASE + 0x1000).unwrap();
     }
+
+    #[test]
+    fn fragmentation() {
+        let report = frame_fragmentation();
+        let free_frames = kmem_stats().free_bytes / PAGE_SIZE;
+        assert!(report.free_runs.iter().sum::<usize>() >= 1);
+        assert!(report.largest_free_run >= 1);
+        assert!(report.largest_free_run <= free_frames + 1);
+    }
 }
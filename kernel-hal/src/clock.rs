@@ -0,0 +1,48 @@
+//! Clocks built on the HAL timer.
+
+use crate::timer_now;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+/// The latest monotonic time returned, in nanoseconds.
+static LAST_MONOTONIC: AtomicU64 = AtomicU64::new(0);
+
+/// Get the monotonic time.
+///
+/// Unlike `timer_now`, it never goes backwards, even if the timers of CPUs
+/// are out of sync or the host clock is adjusted.
+pub fn clock_monotonic() -> Duration {
+    let now = timer_now().as_nanos() as u64;
+    Duration::from_nanos(advance(&LAST_MONOTONIC, now))
+}
+
+/// Move `last` forward to `now`, and return the new value.
+fn advance(last: &AtomicU64, now: u64) -> u64 {
+    let mut prev = last.load(Ordering::SeqCst);
+    while prev < now {
+        match last.compare_exchange_weak(prev, now, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => return now,
+            Err(current) => prev = current,
+        }
+    }
+    prev
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monotonic() {
+        let last = AtomicU64::new(0);
+        let mut prev = 0;
+        for i in 0..10000u64 {
+            // a timer jumping back and forth around the real time
+            let now = i * 100 + (i * 7919 % 13) * 50;
+            let time = advance(&last, now);
+            assert!(time >= prev);
+            assert!(time >= now);
+            prev = time;
+        }
+    }
+}
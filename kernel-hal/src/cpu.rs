@@ -0,0 +1,123 @@
+use crate::cpu_id;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+/// The maximum number of CPUs with statistics.
+pub const MAX_CPU_NUM: usize = 16;
+
+/// Statistics counters of a CPU.
+///
+/// All counters use relaxed atomics, so they can be read without stopping the CPU.
+#[derive(Debug, Default)]
+pub struct CpuStats {
+    idle_time: AtomicU64,
+    reschedules: AtomicU64,
+    context_switches: AtomicU64,
+    interrupts: AtomicU64,
+    syscalls: AtomicU64,
+    /// The thread run last, 0 if none.
+    last_thread: AtomicU64,
+}
+
+/// Values of `CpuStats` counters read at some point in time.
+///
+/// Counters are read one by one, so they may be slightly out of sync.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CpuStatsSnapshot {
+    /// Time spent idling.
+    pub idle_time: Duration,
+    pub reschedules: u64,
+    pub context_switches: u64,
+    pub interrupts: u64,
+    pub syscalls: u64,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const CPU_STATS_INIT: CpuStats = CpuStats {
+    idle_time: AtomicU64::new(0),
+    reschedules: AtomicU64::new(0),
+    context_switches: AtomicU64::new(0),
+    interrupts: AtomicU64::new(0),
+    syscalls: AtomicU64::new(0),
+    last_thread: AtomicU64::new(0),
+};
+
+static CPU_STATS: [CpuStats; MAX_CPU_NUM] = [CPU_STATS_INIT; MAX_CPU_NUM];
+
+impl CpuStats {
+    /// Get statistics of the current CPU.
+    pub fn current() -> &'static CpuStats {
+        &CPU_STATS[cpu_id() as usize % MAX_CPU_NUM]
+    }
+
+    /// Get statistics of CPU `cpu`.
+    pub fn get(cpu: usize) -> Option<&'static CpuStats> {
+        CPU_STATS.get(cpu)
+    }
+
+    /// Account `time` spent idling, called by the idle loop.
+    pub fn add_idle_time(&self, time: Duration) {
+        self.idle_time
+            .fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn inc_reschedules(&self) {
+        self.reschedules.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that the CPU is going to run thread `tid`, called before each
+    /// entry to user mode.
+    ///
+    /// A context switch is counted only if it ran a different thread last.
+    /// Return whether it is a context switch.
+    pub fn switch_to(&self, tid: u64) -> bool {
+        if self.last_thread.swap(tid, Ordering::Relaxed) == tid {
+            return false;
+        }
+        self.context_switches.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    pub fn inc_interrupts(&self) {
+        self.interrupts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_syscalls(&self) {
+        self.syscalls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read all counters.
+    pub fn snapshot(&self) -> CpuStatsSnapshot {
+        CpuStatsSnapshot {
+            idle_time: Duration::from_nanos(self.idle_time.load(Ordering::Relaxed)),
+            reschedules: self.reschedules.load(Ordering::Relaxed),
+            context_switches: self.context_switches.load(Ordering::Relaxed),
+            interrupts: self.interrupts.load(Ordering::Relaxed),
+            syscalls: self.syscalls.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_switches() {
+        let stats = CpuStats::default();
+        assert!(stats.switch_to(1024));
+        // back to user mode of the same thread, e.g. after a syscall
+        assert!(!stats.switch_to(1024));
+        assert!(stats.switch_to(1025));
+        assert!(stats.switch_to(1024));
+        assert_eq!(stats.snapshot().context_switches, 3);
+    }
+
+    #[test]
+    fn idle_time() {
+        let stats = CpuStats::default();
+        stats.add_idle_time(Duration::from_millis(3));
+        stats.add_idle_time(Duration::from_micros(500));
+        assert_eq!(stats.snapshot().idle_time, Duration::from_micros(3500));
+    }
+}
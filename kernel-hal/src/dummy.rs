@@ -78,7 +78,7 @@ impl PageTable {
     pub fn unmap(&mut self, _vaddr: VirtAddr) -> Result<(), ()> {
         unimplemented!()
     }
-    /// Change the `flags` of the page of `vaddr`.
+    /// Change the `flags` of the page of `vaddr`. Fail if it is not mapped.
     #[linkage = "weak"]
     #[export_name = "hal_pt_protect"]
     pub fn protect(&mut self, _vaddr: VirtAddr, _flags: MMUFlags) -> Result<(), ()> {
@@ -164,6 +164,31 @@ impl Drop for PhysFrame {
     }
 }
 
+/// Get physical memory and kernel heap usage.
+#[linkage = "weak"]
+#[export_name = "hal_kmem_stats"]
+pub fn kmem_stats() -> KmemStats {
+    unimplemented!()
+}
+
+/// Add `page_count` physical frames from `phys_start` after boot.
+///
+/// Fails if the range overlaps known memory or is beyond the supported range.
+#[linkage = "weak"]
+#[export_name = "hal_add_memory_region"]
+pub fn add_memory_region(_phys_start: PhysAddr, _page_count: usize) -> Result<(), ()> {
+    unimplemented!()
+}
+
+/// Get the free runs of physical frames.
+///
+/// It scans all frames, so it is for debugging only.
+#[linkage = "weak"]
+#[export_name = "hal_frame_fragmentation"]
+pub fn frame_fragmentation() -> FragReport {
+    unimplemented!()
+}
+
 /// Read physical memory from `paddr` to `buf`.
 #[linkage = "weak"]
 #[export_name = "hal_pmem_read"]
@@ -227,6 +252,15 @@ pub fn timer_now() -> Duration {
     unimplemented!()
 }
 
+/// Get the raw ticks of the high-resolution timer.
+///
+/// There are `ticks_per_second` of `vdso_constants` ticks in a second.
+#[linkage = "weak"]
+#[export_name = "hal_timer_ticks"]
+pub fn timer_ticks() -> u64 {
+    unimplemented!()
+}
+
 /// Set a new timer. After `deadline`, the `callback` will be called.
 #[linkage = "weak"]
 #[export_name = "hal_timer_set"]
@@ -248,6 +282,27 @@ pub fn irq_handle(_irq: u8) {
     unimplemented!()
 }
 
+/// Get the ID of the current CPU.
+#[linkage = "weak"]
+#[export_name = "hal_cpu_id"]
+pub fn cpu_id() -> u8 {
+    unimplemented!()
+}
+
+/// Send an IPI to the CPU `cpu_id` to handle TLB shootdown requests.
+#[linkage = "weak"]
+#[export_name = "hal_send_tlb_shootdown_ipi"]
+pub fn send_tlb_shootdown_ipi(_cpu_id: u8) {
+    unimplemented!()
+}
+
+/// Flush all non-global TLB entries of the current CPU.
+#[linkage = "weak"]
+#[export_name = "hal_flush_tlb"]
+pub fn flush_tlb() {
+    unimplemented!()
+}
+
 /// Get platform specific information.
 #[linkage = "weak"]
 #[export_name = "hal_vdso_constants"]
@@ -261,3 +316,13 @@ pub fn vdso_constants() -> VdsoConstants {
 pub fn fetch_fault_vaddr() -> VirtAddr {
     unimplemented!()
 }
+
+/// Whether page faults on user memory are resolved, both from user mode and
+/// in the kernel through `user::handle_page_fault`.
+///
+/// Otherwise user pages must be committed and mapped before they are accessed.
+#[linkage = "weak"]
+#[export_name = "hal_user_page_fault_supported"]
+pub fn user_page_fault_supported() -> bool {
+    unimplemented!()
+}
@@ -2,6 +2,7 @@
 
 #![no_std]
 #![feature(linkage)]
+#![feature(const_in_array_repeat_expressions)]
 #![deny(warnings)]
 
 extern crate alloc;
@@ -19,6 +20,7 @@ pub mod defs {
             const WRITE     = 1 << 3;
             const EXECUTE   = 1 << 4;
             const USER      = 1 << 5;
+            const RXW       = Self::READ.bits | Self::WRITE.bits | Self::EXECUTE.bits;
         }
     }
     numeric_enum! {
@@ -33,17 +35,46 @@ pub mod defs {
     }
     pub const CACHE_POLICY_MASK: u32 = 3;
 
+    /// Physical memory and kernel heap usage, in bytes.
+    #[repr(C)]
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct KmemStats {
+        pub total_bytes: usize,
+        pub free_bytes: usize,
+        pub total_heap_bytes: usize,
+        pub free_heap_bytes: usize,
+    }
+
+    /// Number of buckets in `FragReport::free_runs`.
+    pub const FRAG_BUCKETS: usize = 32;
+
+    /// Free runs of physical frames, by length.
+    #[repr(C)]
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct FragReport {
+        /// `free_runs[i]` is the number of free runs of `2^i..2^(i+1)` frames.
+        pub free_runs: [usize; FRAG_BUCKETS],
+        /// Length of the longest free run in frames.
+        pub largest_free_run: usize,
+    }
+
     pub type PhysAddr = usize;
     pub type VirtAddr = usize;
     pub const PAGE_SIZE: usize = 0x1000;
 }
 
+mod clock;
+mod cpu;
 mod dummy;
 mod future;
+mod tlb;
 pub mod user;
 pub mod vdso;
 
+pub use self::clock::*;
+pub use self::cpu::*;
 pub use self::defs::*;
 pub use self::dummy::*;
 pub use self::future::*;
+pub use self::tlb::*;
 pub use trapframe::{GeneralRegs, UserContext, VectorRegs};
@@ -0,0 +1,191 @@
+//! TLB shootdown across CPUs.
+//!
+//! After changing a page table, the CPU doing it only flushes its own TLB.
+//! Other CPUs running on the same page table are asked by an IPI to flush
+//! theirs, and the change is complete once all of them have done so.
+//!
+//! Page table changes only queue the shootdown, since a CPU waiting for it
+//! with the page table locked can deadlock with one that takes the lock in a
+//! trap with interrupts off. The queue is flushed once the locks are dropped,
+//! with one shootdown for all pages changed meanwhile.
+
+use crate::{cpu_id, flush_tlb, send_tlb_shootdown_ipi, PhysAddr, MAX_CPU_NUM};
+use core::sync::atomic::{spin_loop_hint, AtomicUsize, Ordering};
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+/// The root page table active on each CPU, or 0 if unknown.
+static ACTIVE: [AtomicUsize; MAX_CPU_NUM] = [ZERO; MAX_CPU_NUM];
+/// The number of shootdown requests sent to each CPU.
+static REQUESTED: [AtomicUsize; MAX_CPU_NUM] = [ZERO; MAX_CPU_NUM];
+/// The number of shootdown requests each CPU has handled.
+static DONE: [AtomicUsize; MAX_CPU_NUM] = [ZERO; MAX_CPU_NUM];
+/// The page table with a shootdown queued by each CPU, 0 if none, or
+/// `ALL_PAGE_TABLES` if more than one.
+static PENDING: [AtomicUsize; MAX_CPU_NUM] = [ZERO; MAX_CPU_NUM];
+
+/// Shoot down the TLB entries of all page tables.
+const ALL_PAGE_TABLES: PhysAddr = PhysAddr::max_value();
+
+fn current_cpu() -> usize {
+    cpu_id() as usize % MAX_CPU_NUM
+}
+
+/// Record that the current CPU switched to the page table at `root`.
+pub fn set_active_page_table(root: PhysAddr) {
+    ACTIVE[current_cpu()].store(root, Ordering::SeqCst);
+}
+
+/// Queue a shootdown for the page table at `root`, done by the next
+/// `flush_tlb_shootdown` on this CPU.
+///
+/// Call it after changing the page table and flushing the local TLB.
+pub fn queue_tlb_shootdown(root: PhysAddr) {
+    queue(current_cpu(), root);
+}
+
+/// Do the shootdown queued by this CPU, if any.
+///
+/// Call it without holding any lock that a trap handler may take.
+pub fn flush_tlb_shootdown() {
+    let root = take_pending(current_cpu());
+    if root != 0 {
+        tlb_shootdown(root);
+    }
+}
+
+/// Make other CPUs using the page table at `root` flush their TLB,
+/// and wait until they have done so.
+///
+/// Call it after changing the page table and flushing the local TLB,
+/// without holding any lock that a trap handler may take.
+pub fn tlb_shootdown(root: PhysAddr) {
+    let current = current_cpu();
+    let mut waiting = [0usize; MAX_CPU_NUM];
+    for (cpu, target) in post(current, root) {
+        send_tlb_shootdown_ipi(cpu as u8);
+        waiting[cpu] = target;
+    }
+    for (cpu, &target) in waiting.iter().enumerate() {
+        while target != 0 && !is_done(cpu, target) {
+            // others may be waiting for us at the same time
+            handle_tlb_shootdown();
+            spin_loop_hint();
+        }
+    }
+}
+
+/// Handle shootdown requests to the current CPU, called on the IPI.
+pub fn handle_tlb_shootdown() {
+    handle(current_cpu(), flush_tlb);
+}
+
+fn queue(cpu: usize, root: PhysAddr) {
+    let pending = &PENDING[cpu];
+    if let Err(other) = pending.compare_exchange(0, root, Ordering::SeqCst, Ordering::SeqCst) {
+        if other != root {
+            pending.store(ALL_PAGE_TABLES, Ordering::SeqCst);
+        }
+    }
+}
+
+fn take_pending(cpu: usize) -> PhysAddr {
+    PENDING[cpu].swap(0, Ordering::SeqCst)
+}
+
+/// Post a request to each other CPU using `root`, or any page table if it is
+/// `ALL_PAGE_TABLES`.
+///
+/// Return the CPUs and the request number to wait for.
+fn post(current: usize, root: PhysAddr) -> impl Iterator<Item = (usize, usize)> {
+    (0..MAX_CPU_NUM)
+        .filter(move |&cpu| {
+            let active = ACTIVE[cpu].load(Ordering::SeqCst);
+            cpu != current && active != 0 && (root == ALL_PAGE_TABLES || active == root)
+        })
+        .map(|cpu| (cpu, REQUESTED[cpu].fetch_add(1, Ordering::SeqCst) + 1))
+}
+
+fn handle(cpu: usize, flush: impl FnOnce()) {
+    let requested = REQUESTED[cpu].load(Ordering::SeqCst);
+    if DONE[cpu].load(Ordering::SeqCst) == requested {
+        return;
+    }
+    flush();
+    // an IPI may come during the flush and handle a newer request first
+    let mut done = DONE[cpu].load(Ordering::SeqCst);
+    while done < requested {
+        match DONE[cpu].compare_exchange(done, requested, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => break,
+            Err(current) => done = current,
+        }
+    }
+}
+
+fn is_done(cpu: usize, target: usize) -> bool {
+    DONE[cpu].load(Ordering::SeqCst) >= target
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn shootdown() {
+        const ROOT: PhysAddr = 0x1000;
+        ACTIVE[1].store(ROOT, Ordering::SeqCst);
+        ACTIVE[2].store(0x2000, Ordering::SeqCst);
+        ACTIVE[3].store(ROOT, Ordering::SeqCst);
+
+        // only other CPUs on the same page table are asked
+        let posted: Vec<_> = post(1, ROOT).collect();
+        assert_eq!(posted, [(3, 1)]);
+        let posted: Vec<_> = post(0, ROOT).collect();
+        assert_eq!(posted, [(1, 1), (3, 2)]);
+
+        // CPU 3 handles both requests with one flush
+        let mut flushes = 0;
+        handle(3, || flushes += 1);
+        handle(3, || flushes += 1);
+        assert_eq!(flushes, 1);
+        assert!(is_done(3, 2));
+
+        // the stale entries of CPU 1 are still there until it flushes
+        assert!(!is_done(1, 1));
+        handle(1, || flushes += 1);
+        assert!(is_done(1, 1));
+        assert_eq!(flushes, 2);
+    }
+
+    #[test]
+    fn queued_shootdown() {
+        const ROOT: PhysAddr = 0x3000;
+        ACTIVE[5].store(ROOT, Ordering::SeqCst);
+        ACTIVE[6].store(ROOT, Ordering::SeqCst);
+        // the TLB of CPU 6 still maps the pages unmapped by CPU 5
+        let mut stale = true;
+
+        // changing two pages only queues the shootdown
+        queue(5, ROOT);
+        queue(5, ROOT);
+        assert_eq!(REQUESTED[6].load(Ordering::SeqCst), 0);
+
+        // and one request is posted once the queue is flushed
+        let root = take_pending(5);
+        assert_eq!(root, ROOT);
+        let posted: Vec<_> = post(5, root).collect();
+        assert_eq!(posted, [(6, 1)]);
+        assert_eq!(take_pending(5), 0);
+        assert!(!is_done(6, 1));
+        handle(6, || stale = false);
+        assert!(!stale);
+        assert!(is_done(6, 1));
+
+        // changes to different page tables shoot down all of them
+        queue(5, ROOT);
+        queue(5, 0x4000);
+        assert_eq!(take_pending(5), ALL_PAGE_TABLES);
+    }
+}
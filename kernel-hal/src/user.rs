@@ -1,7 +1,9 @@
+use crate::{MMUFlags, VirtAddr};
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter};
 use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 #[repr(C)]
 pub struct UserPtr<T, P: Policy> {
@@ -78,6 +80,19 @@ impl<T, P: Policy> UserPtr<T, P> {
         }
         Ok(())
     }
+
+    /// Check that `count` elements from the pointer are user memory
+    /// accessible with `flags`, so the kernel doesn't fault on them.
+    fn check_range(&self, count: usize, flags: MMUFlags) -> Result<()> {
+        self.check()?;
+        let len = count
+            .checked_mul(core::mem::size_of::<T>())
+            .ok_or(Error::InvalidPointer)?;
+        if !check_user_range(self.ptr as VirtAddr, len, flags) {
+            return Err(Error::InvalidPointer);
+        }
+        Ok(())
+    }
 }
 
 impl<T, P: Read> UserPtr<T, P> {
@@ -86,8 +101,7 @@ impl<T, P: Read> UserPtr<T, P> {
     }
 
     pub fn read(&self) -> Result<T> {
-        // TODO: check ptr and return err
-        self.check()?;
+        self.check_range(1, MMUFlags::READ)?;
         Ok(unsafe { self.ptr.read() })
     }
 
@@ -103,7 +117,7 @@ impl<T, P: Read> UserPtr<T, P> {
         if len == 0 {
             return Ok(Vec::default());
         }
-        self.check()?;
+        self.check_range(len, MMUFlags::READ)?;
         let mut ret = Vec::<T>::with_capacity(len);
         unsafe {
             ret.set_len(len);
@@ -115,7 +129,7 @@ impl<T, P: Read> UserPtr<T, P> {
 
 impl<P: Read> UserPtr<u8, P> {
     pub fn read_string(&self, len: usize) -> Result<String> {
-        self.check()?;
+        self.check_range(len, MMUFlags::READ)?;
         let src = unsafe { core::slice::from_raw_parts(self.ptr, len) };
         let s = core::str::from_utf8(src).map_err(|_| Error::InvalidUtf8)?;
         Ok(String::from(s))
@@ -145,7 +159,7 @@ impl<P: Read> UserPtr<UserPtr<u8, P>, P> {
 
 impl<T, P: Write> UserPtr<T, P> {
     pub fn write(&mut self, value: T) -> Result<()> {
-        self.check()?;
+        self.check_range(1, MMUFlags::WRITE)?;
         unsafe {
             self.ptr.write(value);
         }
@@ -163,7 +177,7 @@ impl<T, P: Write> UserPtr<T, P> {
         if values.is_empty() {
             return Ok(());
         }
-        self.check()?;
+        self.check_range(values.len(), MMUFlags::WRITE)?;
         unsafe {
             self.ptr
                 .copy_from_nonoverlapping(values.as_ptr(), values.len());
@@ -175,6 +189,7 @@ impl<T, P: Write> UserPtr<T, P> {
 impl<P: Write> UserPtr<u8, P> {
     pub fn write_cstring(&mut self, s: &str) -> Result<()> {
         let bytes = s.as_bytes();
+        self.check_range(bytes.len() + 1, MMUFlags::WRITE)?;
         self.write_array(bytes)?;
         unsafe {
             self.ptr.add(bytes.len()).write(0);
@@ -182,3 +197,82 @@ impl<P: Write> UserPtr<u8, P> {
         Ok(())
     }
 }
+
+/// Resolve a page fault the kernel raised at `vaddr` of user memory on an
+/// access of `flags`. Returns false if the fault can not be resolved.
+pub type PageFaultHandler = fn(VirtAddr, MMUFlags) -> bool;
+
+/// The `PageFaultHandler` set by `set_page_fault_handler`, 0 if not set.
+static PAGE_FAULT_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the handler of page faults the kernel raises on user memory,
+/// e.g. in `UserPtr::read` on a page not committed yet.
+pub fn set_page_fault_handler(handler: PageFaultHandler) {
+    PAGE_FAULT_HANDLER.store(handler as usize, Ordering::Release);
+}
+
+/// Check if `[vaddr, vaddr + len)` is user memory accessible with `flags`.
+pub type UserRangeChecker = fn(VirtAddr, usize, MMUFlags) -> bool;
+
+/// The `UserRangeChecker` set by `set_user_range_checker`, 0 if not set.
+static USER_RANGE_CHECKER: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the checker of user memory the kernel is about to access through
+/// `UserPtr`. Without one, only null pointers are rejected.
+pub fn set_user_range_checker(checker: UserRangeChecker) {
+    USER_RANGE_CHECKER.store(checker as usize, Ordering::Release);
+}
+
+fn check_user_range(vaddr: VirtAddr, len: usize, flags: MMUFlags) -> bool {
+    match USER_RANGE_CHECKER.load(Ordering::Acquire) {
+        0 => true,
+        checker => {
+            let checker: UserRangeChecker = unsafe { core::mem::transmute(checker) };
+            checker(vaddr, len, flags)
+        }
+    }
+}
+
+/// Handle a page fault the kernel raised on user memory.
+///
+/// Called by the HAL implementation in its trap handler.
+pub fn handle_page_fault(vaddr: VirtAddr, flags: MMUFlags) -> bool {
+    match PAGE_FAULT_HANDLER.load(Ordering::Acquire) {
+        0 => false,
+        handler => {
+            let handler: PageFaultHandler = unsafe { core::mem::transmute(handler) };
+            handler(vaddr, flags)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static mut BUF: [u8; 16] = [0; 16];
+
+    /// Only `BUF` is user memory, and it is read-only.
+    fn checker(vaddr: VirtAddr, len: usize, flags: MMUFlags) -> bool {
+        let start = unsafe { BUF.as_ptr() as VirtAddr };
+        !flags.contains(MMUFlags::WRITE) && start <= vaddr && vaddr + len <= start + 16
+    }
+
+    #[test]
+    fn bad_pointer() {
+        set_user_range_checker(checker);
+        let start = unsafe { BUF.as_ptr() as usize };
+
+        let ptr = UserInPtr::<u8>::from(start);
+        assert_eq!(ptr.read_array(16), Ok(alloc::vec![0; 16]));
+        assert_eq!(ptr.read_array(17).err(), Some(Error::InvalidPointer));
+        assert_eq!(ptr.add(16).read().err(), Some(Error::InvalidPointer));
+
+        // non-null pointers to read-only or unmapped memory are rejected
+        let mut ptr = UserOutPtr::<u8>::from(start);
+        assert_eq!(ptr.write_cstring("name"), Err(Error::InvalidPointer));
+        let mut ptr = UserOutPtr::<u8>::from(0x1000);
+        assert_eq!(ptr.write_cstring("name"), Err(Error::InvalidPointer));
+        assert_eq!(unsafe { BUF }, [0; 16]);
+    }
+}
@@ -17,7 +17,7 @@ use {
         thread::ThreadExt,
     },
     linux_syscall::Syscall,
-    zircon_object::task::*,
+    zircon_object::{object::KernelObject, task::*},
 };
 
 pub fn run(
@@ -53,9 +53,13 @@ fn spawn(thread: Arc<Thread>) {
         loop {
             let mut cx = thread.wait_for_run().await;
             trace!("go to user: {:#x?}", cx);
+            kernel_hal::CpuStats::current().switch_to(thread.id());
+            // for the page tables changed by the last syscall or fault
+            kernel_hal::flush_tlb_shootdown();
             kernel_hal::context_run(&mut cx);
             trace!("back from user: {:#x?}", cx);
             assert_eq!(cx.trap_num, 0x100, "user interrupt still no support");
+            kernel_hal::CpuStats::current().inc_syscalls();
             let exit = handle_syscall(&thread, &mut cx.general).await;
             thread.end_running(cx);
             if exit {
@@ -0,0 +1,44 @@
+//! aarch64 specific memory layout and translation table helpers,
+//! using 4KB granule and 48-bit virtual address.
+//!
+//! NOTE: the rest of the port is missing, so zCore refuses to build for
+//! aarch64 (see `main.rs`).
+
+/// Physical memory above this limit is ignored by the frame allocator.
+pub const MAX_PHYS_MEMORY: usize = 1 << 40; // 1 TB
+
+/// It must be able to hold `MAX_PHYS_MEMORY / PAGE_SIZE` frames.
+pub type FrameAlloc = bitmap_allocator::BitAlloc256M;
+
+pub const MEMORY_OFFSET: usize = 0;
+pub const KERNEL_OFFSET: usize = 0xffff0000_00000000;
+/// The default base of the physical memory mapping, if the bootloader does not tell.
+pub const PHYSICAL_MEMORY_OFFSET: usize = 0xffff8000_00000000;
+/// Each level 0 entry maps 512GB.
+pub const PHYSICAL_MEMORY_ALIGN: usize = 1 << 39;
+
+/// Descriptor is valid.
+const DESC_VALID: u64 = 1 << 0;
+/// Descriptor points to a next level table, rather than a block.
+const DESC_TABLE: u64 = 1 << 1;
+
+/// A level 0 translation table.
+#[repr(C, align(4096))]
+pub struct PageTable {
+    entries: [u64; 512],
+}
+
+/// Copy the level 0 entries of the kernel and physical memory
+/// from `current` to `pt`, which will be loaded into `TTBR1_EL1`.
+#[no_mangle]
+pub extern "C" fn hal_pt_map_kernel(pt: &mut PageTable, current: &PageTable) {
+    // the entry translated by `TTBR1_EL1` mapping the kernel
+    let kernel_l0 = (super::kernel_base() >> 39) & 0o777;
+    let physical_memory_l0 = (super::pmem_base() >> 39) & 0o777;
+    for &i in [kernel_l0, physical_memory_l0].iter() {
+        let entry = current.entries[i];
+        // level 0 entries can only be table descriptors
+        debug_assert_eq!(entry & (DESC_VALID | DESC_TABLE), DESC_VALID | DESC_TABLE);
+        pt.entries[i] = entry;
+    }
+}
@@ -0,0 +1,32 @@
+//! riscv64 specific memory layout and page table helpers, using Sv39.
+//!
+//! NOTE: the kernel still takes its memory map from rboot's `BootInfo`, and
+//! the riscv HAL has no timer or serial backend yet, so zCore does not boot
+//! on riscv64 until a loader passes a `BootInfo` and those are added.
+
+use riscv::paging::{PageTable, PageTableFlags as EF};
+
+/// Physical memory above this limit is ignored by the frame allocator.
+pub const MAX_PHYS_MEMORY: usize = 1 << 34; // 16 GB
+
+/// It must be able to hold `MAX_PHYS_MEMORY / PAGE_SIZE` frames.
+pub type FrameAlloc = bitmap_allocator::BitAlloc16M;
+
+pub const MEMORY_OFFSET: usize = 0;
+pub const KERNEL_OFFSET: usize = 0xffffffff_c0000000;
+/// The default base of the physical memory mapping, if the bootloader does not tell.
+pub const PHYSICAL_MEMORY_OFFSET: usize = 0xffffffff_40000000;
+/// Each root entry of Sv39 maps 1GB.
+pub const PHYSICAL_MEMORY_ALIGN: usize = 1 << 30;
+
+#[no_mangle]
+pub extern "C" fn hal_pt_map_kernel(pt: &mut PageTable, current: &PageTable) {
+    // physical memory is mapped from its base up to the end of the address
+    // space, which also covers the kernel
+    let kernel_pm = (super::kernel_base() >> 30) & 0o777;
+    let physical_memory_pm = (super::pmem_base() >> 30) & 0o777;
+    for i in physical_memory_pm..=kernel_pm {
+        let entry = current[i].clone();
+        pt[i].set(entry.frame(), entry.flags() | EF::GLOBAL);
+    }
+}
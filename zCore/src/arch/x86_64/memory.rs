@@ -0,0 +1,51 @@
+//! x86_64 specific memory layout and page table helpers.
+
+use x86_64::structures::paging::page_table::{PageTable, PageTableFlags as EF};
+
+/// Physical memory above this limit is ignored by the frame allocator.
+pub const MAX_PHYS_MEMORY: usize = 1 << 40; // 1 TB
+
+/// It must be able to hold `MAX_PHYS_MEMORY / PAGE_SIZE` frames.
+pub type FrameAlloc = bitmap_allocator::BitAlloc256M;
+
+pub const MEMORY_OFFSET: usize = 0;
+pub const KERNEL_OFFSET: usize = 0xffffff00_00000000;
+/// The default base of the physical memory mapping, if the bootloader does not tell.
+pub const PHYSICAL_MEMORY_OFFSET: usize = 0xffff8000_00000000;
+/// Each PML4 entry maps 512GB.
+pub const PHYSICAL_MEMORY_ALIGN: usize = 1 << 39;
+
+#[no_mangle]
+pub extern "C" fn hal_pt_map_kernel(pt: &mut PageTable, current: &PageTable) {
+    let kernel_pm4 = (super::kernel_base() >> 39) & 0o777;
+    let physical_memory_pm4 = (super::pmem_base() >> 39) & 0o777;
+    let ekernel = current[kernel_pm4].clone();
+    let ephysical = current[physical_memory_pm4].clone();
+    pt[kernel_pm4].set_addr(ekernel.addr(), ekernel.flags() | EF::GLOBAL);
+    pt[physical_memory_pm4].set_addr(ephysical.addr(), ephysical.flags() | EF::GLOBAL);
+}
+
+/// Map physical memory at `offset` too, by sharing the PML4 entry of the
+/// physical memory mapping in the current page table.
+#[cfg(feature = "self-test")]
+pub fn map_pmem_alias(offset: usize) {
+    let root = current_root();
+    let entry = root[(super::pmem_base() >> 39) & 0o777].clone();
+    let alias = &mut root[(offset >> 39) & 0o777];
+    assert!(alias.is_unused(), "{:#x} is already mapped", offset);
+    *alias = entry;
+}
+
+/// Undo `map_pmem_alias`.
+#[cfg(feature = "self-test")]
+pub fn unmap_pmem_alias(offset: usize) {
+    current_root()[(offset >> 39) & 0o777].set_unused();
+    x86_64::instructions::tlb::flush_all();
+}
+
+#[cfg(feature = "self-test")]
+fn current_root() -> &'static mut PageTable {
+    let (frame, _) = x86_64::registers::control::Cr3::read();
+    let vaddr = super::pmem_base() + frame.start_address().as_u64() as usize;
+    unsafe { &mut *(vaddr as *mut PageTable) }
+}
@@ -16,6 +16,6 @@ fn panic(info: &PanicInfo) -> ! {
 }
 
 #[lang = "oom"]
-fn oom(_: Layout) -> ! {
-    panic!("out of memory");
+fn oom(layout: Layout) -> ! {
+    panic!("out of memory: {:?}", layout);
 }
@@ -2,6 +2,7 @@
 #![no_main]
 #![feature(lang_items)]
 #![feature(asm)]
+#![feature(const_in_array_repeat_expressions)]
 #![feature(panic_info_message)]
 #![deny(unused_must_use)]
 #![deny(warnings)] // comment this on develop
@@ -11,25 +12,44 @@ extern crate alloc;
 extern crate log;
 extern crate rlibc;
 
+// Only the memory layout and `hal_pt_map_kernel` are ported to aarch64: there
+// is no HAL backend (page tables, `cpu_id`, timer), no `wait_for_interrupt`,
+// and no loader passing rboot's `BootInfo`.
+#[cfg(target_arch = "aarch64")]
+compile_error!("zCore does not support aarch64 yet");
+
 #[macro_use]
 mod logging;
 mod lang;
 mod memory;
+mod pressure;
 
 use rboot::BootInfo;
 
-pub use memory::{hal_frame_alloc, hal_frame_dealloc, hal_pt_map_kernel};
+pub use memory::{
+    add_memory_region, frame_fragmentation, hal_frame_alloc, hal_frame_alloc_aligned,
+    hal_frame_alloc_contiguous, hal_frame_alloc_huge, hal_frame_dealloc,
+    hal_frame_dealloc_contiguous, hal_frame_dealloc_huge, hal_pt_map_kernel, kmem_stats,
+};
+#[cfg(feature = "frame-snapshot")]
+pub use memory::{restore, snapshot, FrameSnapshot};
 use zircon_loader::{run_userboot, Images};
 
 #[no_mangle]
 pub extern "C" fn _start(boot_info: &BootInfo) -> ! {
     logging::init(get_log_level(boot_info.cmdline));
+    let heap_size = get_heap_size(boot_info.cmdline);
     memory::init_heap();
     memory::init_frame_allocator(boot_info);
+    memory::grow_heap_to(heap_size);
     #[cfg(feature = "graphic")]
     init_framebuffer(boot_info);
     info!("{:#x?}", boot_info);
     kernel_hal_bare::init();
+    #[cfg(feature = "self-test")]
+    memory::self_test::run(boot_info);
+    #[cfg(feature = "self-test")]
+    cmdline_self_test(boot_info.cmdline);
 
     let zbi_data = unsafe {
         core::slice::from_raw_parts(
@@ -48,21 +68,109 @@ fn main(zbi_data: &[u8], cmdline: &str) {
         decompressor: include_bytes!("../../prebuilt/zircon/decompress-zstd.so"),
         zbi: zbi_data,
     };
-    let _proc = run_userboot(&images, cmdline);
-    executor::run();
+    pressure::init();
+    let proc = run_userboot(&images, cmdline);
+    executor::spawn(memory::heap_shrinker());
+    executor::spawn(pressure::low_memory_handler(proc.job()));
+    run();
+}
+
+/// Run the ready tasks, and halt the CPU until the next interrupt when there
+/// is none, accounting the time halted as idle time of the CPU.
+fn run() -> ! {
+    loop {
+        executor::run_until_idle();
+        let start = kernel_hal::timer_now();
+        wait_for_interrupt();
+        let idle = kernel_hal::timer_now().checked_sub(start);
+        kernel_hal::CpuStats::current().add_idle_time(idle.unwrap_or_default());
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn wait_for_interrupt() {
+    x86_64::instructions::interrupts::enable_and_hlt();
+    x86_64::instructions::interrupts::disable();
+}
+
+#[cfg(target_arch = "riscv64")]
+fn wait_for_interrupt() {
+    unsafe {
+        riscv::register::sstatus::set_sie();
+        riscv::asm::wfi();
+        riscv::register::sstatus::clear_sie();
+    }
 }
 
 fn get_log_level(cmdline: &str) -> &str {
+    get_option(cmdline, "LOG").unwrap_or("")
+}
+
+/// Get the initial heap size from `HEAP_SIZE=<bytes>[K|M|G]`, 8MB by default.
+fn get_heap_size(cmdline: &str) -> usize {
+    const DEFAULT_HEAP_SIZE: usize = 8 * 1024 * 1024; // 8 MB
+    let value = match get_option(cmdline, "HEAP_SIZE") {
+        Some(value) => value,
+        None => return DEFAULT_HEAP_SIZE,
+    };
+    let (number, shift) = match value.as_bytes().last() {
+        Some(b'K') | Some(b'k') => (&value[..value.len() - 1], 10),
+        Some(b'M') | Some(b'm') => (&value[..value.len() - 1], 20),
+        Some(b'G') | Some(b'g') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    // reject sizes which do not fit in a `usize` once scaled
+    let size = number
+        .parse::<usize>()
+        .ok()
+        .filter(|size| size.leading_zeros() >= shift)
+        .and_then(|size| size.checked_shl(shift));
+    match size {
+        Some(size) => size,
+        None => {
+            warn!("invalid HEAP_SIZE: {:?}", value);
+            DEFAULT_HEAP_SIZE
+        }
+    }
+}
+
+/// Get the value of `key` from the command line of format `key=value:key=value`.
+fn get_option<'a>(cmdline: &'a str, key: &str) -> Option<&'a str> {
     for opt in cmdline.split(':') {
         // parse 'key=value'
         let mut iter = opt.trim().splitn(2, '=');
-        let key = iter.next().expect("failed to parse key");
+        let k = iter.next().expect("failed to parse key");
         let value = iter.next().expect("failed to parse value");
-        if key == "LOG" {
-            return value;
+        if k == key {
+            return Some(value);
         }
     }
-    ""
+    None
+}
+
+/// The heap size is parsed from the command line, with the default kept for
+/// a missing or invalid value, and applied at boot.
+#[cfg(feature = "self-test")]
+fn cmdline_self_test(cmdline: &str) {
+    const MB: usize = 1024 * 1024;
+    assert_eq!(get_heap_size("LOG=warn"), 8 * MB);
+    assert_eq!(get_heap_size("HEAP_SIZE=65536"), 65536);
+    assert_eq!(get_heap_size("HEAP_SIZE=4k"), 4096);
+    assert_eq!(get_heap_size("LOG=warn:HEAP_SIZE=16M"), 16 * MB);
+    assert_eq!(get_heap_size("HEAP_SIZE=1G:LOG=warn"), 1024 * MB);
+    assert_eq!(get_heap_size("HEAP_SIZE=lots"), 8 * MB);
+    assert_eq!(get_heap_size("HEAP_SIZE=M"), 8 * MB);
+    assert_eq!(get_heap_size("HEAP_SIZE=17179869184G"), 8 * MB);
+    assert_eq!(get_heap_size("HEAP_SIZE=8589934591G"), 8589934591 << 30);
+
+    assert!(memory::heap_total_bytes() >= get_heap_size(cmdline));
+    // a larger size than the current one grows the heap to it
+    let size = memory::heap_total_bytes() / 1024 + 4096;
+    let size = get_heap_size(&alloc::format!("HEAP_SIZE={}K", size));
+    memory::grow_heap_to(size);
+    assert!(memory::heap_total_bytes() >= size);
+    assert!(memory::heap_total_bytes() < size + kernel_hal::PAGE_SIZE);
+    info!("cmdline self tests passed");
 }
 
 #[cfg(feature = "graphic")]
@@ -1,109 +1,997 @@
 //! Define the FrameAllocator for physical memory
-//! x86_64      --  64GB
+//! x86_64      --  1TB
+//! riscv64     --  16GB
+//! aarch64     --  1TB (the layout only, zCore does not build for aarch64 yet)
 
 use {
     bitmap_allocator::BitAlloc,
     buddy_system_allocator::{Heap, LockedHeapWithRescue},
+    core::alloc::Layout,
+    core::sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    core::time::Duration,
+    kernel_hal::{FragReport, KmemStats, FRAG_BUCKETS, MAX_CPU_NUM},
     rboot::{BootInfo, MemoryType},
     spin::Mutex,
-    x86_64::structures::paging::page_table::{PageTable, PageTableFlags as EF},
 };
 
 #[cfg(target_arch = "x86_64")]
-type FrameAlloc = bitmap_allocator::BitAlloc16M;
+#[path = "arch/x86_64/memory.rs"]
+mod arch;
+#[cfg(target_arch = "riscv64")]
+#[path = "arch/riscv64/memory.rs"]
+mod arch;
+#[cfg(target_arch = "aarch64")]
+#[path = "arch/aarch64/memory.rs"]
+mod arch;
+
+pub use self::arch::hal_pt_map_kernel;
+use self::arch::*;
+
+#[cfg(feature = "self-test")]
+pub mod self_test;
 
 static FRAME_ALLOCATOR: Mutex<FrameAlloc> = Mutex::new(FrameAlloc::DEFAULT);
 
-const MEMORY_OFFSET: usize = 0;
-const KERNEL_OFFSET: usize = 0xffffff00_00000000;
-const PHYSICAL_MEMORY_OFFSET: usize = 0xffff8000_00000000;
-const KERNEL_HEAP_SIZE: usize = 8 * 1024 * 1024; // 8 MB
+/// Number of frames inserted into `FRAME_ALLOCATOR`.
+static TOTAL_FRAMES: AtomicUsize = AtomicUsize::new(0);
+/// Number of frames currently free in `FRAME_ALLOCATOR`.
+static FREE_FRAMES: AtomicUsize = AtomicUsize::new(0);
+/// The lowest frame inserted into `FRAME_ALLOCATOR`.
+///
+/// `MIN_FRAME` and `MAX_FRAME` are only changed with `FRAME_ALLOCATOR` locked,
+/// and are read without locks when frames are freed.
+static MIN_FRAME: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// The highest (exclusive) frame inserted into `FRAME_ALLOCATOR`.
+static MAX_FRAME: AtomicUsize = AtomicUsize::new(0);
+/// The memory regions inserted into `FRAME_ALLOCATOR`, to reject overlapping hot-add.
+static MEMORY_REGIONS: Mutex<MemoryRegions> = Mutex::new(MemoryRegions::EMPTY);
+
+/// Called once when free frames drop below `LOW_MEMORY_WATERMARK_PERCENT`.
+static LOW_MEMORY_CALLBACK: Mutex<Option<fn()>> = Mutex::new(None);
+/// Whether free frames are below the watermark, so the callback will not be called again.
+static LOW_MEMORY: AtomicBool = AtomicBool::new(false);
+
+/// Called when the heap cannot grow, before the allocation fails.
+static OOM_CALLBACK: Mutex<Option<fn()>> = Mutex::new(None);
+
+/// `(paddr, size)` of the aligned chunks added by `enlarge_heap`, zero size for empty slots.
+static HEAP_CHUNKS: Mutex<[(usize, usize); MAX_HEAP_CHUNKS]> =
+    Mutex::new([(0, 0); MAX_HEAP_CHUNKS]);
+/// Bytes given back by `shrink_heap`, which the heap still counts as allocated.
+static HEAP_RETURNED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The static heap used before the frame allocator is ready.
+const BOOT_HEAP_SIZE: usize = 64 * 1024; // 64 KB
+const MIN_KERNEL_HEAP_SIZE: usize = 1024 * 1024; // 1 MB
 
-const KERNEL_PM4: usize = (KERNEL_OFFSET >> 39) & 0o777;
-const PHYSICAL_MEMORY_PM4: usize = (PHYSICAL_MEMORY_OFFSET >> 39) & 0o777;
+/// The heap grows by at least `1 << HEAP_GROWTH_FRAMES_LOG2` frames at a time.
+const HEAP_GROWTH_FRAMES_LOG2: usize = 14; // 64 MB
+const MAX_HEAP_CHUNKS: usize = 64;
+const HEAP_SHRINK_INTERVAL: Duration = Duration::from_secs(10);
 
 const PAGE_SIZE: usize = 1 << 12;
+const HUGE_PAGE_FRAMES_LOG2: usize = 9; // 2 MB
+const MAX_FRAMES: usize = MAX_PHYS_MEMORY / PAGE_SIZE;
 
+const MAX_MEMORY_REGIONS: usize = 128;
+
+const FRAME_CACHE_SIZE: usize = 64;
+const FRAME_CACHE_BATCH: usize = FRAME_CACHE_SIZE / 2;
+/// Matches the `Warning` level of `MemoryPressureEvent`.
+const LOW_MEMORY_WATERMARK_PERCENT: usize = 10;
+
+zircon_object::kcounter!(OOM_EVENTS, "memory.oom");
+zircon_object::kcounter!(FRAMES_ALLOCED, "memory.frames_alloced");
+zircon_object::kcounter!(FRAMES_FREED, "memory.frames_freed");
+zircon_object::kcounter!(HEAP_RESCUES, "memory.heap_rescue");
+zircon_object::kcounter!(FRAME_CACHE_REFILLS, "memory.frame_cache_refills");
+zircon_object::kcounter!(FRAME_CACHE_FLUSHES, "memory.frame_cache_flushes");
+
+/// The base of the physical memory mapping.
+///
+/// It is `PHYSICAL_MEMORY_OFFSET` unless the bootloader maps physical memory elsewhere.
 #[used]
 #[export_name = "hal_pmem_base"]
-static PMEM_BASE: usize = PHYSICAL_MEMORY_OFFSET;
+static PMEM_BASE: AtomicUsize = AtomicUsize::new(PHYSICAL_MEMORY_OFFSET);
+
+/// Get the base of the physical memory mapping.
+fn pmem_base() -> usize {
+    PMEM_BASE.load(Ordering::Relaxed)
+}
+
+/// The physical address of frame 0 of `FRAME_ALLOCATOR`.
+///
+/// It is `MEMORY_OFFSET` unless physical memory starts higher.
+static MEMORY_BASE: AtomicUsize = AtomicUsize::new(MEMORY_OFFSET);
+/// Physical memory starts at a multiple of this.
+const MEMORY_BASE_ALIGN: usize = 1 << 30; // 1 GB
+
+/// The base of the root page table entries mapping the kernel.
+///
+/// It is where `KERNEL_OFFSET` is unless the kernel is loaded elsewhere.
+static KERNEL_BASE: AtomicUsize = AtomicUsize::new(KERNEL_OFFSET);
+
+/// Get the physical address of frame 0 of the frame allocator.
+fn memory_base() -> usize {
+    MEMORY_BASE.load(Ordering::Relaxed)
+}
+
+/// Get the base of the root page table entries mapping the kernel.
+fn kernel_base() -> usize {
+    KERNEL_BASE.load(Ordering::Relaxed)
+}
+
+/// Get the physical address of frame `id`.
+fn frame_to_phys(id: usize) -> usize {
+    id * PAGE_SIZE + memory_base()
+}
+
+/// Use the physical memory mapping at `offset` set up by the bootloader,
+/// or keep the default if it is 0.
+///
+/// Panics if `offset` is not aligned to the memory mapped by a root page table entry,
+/// since the mapping is shared with user page tables by those entries.
+fn init_pmem_base(offset: usize) {
+    if offset == 0 || offset == PHYSICAL_MEMORY_OFFSET {
+        return;
+    }
+    assert!(
+        set_pmem_base(offset),
+        "physical memory offset {:#x} is not aligned to {:#x}",
+        offset,
+        PHYSICAL_MEMORY_ALIGN
+    );
+    info!("Physical memory is mapped at {:#x}", offset);
+}
+
+/// Set the base of the physical memory mapping for the kernel and the HAL.
+///
+/// Returns false and keeps the old base if `offset` is not aligned to
+/// `PHYSICAL_MEMORY_ALIGN`.
+fn set_pmem_base(offset: usize) -> bool {
+    if offset % PHYSICAL_MEMORY_ALIGN != 0 {
+        return false;
+    }
+    PMEM_BASE.store(offset, Ordering::Relaxed);
+    true
+}
+
+/// Number the frames from the lowest conventional memory in `boot_info`,
+/// so the frame allocator doesn't waste its bitmap on the hole below.
+fn init_memory_base(boot_info: &BootInfo) {
+    let lowest = boot_info
+        .memory_map
+        .clone()
+        .iter
+        .filter(|region| region.ty == MemoryType::CONVENTIONAL)
+        .map(|region| region.phys_start as usize)
+        .min();
+    let base = match lowest {
+        Some(lowest) => lowest & !(MEMORY_BASE_ALIGN - 1),
+        None => return,
+    };
+    if base != MEMORY_OFFSET {
+        info!("Physical memory starts at {:#x}", base);
+        MEMORY_BASE.store(base, Ordering::Relaxed);
+    }
+}
+
+/// Find the root page table entries mapping the kernel from where it runs.
+fn init_kernel_base() {
+    let base = init_kernel_base as usize & !(PHYSICAL_MEMORY_ALIGN - 1);
+    if base != kernel_base() {
+        info!("Kernel is loaded at {:#x}", base);
+        KERNEL_BASE.store(base, Ordering::Relaxed);
+    }
+}
 
 pub fn init_frame_allocator(boot_info: &BootInfo) {
+    init_pmem_base(boot_info.physical_memory_offset as usize);
+    init_memory_base(boot_info);
+    init_kernel_base();
+    let regions: alloc::vec::Vec<BootRegion> = boot_info
+        .memory_map
+        .clone()
+        .iter
+        .map(|region| {
+            let start = region.phys_start as usize;
+            BootRegion {
+                start,
+                end: start + region.page_count as usize * PAGE_SIZE,
+                ty: region.ty,
+            }
+        })
+        .collect();
+    add_boot_regions(&mut FRAME_ALLOCATOR.lock(), &regions);
+    info!("Frame allocator init end");
+}
+
+/// `[start, end)` bytes of physical memory in the boot memory map.
+#[derive(Debug, Clone, Copy)]
+struct BootRegion {
+    start: usize,
+    end: usize,
+    ty: MemoryType,
+}
+
+/// Give the conventional memory in `regions` to `ba`, up to `MAX_PHYS_MEMORY`
+/// above `memory_base()`, and record all of `regions` as known memory.
+fn add_boot_regions(ba: &mut FrameAlloc, regions: &[BootRegion]) {
+    let base = memory_base();
+    for region in regions {
+        let (start, end) = (region.start, region.end);
+        // all memory in the map is known, so it can not be hot-added again
+        let start_frame = start.saturating_sub(base) / PAGE_SIZE;
+        let end_frame = (end.saturating_sub(base) / PAGE_SIZE).min(MAX_FRAMES);
+        if start_frame < end_frame && !MEMORY_REGIONS.lock().insert(start_frame, end_frame) {
+            warn!("Too many memory regions, no memory can be hot-added");
+        }
+        match region.ty {
+            MemoryType::CONVENTIONAL => {
+                // `base` is not above any conventional memory
+                let start_frame = (start - base) / PAGE_SIZE;
+                let end_frame = (end - base) / PAGE_SIZE;
+                if end_frame > MAX_FRAMES {
+                    warn!(
+                        "Ignore memory beyond {:#x}: {:#x?}",
+                        base + MAX_PHYS_MEMORY,
+                        start..end
+                    );
+                }
+                let end_frame = end_frame.min(MAX_FRAMES);
+                if start_frame >= end_frame {
+                    continue;
+                }
+                insert_frames(ba, start_frame, end_frame);
+            }
+            // firmware tables and reserved ranges must never be handed out
+            MemoryType::ACPI_RECLAIM | MemoryType::ACPI_NON_VOLATILE | MemoryType::RESERVED => {
+                info!("Skip {:?} memory: {:#x?}", region.ty, start..end);
+            }
+            // the kernel image and boot data are loaded here by the bootloader
+            MemoryType::LOADER_CODE | MemoryType::LOADER_DATA => {
+                info!("Skip kernel memory: {:#x?}", start..end);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Add `page_count` frames from `phys_start` to the frame allocator after boot,
+/// e.g. memory from hotplug or a balloon device.
+///
+/// The range must already be covered by the physical memory mapping.
+/// It is rejected if it overlaps the memory in the boot memory map or added
+/// before, or goes beyond `MAX_PHYS_MEMORY`. If some memory could not be
+/// recorded, every range is rejected.
+#[export_name = "hal_add_memory_region"]
+pub fn add_memory_region(phys_start: usize, page_count: usize) -> Result<(), ()> {
+    if phys_start % PAGE_SIZE != 0 || page_count == 0 {
+        return Err(());
+    }
+    let start_frame = match phys_start.checked_sub(memory_base()) {
+        Some(offset) => offset / PAGE_SIZE,
+        None => {
+            warn!(
+                "Reject memory below {:#x}: {:#x}",
+                memory_base(),
+                phys_start
+            );
+            return Err(());
+        }
+    };
+    let end_frame = start_frame.checked_add(page_count).ok_or(())?;
+    let range = frame_to_phys(start_frame)..frame_to_phys(end_frame);
+    if end_frame > MAX_FRAMES {
+        warn!(
+            "Reject memory beyond {:#x}: {:#x?}",
+            memory_base() + MAX_PHYS_MEMORY,
+            range
+        );
+        return Err(());
+    }
     let mut ba = FRAME_ALLOCATOR.lock();
-    for region in boot_info.memory_map.clone().iter {
-        if region.ty == MemoryType::CONVENTIONAL {
-            let start_frame = region.phys_start as usize / PAGE_SIZE;
-            let end_frame = start_frame + region.page_count as usize;
-            ba.insert(start_frame..end_frame);
+    let mut regions = MEMORY_REGIONS.lock();
+    if regions.overlaps(start_frame, end_frame) {
+        warn!("Reject memory overlapping known memory: {:#x?}", range);
+        return Err(());
+    }
+    if !regions.insert(start_frame, end_frame) {
+        warn!("Reject memory, too many memory regions: {:#x?}", range);
+        return Err(());
+    }
+    drop(regions);
+    insert_frames(&mut ba, start_frame, end_frame);
+    info!("Add memory region: {:#x?}", range);
+    Ok(())
+}
+
+/// Give frames `[start_frame, end_frame)` to the frame allocator.
+fn insert_frames(ba: &mut FrameAlloc, start_frame: usize, end_frame: usize) {
+    ba.insert(start_frame..end_frame);
+    TOTAL_FRAMES.fetch_add(end_frame - start_frame, Ordering::Relaxed);
+    FREE_FRAMES.fetch_add(end_frame - start_frame, Ordering::Relaxed);
+    // `ba` is locked, so there are no concurrent updates
+    if start_frame < MIN_FRAME.load(Ordering::Relaxed) {
+        MIN_FRAME.store(start_frame, Ordering::Release);
+    }
+    if end_frame > MAX_FRAME.load(Ordering::Relaxed) {
+        MAX_FRAME.store(end_frame, Ordering::Release);
+    }
+}
+
+/// Get the lowest and highest (exclusive) frame given to the frame allocator.
+fn frame_range() -> (usize, usize) {
+    (
+        MIN_FRAME.load(Ordering::Acquire),
+        MAX_FRAME.load(Ordering::Acquire),
+    )
+}
+
+/// `[start, end)` frames of the memory regions known at boot or hot-added.
+struct MemoryRegions {
+    regions: [(usize, usize); MAX_MEMORY_REGIONS],
+    len: usize,
+    /// A region could not be recorded, so any range may overlap.
+    overflowed: bool,
+}
+
+impl MemoryRegions {
+    const EMPTY: Self = MemoryRegions {
+        regions: [(0, 0); MAX_MEMORY_REGIONS],
+        len: 0,
+        overflowed: false,
+    };
+
+    fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.overflowed
+            || self.regions[..self.len]
+                .iter()
+                .any(|&(s, e)| start < e && s < end)
+    }
+
+    /// Record a region, extending an adjacent one if possible.
+    ///
+    /// Returns false if there are no free slots. Then every range overlaps from
+    /// now on, since the region is unknown.
+    fn insert(&mut self, start: usize, end: usize) -> bool {
+        for region in self.regions[..self.len].iter_mut() {
+            if region.1 == start {
+                region.1 = end;
+                return true;
+            }
+            if region.0 == end {
+                region.0 = start;
+                return true;
+            }
         }
+        if self.len == MAX_MEMORY_REGIONS {
+            self.overflowed = true;
+            return false;
+        }
+        self.regions[self.len] = (start, end);
+        self.len += 1;
+        true
     }
-    info!("Frame allocator init end");
 }
 
+/// Initialize the heap with a small static region to boot with.
+///
+/// The heap is grown by `grow_heap_to` after the frame allocator is ready.
 pub fn init_heap() {
     const MACHINE_ALIGN: usize = core::mem::size_of::<usize>();
-    const HEAP_BLOCK: usize = KERNEL_HEAP_SIZE / MACHINE_ALIGN;
+    const HEAP_BLOCK: usize = BOOT_HEAP_SIZE / MACHINE_ALIGN;
     static mut HEAP: [usize; HEAP_BLOCK] = [0; HEAP_BLOCK];
     unsafe {
         HEAP_ALLOCATOR
             .lock()
-            .init(HEAP.as_ptr() as usize, HEAP_BLOCK * MACHINE_ALIGN);
+            .init(HEAP.as_ptr() as usize, BOOT_HEAP_SIZE);
     }
     info!("heap init end");
 }
 
+/// Grow the heap to `size` bytes, at least `MIN_KERNEL_HEAP_SIZE`, with frames.
+///
+/// Nothing is done if the heap is already large enough.
+pub fn grow_heap_to(size: usize) {
+    let size = size.max(MIN_KERNEL_HEAP_SIZE);
+    let total = heap_total_bytes();
+    if total >= size {
+        return;
+    }
+    let frames = (size - total + PAGE_SIZE - 1) / PAGE_SIZE;
+    match hal_frame_alloc_contiguous(frames, 0) {
+        Some(paddr) => {
+            info!(
+                "Adding {:#X} {:#X} to heap",
+                phys_to_virt(paddr),
+                frames * PAGE_SIZE
+            );
+            unsafe {
+                HEAP_ALLOCATOR
+                    .lock()
+                    .init(phys_to_virt(paddr), frames * PAGE_SIZE);
+            }
+        }
+        // the heap still grows on demand in `enlarge_heap`
+        None => warn!("failed to grow heap to {:#x} bytes", size),
+    }
+    info!("heap size: {:#x}", heap_total_bytes());
+}
+
+/// Get the bytes in the heap, not including the chunks given back by `shrink_heap`.
+pub fn heap_total_bytes() -> usize {
+    HEAP_ALLOCATOR.lock().stats_total_bytes() - HEAP_RETURNED_BYTES.load(Ordering::Relaxed)
+}
+
 #[no_mangle]
 pub extern "C" fn hal_frame_alloc() -> Option<usize> {
     // get the real address of the alloc frame
-    let ret = FRAME_ALLOCATOR
-        .lock()
-        .alloc()
-        .map(|id| id * PAGE_SIZE + MEMORY_OFFSET);
+    let ret = local_frame_cache().lock().alloc().map(frame_to_phys);
     trace!("Allocate frame: {:x?}", ret);
+    if let Some(paddr) = ret {
+        FRAMES_ALLOCED.add(1);
+        zero_frames(paddr, 1);
+    }
+    check_low_memory();
     ret
 }
 
 #[no_mangle]
 pub extern "C" fn hal_frame_dealloc(target: &usize) {
     trace!("Deallocate frame: {:x}", *target);
-    FRAME_ALLOCATOR
+    let id = match checked_frame_id(*target, 1) {
+        Some(id) => id,
+        None => return,
+    };
+    check_dealloc(*target, 1);
+    FRAMES_FREED.add(1);
+    local_frame_cache().lock().dealloc(id);
+}
+
+/// A per-CPU magazine of free frames.
+///
+/// It exchanges frames with `FRAME_ALLOCATOR` in batches, so most allocations
+/// don't take the global lock. Cached frames are not counted in `FREE_FRAMES`.
+struct FrameCache {
+    frames: [usize; FRAME_CACHE_SIZE],
+    len: usize,
+}
+
+impl FrameCache {
+    const EMPTY: Self = FrameCache {
+        frames: [0; FRAME_CACHE_SIZE],
+        len: 0,
+    };
+
+    fn alloc(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            self.refill();
+        }
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(self.frames[self.len])
+    }
+
+    fn dealloc(&mut self, id: usize) {
+        if self.len == FRAME_CACHE_SIZE {
+            self.flush();
+        }
+        self.frames[self.len] = id;
+        self.len += 1;
+    }
+
+    /// Take a batch of frames from the global allocator.
+    fn refill(&mut self) {
+        FRAME_CACHE_REFILLS.add(1);
+        let mut ba = FRAME_ALLOCATOR.lock();
+        let old_len = self.len;
+        while self.len < FRAME_CACHE_BATCH {
+            match ba.alloc() {
+                Some(id) => {
+                    self.frames[self.len] = id;
+                    self.len += 1;
+                }
+                None => break,
+            }
+        }
+        FREE_FRAMES.fetch_sub(self.len - old_len, Ordering::Relaxed);
+    }
+
+    /// Give a batch of frames back to the global allocator.
+    fn flush(&mut self) {
+        FRAME_CACHE_FLUSHES.add(1);
+        let mut ba = FRAME_ALLOCATOR.lock();
+        let count = self.len.min(FRAME_CACHE_BATCH);
+        for _ in 0..count {
+            self.len -= 1;
+            ba.dealloc(self.frames[self.len]);
+        }
+        FREE_FRAMES.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const FRAME_CACHE_INIT: Mutex<FrameCache> = Mutex::new(FrameCache::EMPTY);
+
+static FRAME_CACHES: [Mutex<FrameCache>; MAX_CPU_NUM] = [FRAME_CACHE_INIT; MAX_CPU_NUM];
+
+/// Get the frame cache of the current CPU.
+fn local_frame_cache() -> &'static Mutex<FrameCache> {
+    &FRAME_CACHES[kernel_hal::cpu_id() as usize % MAX_CPU_NUM]
+}
+
+/// Get the id of the first of `frame_count` frames from `target` to free,
+/// if they are all in the range given to the frame allocator.
+///
+/// A bad range is a bug of the caller, so it panics in debug builds.
+/// In release builds it is logged and ignored, to keep the bitmap intact.
+fn checked_frame_id(target: usize, frame_count: usize) -> Option<usize> {
+    let (min_frame, max_frame) = frame_range();
+    let id = frame_id_in_range(target, frame_count, min_frame, max_frame);
+    if id.is_none() {
+        if cfg!(debug_assertions) {
+            panic!(
+                "free frame {:#x} out of range, count={}",
+                target, frame_count
+            );
+        }
+        error!(
+            "Ignore freeing frames out of range: {:#x}, count={}",
+            target, frame_count
+        );
+    }
+    id
+}
+
+/// Get the id of the first of `frame_count` frames from `target`,
+/// if they are all in `[min_frame, max_frame)`.
+fn frame_id_in_range(
+    target: usize,
+    frame_count: usize,
+    min_frame: usize,
+    max_frame: usize,
+) -> Option<usize> {
+    target
+        .checked_sub(memory_base())
+        .map(|offset| offset / PAGE_SIZE)
+        .filter(|&id| id >= min_frame && max_frame.checked_sub(id) >= Some(frame_count))
+}
+
+/// Check that `frame_count` frames from `target` are allocated and can be freed.
+///
+/// It catches double free, which would corrupt the allocator silently.
+/// The range must have been checked by `checked_frame_id`.
+#[cfg(debug_assertions)]
+fn check_dealloc(target: usize, frame_count: usize) {
+    assert_eq!(target % PAGE_SIZE, 0, "free unaligned frame {:#x}", target);
+    let start = (target - memory_base()) / PAGE_SIZE;
+    let ba = FRAME_ALLOCATOR.lock();
+    for id in start..start + frame_count {
+        assert!(
+            !ba.test(id),
+            "double free of frame {:#x}",
+            frame_to_phys(id)
+        );
+    }
+    drop(ba);
+    for cache in FRAME_CACHES.iter() {
+        let cache = cache.lock();
+        for &id in cache.frames[..cache.len].iter() {
+            assert!(
+                !(start..start + frame_count).contains(&id),
+                "double free of frame {:#x}",
+                frame_to_phys(id)
+            );
+        }
+    }
+}
+
+#[cfg(not(debug_assertions))]
+fn check_dealloc(_target: usize, _frame_count: usize) {}
+
+/// Allocate `frame_count` physically contiguous frames,
+/// with the first frame aligned to `1 << align_log2` frames.
+///
+/// Either all the frames are allocated or none.
+#[no_mangle]
+pub extern "C" fn hal_frame_alloc_contiguous(
+    frame_count: usize,
+    align_log2: usize,
+) -> Option<usize> {
+    let ret = FRAME_ALLOCATOR
         .lock()
-        .dealloc((*target - MEMORY_OFFSET) / PAGE_SIZE);
+        .alloc_contiguous(frame_count, align_log2)
+        .map(|id| {
+            FREE_FRAMES.fetch_sub(frame_count, Ordering::Relaxed);
+            frame_to_phys(id)
+        });
+    trace!(
+        "Allocate contiguous frames: {:x?}, count={}, align_log2={}",
+        ret,
+        frame_count,
+        align_log2
+    );
+    if let Some(paddr) = ret {
+        FRAMES_ALLOCED.add(frame_count);
+        zero_frames(paddr, frame_count);
+    }
+    check_low_memory();
+    ret
+}
+
+/// Free `frame_count` contiguous frames allocated by `hal_frame_alloc_contiguous`.
+#[no_mangle]
+pub extern "C" fn hal_frame_dealloc_contiguous(target: usize, frame_count: usize) {
+    trace!(
+        "Deallocate contiguous frames: {:x}, count={}",
+        target,
+        frame_count
+    );
+    let start = match checked_frame_id(target, frame_count) {
+        Some(id) => id,
+        None => return,
+    };
+    check_dealloc(target, frame_count);
+    FRAMES_FREED.add(frame_count);
+    let mut ba = FRAME_ALLOCATOR.lock();
+    for id in start..start + frame_count {
+        ba.dealloc(id);
+    }
+    FREE_FRAMES.fetch_add(frame_count, Ordering::Relaxed);
 }
 
+/// Allocate a single frame aligned to `1 << align_log2` frames.
+///
+/// Returns `None` if no such frame is free.
 #[no_mangle]
-pub extern "C" fn hal_pt_map_kernel(pt: &mut PageTable, current: &PageTable) {
-    let ekernel = current[KERNEL_PM4].clone();
-    let ephysical = current[PHYSICAL_MEMORY_PM4].clone();
-    pt[KERNEL_PM4].set_addr(ekernel.addr(), ekernel.flags() | EF::GLOBAL);
-    pt[PHYSICAL_MEMORY_PM4].set_addr(ephysical.addr(), ephysical.flags() | EF::GLOBAL);
-}
-
-fn enlarge_heap(heap: &mut Heap) {
-    error!("Enlarging heap to avoid oom");
-
-    let mut addrs = [(0, 0); 32];
-    let mut addr_len = 0;
-    let va_offset = PMEM_BASE;
-    for _ in 0..16384 {
-        let page = hal_frame_alloc().unwrap();
-        let va = va_offset + page;
-        if addr_len > 0 {
-            let (ref mut addr, ref mut len) = addrs[addr_len - 1];
-            if *addr - PAGE_SIZE == va {
-                *len += PAGE_SIZE;
-                *addr -= PAGE_SIZE;
-                continue;
+pub extern "C" fn hal_frame_alloc_aligned(align_log2: usize) -> Option<usize> {
+    hal_frame_alloc_contiguous(1, align_log2)
+}
+
+/// Allocate a 2MB-aligned huge page made of contiguous frames.
+#[no_mangle]
+pub extern "C" fn hal_frame_alloc_huge() -> Option<usize> {
+    hal_frame_alloc_contiguous(1 << HUGE_PAGE_FRAMES_LOG2, HUGE_PAGE_FRAMES_LOG2)
+}
+
+/// Free a huge page allocated by `hal_frame_alloc_huge`.
+#[no_mangle]
+pub extern "C" fn hal_frame_dealloc_huge(target: usize) {
+    debug_assert_eq!(target % (PAGE_SIZE << HUGE_PAGE_FRAMES_LOG2), 0);
+    hal_frame_dealloc_contiguous(target, 1 << HUGE_PAGE_FRAMES_LOG2);
+}
+
+/// Set the function to call when free frames drop below the low watermark.
+///
+/// It is called at most once per crossing, and is re-armed after free frames
+/// rise above the watermark again. It runs in the allocation path, so it must
+/// not allocate from the kernel heap.
+pub fn set_low_memory_callback(callback: fn()) {
+    *LOW_MEMORY_CALLBACK.lock() = Some(callback);
+}
+
+/// Set the function to call when the heap runs out of frames to grow.
+///
+/// It runs with the heap locked, right before the failing allocation goes to
+/// the OOM handler, so it must not allocate from the kernel heap.
+pub fn set_oom_callback(callback: fn()) {
+    *OOM_CALLBACK.lock() = Some(callback);
+}
+
+/// Log the state of the frame allocator and the heap when `layout` cannot be
+/// satisfied, then call the OOM callback.
+fn report_oom(heap: &Heap, layout: &Layout) {
+    OOM_EVENTS.add(1);
+    let (free_frames, total_frames) = frame_stats();
+    error!(
+        "out of memory: free_frames={} total_frames={} heap_total={:#x} heap_used={:#x} alloc_size={:#x} alloc_align={:#x}",
+        free_frames,
+        total_frames,
+        heap.stats_total_bytes(),
+        heap.stats_alloc_actual(),
+        layout.size(),
+        layout.align(),
+    );
+    let callback = *OOM_CALLBACK.lock();
+    if let Some(callback) = callback {
+        callback();
+    }
+}
+
+/// Call the low memory callback if free frames first drop below the watermark.
+fn check_low_memory() {
+    let (free_frames, total_frames) = frame_stats();
+    if free_frames * 100 >= total_frames * LOW_MEMORY_WATERMARK_PERCENT {
+        LOW_MEMORY.store(false, Ordering::Relaxed);
+        return;
+    }
+    if LOW_MEMORY.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let callback = *LOW_MEMORY_CALLBACK.lock();
+    if let Some(callback) = callback {
+        callback();
+    }
+}
+
+/// Get the number of `(free, total)` frames in the frame allocator.
+pub fn frame_stats() -> (usize, usize) {
+    (
+        FREE_FRAMES.load(Ordering::Relaxed),
+        TOTAL_FRAMES.load(Ordering::Relaxed),
+    )
+}
+
+#[export_name = "hal_kmem_stats"]
+pub fn kmem_stats() -> KmemStats {
+    let (free_frames, total_frames) = frame_stats();
+    let heap = HEAP_ALLOCATOR.lock();
+    KmemStats {
+        total_bytes: total_frames * PAGE_SIZE,
+        free_bytes: free_frames * PAGE_SIZE,
+        total_heap_bytes: heap.stats_total_bytes() - HEAP_RETURNED_BYTES.load(Ordering::Relaxed),
+        free_heap_bytes: heap.stats_total_bytes() - heap.stats_alloc_actual(),
+    }
+}
+
+/// Count the runs of free frames in the frame allocator by length.
+///
+/// It takes O(total frames) with the frame allocator locked, so it is only
+/// meant for debugging. Frames in the per-CPU caches are counted as used.
+#[export_name = "hal_frame_fragmentation"]
+pub fn frame_fragmentation() -> FragReport {
+    fn add_run(report: &mut FragReport, run: usize) {
+        if run == 0 {
+            return;
+        }
+        let log2 = core::mem::size_of::<usize>() * 8 - 1 - run.leading_zeros() as usize;
+        let bucket = log2.min(FRAG_BUCKETS - 1);
+        report.free_runs[bucket] += 1;
+        report.largest_free_run = report.largest_free_run.max(run);
+    }
+    let (min_frame, max_frame) = frame_range();
+    let ba = FRAME_ALLOCATOR.lock();
+    let mut report = FragReport::default();
+    let mut run = 0;
+    for id in min_frame..max_frame {
+        if ba.test(id) {
+            run += 1;
+        } else {
+            add_run(&mut report, run);
+            run = 0;
+        }
+    }
+    add_run(&mut report, run);
+    report
+}
+
+/// The state of the frame allocator, taken by `snapshot`.
+#[cfg(feature = "frame-snapshot")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSnapshot {
+    /// `[start, end)` frames of the free runs.
+    free_runs: alloc::vec::Vec<(usize, usize)>,
+    free_frames: usize,
+    total_frames: usize,
+}
+
+/// Take the state of the frame allocator, e.g. to find frames leaked by a test.
+///
+/// Frames in the per-CPU caches are given back first, so they count as free.
+/// Like `frame_fragmentation`, it scans all frames.
+#[cfg(feature = "frame-snapshot")]
+pub fn snapshot() -> FrameSnapshot {
+    flush_frame_caches();
+    let (min_frame, max_frame) = frame_range();
+    let mut free_runs = alloc::vec::Vec::new();
+    loop {
+        // The heap grows from the frame allocator, so the runs are only
+        // pushed into reserved space while it is locked, and it is scanned
+        // again with more space if they don't fit.
+        let ba = FRAME_ALLOCATOR.lock();
+        free_runs.clear();
+        let mut runs = 0;
+        for_each_free_run(&ba, min_frame, max_frame, |start, end| {
+            runs += 1;
+            if free_runs.len() < free_runs.capacity() {
+                free_runs.push((start, end));
             }
+        });
+        let free_frames = FREE_FRAMES.load(Ordering::Relaxed);
+        let total_frames = TOTAL_FRAMES.load(Ordering::Relaxed);
+        drop(ba);
+        if free_runs.len() == runs {
+            return FrameSnapshot {
+                free_runs,
+                free_frames,
+                total_frames,
+            };
+        }
+        free_runs.clear();
+        free_runs.reserve(runs + runs / 8 + 1);
+    }
+}
+
+/// Call `f` with `[start, end)` of each free run in `[min_frame, max_frame)`.
+#[cfg(feature = "frame-snapshot")]
+fn for_each_free_run(
+    ba: &FrameAlloc,
+    min_frame: usize,
+    max_frame: usize,
+    mut f: impl FnMut(usize, usize),
+) {
+    let mut start = None;
+    for id in min_frame..max_frame {
+        match (ba.test(id), start) {
+            (true, None) => start = Some(id),
+            (false, Some(s)) => {
+                f(s, id);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        f(s, max_frame);
+    }
+}
+
+/// Restore the frame allocator to the state of `snap`.
+///
+/// Frames allocated after the snapshot must no longer be in use.
+#[cfg(feature = "frame-snapshot")]
+pub fn restore(snap: &FrameSnapshot) {
+    flush_frame_caches();
+    let (min_frame, max_frame) = frame_range();
+    let mut ba = FRAME_ALLOCATOR.lock();
+    ba.remove(min_frame..max_frame);
+    for &(start, end) in snap.free_runs.iter() {
+        ba.insert(start..end);
+    }
+    FREE_FRAMES.store(snap.free_frames, Ordering::Relaxed);
+    TOTAL_FRAMES.store(snap.total_frames, Ordering::Relaxed);
+}
+
+/// Give all frames in the per-CPU caches back to the frame allocator.
+#[cfg(feature = "frame-snapshot")]
+fn flush_frame_caches() {
+    for cache in FRAME_CACHES.iter() {
+        let mut cache = cache.lock();
+        while cache.len != 0 {
+            cache.flush();
         }
-        addrs[addr_len] = (va, PAGE_SIZE);
-        addr_len += 1;
     }
-    for (addr, len) in addrs[..addr_len].iter() {
-        info!("Adding {:#X} {:#X} to heap", addr, len);
+}
+
+/// Get the virtual address of `paddr` in the physical memory mapping.
+///
+/// Panics in debug builds if `paddr` is outside the mapped window.
+fn phys_to_virt(paddr: usize) -> usize {
+    debug_assert!(
+        paddr
+            .checked_sub(memory_base())
+            .map_or(false, |offset| offset < MAX_PHYS_MEMORY),
+        "physical address {:#x} is not in the mapped window {:#x?}",
+        paddr,
+        memory_base()..memory_base() + MAX_PHYS_MEMORY
+    );
+    pmem_base() + paddr
+}
+
+/// Clear `count` frames from `paddr` through the physical memory mapping.
+#[cfg(feature = "zero_on_alloc")]
+fn zero_frames(paddr: usize, count: usize) {
+    unsafe {
+        core::ptr::write_bytes(phys_to_virt(paddr) as *mut u8, 0, count * PAGE_SIZE);
+    }
+}
+
+#[cfg(not(feature = "zero_on_alloc"))]
+fn zero_frames(_paddr: usize, _count: usize) {}
+
+fn enlarge_heap(heap: &mut Heap, layout: &Layout) {
+    error!("Enlarging heap to avoid oom: {:?}", layout);
+    HEAP_RESCUES.add(1);
+
+    // An aligned contiguous chunk is a single buddy block, so it can be given back later.
+    let frames_log2 = heap_growth_frames_log2(layout);
+    let bytes = PAGE_SIZE << frames_log2;
+    if let Some(paddr) = hal_frame_alloc_contiguous(1 << frames_log2, frames_log2) {
+        if let Some(slot) = HEAP_CHUNKS.lock().iter_mut().find(|(_, len)| *len == 0) {
+            *slot = (paddr, bytes);
+        }
+        info!("Adding {:#X} {:#X} to heap", phys_to_virt(paddr), bytes);
         unsafe {
-            heap.init(*addr, *len);
+            heap.init(phys_to_virt(paddr), bytes);
         }
+        return;
+    }
+
+    // the frames may be scattered, so each run is added once it is closed
+    let mut run: Option<(usize, usize)> = None;
+    for _ in 0..1 << frames_log2 {
+        let page = match hal_frame_alloc() {
+            Some(page) => page,
+            None => {
+                report_oom(heap, layout);
+                break;
+            }
+        };
+        let va = phys_to_virt(page);
+        run = match run {
+            Some((addr, len)) if va + PAGE_SIZE == addr => Some((va, len + PAGE_SIZE)),
+            Some((addr, len)) if addr + len == va => Some((addr, len + PAGE_SIZE)),
+            Some((addr, len)) => {
+                add_to_heap(heap, addr, len);
+                Some((va, PAGE_SIZE))
+            }
+            None => Some((va, PAGE_SIZE)),
+        };
+    }
+    if let Some((addr, len)) = run {
+        add_to_heap(heap, addr, len);
+    }
+}
+
+fn add_to_heap(heap: &mut Heap, addr: usize, len: usize) {
+    info!("Adding {:#X} {:#X} to heap", addr, len);
+    unsafe {
+        heap.init(addr, len);
+    }
+}
+
+/// Get the frame count (in log2) to grow the heap by, so that `layout` fits in one chunk.
+fn heap_growth_frames_log2(layout: &Layout) -> usize {
+    let block_size = layout.size().max(layout.align()).next_power_of_two();
+    let frames = (block_size / PAGE_SIZE).max(1);
+    (frames.trailing_zeros() as usize).max(HEAP_GROWTH_FRAMES_LOG2)
+}
+
+/// Give the idle chunks added by `enlarge_heap` back to the frame allocator.
+pub fn shrink_heap() {
+    let mut heap = HEAP_ALLOCATOR.lock();
+    let mut chunks = HEAP_CHUNKS.lock();
+    let snapshot = *chunks;
+    for &(_, size) in snapshot.iter() {
+        if size == 0 || heap.stats_total_bytes() - heap.stats_alloc_actual() < size {
+            continue;
+        }
+        // Take a whole free chunk out of the heap. It stays allocated from
+        // the heap's point of view, so its frames can be reused safely.
+        let layout = Layout::from_size_align(size, size).unwrap();
+        let ptr = match heap.alloc(layout) {
+            Ok(ptr) => ptr,
+            Err(_) => continue,
+        };
+        let paddr = ptr.as_ptr() as usize - pmem_base();
+        match chunks
+            .iter_mut()
+            .find(|&&mut (addr, len)| addr == paddr && len == size)
+        {
+            Some(chunk) => {
+                *chunk = (0, 0);
+                info!(
+                    "Removing {:#X} {:#X} from heap",
+                    ptr.as_ptr() as usize,
+                    size
+                );
+                hal_frame_dealloc_contiguous(paddr, size / PAGE_SIZE);
+                HEAP_RETURNED_BYTES.fetch_add(size, Ordering::Relaxed);
+            }
+            // the block does not come from `enlarge_heap`
+            None => heap.dealloc(ptr, layout),
+        }
+    }
+}
+
+/// Periodically shrink the heap.
+pub async fn heap_shrinker() {
+    loop {
+        kernel_hal::sleep_until(kernel_hal::timer_now() + HEAP_SHRINK_INTERVAL).await;
+        shrink_heap();
     }
 }
 
@@ -0,0 +1,662 @@
+//! Memory self tests, run at boot with the `self-test` feature.
+//!
+//! The kernel can not run `cargo test`, so the parts of the memory manager
+//! that need the real frame allocator are tested here before any user
+//! program starts. A failed test panics.
+
+use {super::*, alloc::vec::Vec};
+
+/// Ends the list of frames built by `exhaust_frames`.
+const END: usize = usize::MAX;
+
+/// Run all memory self tests.
+pub fn run(boot_info: &BootInfo) {
+    heap_from_frames();
+    zero_frame();
+    snapshot_restore();
+    frame_cache();
+    frame_stats_count();
+    huge_page();
+    aligned_frame();
+    #[cfg(feature = "zero_on_alloc")]
+    zero_on_alloc();
+    reserved_regions();
+    boot_reserved_regions(boot_info);
+    frame_range_check();
+    memory_limit();
+    memory_regions();
+    hot_add_region();
+    boot_regions_clamp();
+    memory_base_change();
+    #[cfg(target_arch = "x86_64")]
+    pmem_base_change();
+    heap_growth();
+    heap_shrink();
+    heap_exhaustion();
+    heap_scattered_frames();
+    low_memory_callback();
+    process_exit_frames();
+    info!("memory self tests passed");
+}
+
+/// `restore` gives back the frames allocated after `snapshot`.
+fn snapshot_restore() {
+    // allocate before the snapshot, so the heap doesn't grow in between
+    let mut frames = Vec::with_capacity(64);
+    let snap = snapshot();
+    for _ in 0..64 {
+        frames.push(hal_frame_alloc().expect("failed to alloc frame"));
+    }
+    let contiguous = hal_frame_alloc_contiguous(16, 4).expect("failed to alloc frames");
+    for paddr in frames.iter().step_by(2) {
+        hal_frame_dealloc(paddr);
+    }
+    hal_frame_dealloc_contiguous(contiguous, 16);
+    assert_ne!(snapshot(), snap);
+    // the other half of `frames` is still allocated
+    restore(&snap);
+    assert_eq!(snapshot(), snap);
+}
+
+/// Frames are allocated from and freed to the cache of the current CPU,
+/// which exchanges them with the frame allocator in batches.
+fn frame_cache() {
+    flush_frame_caches();
+    let free_frames = FREE_FRAMES.load(Ordering::Relaxed);
+    let cache = local_frame_cache();
+    let paddr = hal_frame_alloc().expect("failed to alloc frame");
+    assert_eq!(cache.lock().len, FRAME_CACHE_BATCH - 1);
+    assert_eq!(
+        FREE_FRAMES.load(Ordering::Relaxed),
+        free_frames - FRAME_CACHE_BATCH
+    );
+    // a freed frame stays in the cache, and is the next to be allocated
+    hal_frame_dealloc(&paddr);
+    assert_eq!(cache.lock().len, FRAME_CACHE_BATCH);
+    assert_eq!(hal_frame_alloc(), Some(paddr));
+    hal_frame_dealloc(&paddr);
+
+    // a full cache gives a batch back before taking one more frame
+    flush_frame_caches();
+    let (refills, flushes) = (FRAME_CACHE_REFILLS.get(), FRAME_CACHE_FLUSHES.get());
+    let mut frames = [0; FRAME_CACHE_SIZE];
+    for frame in frames.iter_mut() {
+        *frame = hal_frame_alloc().expect("failed to alloc frame");
+    }
+    assert_eq!(cache.lock().len, 0);
+    assert_eq!(
+        FRAME_CACHE_REFILLS.get(),
+        refills + FRAME_CACHE_SIZE / FRAME_CACHE_BATCH
+    );
+    for frame in frames.iter() {
+        hal_frame_dealloc(frame);
+    }
+    assert_eq!(cache.lock().len, FRAME_CACHE_SIZE);
+    assert_eq!(FRAME_CACHE_FLUSHES.get(), flushes);
+    // a burst of allocations and frees within the cache takes no global lock
+    let refills = FRAME_CACHE_REFILLS.get();
+    for _ in 0..1000 {
+        let paddr = hal_frame_alloc().expect("failed to alloc frame");
+        hal_frame_dealloc(&paddr);
+    }
+    assert_eq!(FRAME_CACHE_REFILLS.get(), refills);
+    assert_eq!(FRAME_CACHE_FLUSHES.get(), flushes);
+    // bypass the cache to get one more frame to free
+    let paddr = hal_frame_alloc_contiguous(1, 0).expect("failed to alloc frame");
+    hal_frame_dealloc(&paddr);
+    assert_eq!(cache.lock().len, FRAME_CACHE_SIZE - FRAME_CACHE_BATCH + 1);
+    assert_eq!(FRAME_CACHE_FLUSHES.get(), flushes + 1);
+    flush_frame_caches();
+    assert_eq!(FREE_FRAMES.load(Ordering::Relaxed), free_frames);
+}
+
+/// `frame_stats` counts the frames taken from and given back to the frame
+/// allocator.
+fn frame_stats_count() {
+    let (free_frames, total_frames) = frame_stats();
+    assert!(free_frames <= total_frames);
+    let paddr = hal_frame_alloc_contiguous(4, 0).expect("failed to alloc frames");
+    assert_eq!(frame_stats(), (free_frames - 4, total_frames));
+    hal_frame_dealloc_contiguous(paddr, 4);
+    assert_eq!(frame_stats(), (free_frames, total_frames));
+}
+
+/// Huge pages are 2MB-aligned runs of frames.
+fn huge_page() {
+    let (free_frames, _) = frame_stats();
+    let paddr = hal_frame_alloc_huge().expect("failed to alloc huge page");
+    assert_eq!(paddr % (PAGE_SIZE << HUGE_PAGE_FRAMES_LOG2), 0);
+    assert_eq!(frame_stats().0, free_frames - (1 << HUGE_PAGE_FRAMES_LOG2));
+    hal_frame_dealloc_huge(paddr);
+    assert_eq!(frame_stats().0, free_frames);
+}
+
+/// Aligned frames are aligned in physical memory, e.g. 16KB for `align_log2 = 2`.
+fn aligned_frame() {
+    for align_log2 in 0..=HUGE_PAGE_FRAMES_LOG2 {
+        let paddr = hal_frame_alloc_aligned(align_log2).expect("failed to alloc frame");
+        assert_eq!(paddr % (PAGE_SIZE << align_log2), 0);
+        hal_frame_dealloc_contiguous(paddr, 1);
+    }
+}
+
+/// A frame is cleared before it is handed out again.
+#[cfg(feature = "zero_on_alloc")]
+fn zero_on_alloc() {
+    let paddr = hal_frame_alloc().expect("failed to alloc frame");
+    let vaddr = phys_to_virt(paddr) as *mut u8;
+    unsafe { core::ptr::write_bytes(vaddr, 0xcc, PAGE_SIZE) };
+    hal_frame_dealloc(&paddr);
+    // the freed frame is the next one in the cache of this CPU
+    assert_eq!(hal_frame_alloc(), Some(paddr));
+    let bytes = unsafe { core::slice::from_raw_parts(vaddr, PAGE_SIZE) };
+    assert!(bytes.iter().all(|&b| b == 0));
+    hal_frame_dealloc(&paddr);
+}
+
+/// Only conventional memory of a boot memory map is given to the frame
+/// allocator, but all of it is known.
+fn reserved_regions() {
+    let snap = snapshot();
+    let (old_min, old_max) = frame_range();
+    let old_regions = core::mem::replace(&mut *MEMORY_REGIONS.lock(), MemoryRegions::EMPTY);
+    // 16 frames each, from frames below the limit which are not real memory
+    let types = [
+        MemoryType::CONVENTIONAL,
+        MemoryType::ACPI_RECLAIM,
+        MemoryType::CONVENTIONAL,
+        MemoryType::RESERVED,
+        MemoryType::LOADER_CODE,
+        MemoryType::LOADER_DATA,
+        MemoryType::ACPI_NON_VOLATILE,
+        MemoryType::CONVENTIONAL,
+    ];
+    let first = MAX_FRAMES - types.len() * 16;
+    let mut regions = [BootRegion {
+        start: 0,
+        end: 0,
+        ty: MemoryType::RESERVED,
+    }; 8];
+    for (i, (region, &ty)) in regions.iter_mut().zip(types.iter()).enumerate() {
+        let start = frame_to_phys(first + i * 16);
+        *region = BootRegion {
+            start,
+            end: start + 16 * PAGE_SIZE,
+            ty,
+        };
+    }
+    let mut ba = FRAME_ALLOCATOR.lock();
+    assert!((first..MAX_FRAMES).all(|id| !ba.test(id)));
+    add_boot_regions(&mut ba, &regions);
+    for (i, &ty) in types.iter().enumerate() {
+        let ids = first + i * 16..first + (i + 1) * 16;
+        let conventional = ty == MemoryType::CONVENTIONAL;
+        for id in ids {
+            assert_eq!(ba.test(id), conventional, "{:?} frame {:#x}", ty, id);
+        }
+    }
+    assert_eq!(FREE_FRAMES.load(Ordering::Relaxed), snap.free_frames + 48);
+    // the frames are not real memory
+    ba.remove(first..MAX_FRAMES);
+    drop(ba);
+    let regions = MEMORY_REGIONS.lock();
+    assert_eq!(&regions.regions[..regions.len], &[(first, MAX_FRAMES)]);
+    drop(regions);
+
+    *MEMORY_REGIONS.lock() = old_regions;
+    MIN_FRAME.store(old_min, Ordering::Relaxed);
+    MAX_FRAME.store(old_max, Ordering::Relaxed);
+    restore(&snap);
+    assert_eq!(snapshot(), snap);
+}
+
+/// Neither is firmware, reserved or kernel memory of the real boot memory map.
+fn boot_reserved_regions(boot_info: &BootInfo) {
+    flush_frame_caches();
+    let ba = FRAME_ALLOCATOR.lock();
+    for region in boot_info.memory_map.clone().iter {
+        match region.ty {
+            MemoryType::ACPI_RECLAIM
+            | MemoryType::ACPI_NON_VOLATILE
+            | MemoryType::RESERVED
+            | MemoryType::LOADER_CODE
+            | MemoryType::LOADER_DATA => {}
+            _ => continue,
+        }
+        let start = region.phys_start as usize;
+        for paddr in (start..start + region.page_count as usize * PAGE_SIZE).step_by(PAGE_SIZE) {
+            let id = match paddr.checked_sub(memory_base()) {
+                Some(offset) if offset < MAX_PHYS_MEMORY => offset / PAGE_SIZE,
+                _ => continue,
+            };
+            assert!(!ba.test(id), "{:?} frame {:#x} is free", region.ty, paddr);
+        }
+    }
+}
+
+/// Frames to free are checked against the range given to the frame allocator.
+fn frame_range_check() {
+    let frame = frame_to_phys;
+    assert_eq!(frame_id_in_range(frame(16), 1, 16, 32), Some(16));
+    assert_eq!(frame_id_in_range(frame(16), 16, 16, 32), Some(16));
+    assert_eq!(frame_id_in_range(frame(31), 1, 16, 32), Some(31));
+    assert_eq!(frame_id_in_range(frame(15), 1, 16, 32), None);
+    assert_eq!(frame_id_in_range(frame(32), 1, 16, 32), None);
+    assert_eq!(frame_id_in_range(frame(31), 2, 16, 32), None);
+    assert_eq!(frame_id_in_range(frame(16), usize::MAX, 16, 32), None);
+
+    // frames from the real allocator are in range
+    let paddr = hal_frame_alloc().expect("failed to alloc frame");
+    let id = (paddr - memory_base()) / PAGE_SIZE;
+    assert_eq!(checked_frame_id(paddr, 1), Some(id));
+    hal_frame_dealloc(&paddr);
+}
+
+/// Frames beyond `MAX_PHYS_MEMORY` are never given to the frame allocator.
+///
+/// Machines with that much memory are not at hand, so only check that no
+/// frame beyond the limit is known, and that it can not be added later.
+fn memory_limit() {
+    let (_, max) = frame_range();
+    assert!(max <= MAX_FRAMES);
+    let limit = memory_base() + MAX_PHYS_MEMORY;
+    assert_eq!(add_memory_region(limit, 1), Err(()));
+    assert_eq!(add_memory_region(limit - PAGE_SIZE, 2), Err(()));
+}
+
+/// Memory regions added after boot are checked before the frames are used.
+///
+/// No memory can be hot-added here, so only the rejected cases are tested on
+/// the real allocator.
+fn memory_regions() {
+    let mut regions = MemoryRegions::EMPTY;
+    assert!(regions.insert(16, 32));
+    // adjacent regions are merged
+    assert!(regions.insert(32, 48));
+    assert!(regions.insert(8, 16));
+    assert_eq!(regions.len, 1);
+    assert_eq!(regions.regions[0], (8, 48));
+    assert!(regions.overlaps(0, 9));
+    assert!(regions.overlaps(47, 64));
+    assert!(regions.overlaps(0, 64));
+    assert!(!regions.overlaps(0, 8));
+    assert!(!regions.overlaps(48, 64));
+    // the table can fill up
+    for i in 1..MAX_MEMORY_REGIONS {
+        assert!(regions.insert(100 * i, 100 * i + 1));
+    }
+    assert!(!regions.insert(100 * MAX_MEMORY_REGIONS, 100 * MAX_MEMORY_REGIONS + 1));
+    // but a full table can still be extended
+    assert!(regions.insert(101, 102));
+    // and any range may overlap the region that was not recorded
+    assert!(regions.overlaps(1 << 30, (1 << 30) + 1));
+
+    let paddr = hal_frame_alloc().expect("failed to alloc frame");
+    assert_eq!(add_memory_region(paddr + 1, 1), Err(()));
+    assert_eq!(add_memory_region(paddr, 0), Err(()));
+    // the frame is known memory, whether it is free or not
+    assert_eq!(add_memory_region(paddr, 1), Err(()));
+    hal_frame_dealloc(&paddr);
+    assert_eq!(add_memory_region(paddr, 1), Err(()));
+    if memory_base() >= PAGE_SIZE {
+        assert_eq!(add_memory_region(memory_base() - PAGE_SIZE, 1), Err(()));
+    }
+}
+
+/// Frames hot-added after boot are given to the frame allocator.
+fn hot_add_region() {
+    let snap = snapshot();
+    let (old_min, old_max) = frame_range();
+    // take up to 1024 frames of the largest free run out of the known memory
+    let &(start, end) = snap
+        .free_runs
+        .iter()
+        .max_by_key(|(start, end)| end - start)
+        .expect("no free frames");
+    let end = end.min(start + 1024);
+    let count = end - start;
+    let old_regions = core::mem::replace(&mut *MEMORY_REGIONS.lock(), MemoryRegions::EMPTY);
+    assert!(!old_regions.overflowed);
+    let mut regions = MEMORY_REGIONS.lock();
+    for &(s, e) in old_regions.regions[..old_regions.len].iter() {
+        if s <= start && end <= e {
+            for &(s, e) in [(s, start), (end, e)].iter().filter(|(s, e)| s < e) {
+                assert!(regions.insert(s, e));
+            }
+        } else {
+            assert!(regions.insert(s, e));
+        }
+    }
+    drop(regions);
+    // so the frames are free only once hot-added
+    FRAME_ALLOCATOR.lock().remove(old_min..old_max);
+    FREE_FRAMES.store(0, Ordering::Relaxed);
+
+    let paddr = frame_to_phys(start);
+    assert_eq!(add_memory_region(paddr, count), Ok(()));
+    assert_eq!(frame_stats().0, count);
+    // it is known memory now
+    assert_eq!(add_memory_region(paddr, count), Err(()));
+    let frames = hal_frame_alloc_contiguous(count, 0).expect("failed to alloc frames");
+    assert_eq!(frames, paddr);
+    hal_frame_dealloc_contiguous(frames, count);
+
+    *MEMORY_REGIONS.lock() = old_regions;
+    restore(&snap);
+    assert_eq!(snapshot(), snap);
+}
+
+/// Boot memory beyond `MAX_PHYS_MEMORY` is not given to the frame allocator,
+/// but it is still known, so it can not be hot-added.
+fn boot_regions_clamp() {
+    let snap = snapshot();
+    let (old_min, old_max) = frame_range();
+    let old_regions = core::mem::replace(&mut *MEMORY_REGIONS.lock(), MemoryRegions::EMPTY);
+    // 128GB of conventional memory, of which only 64 frames are below the limit
+    let start = frame_to_phys(MAX_FRAMES - 64);
+    let regions = [BootRegion {
+        start,
+        end: start + (128 << 30),
+        ty: MemoryType::CONVENTIONAL,
+    }];
+    let mut ba = FRAME_ALLOCATOR.lock();
+    assert!((MAX_FRAMES - 64..MAX_FRAMES).all(|id| !ba.test(id)));
+    add_boot_regions(&mut ba, &regions);
+    assert!((MAX_FRAMES - 64..MAX_FRAMES).all(|id| ba.test(id)));
+    assert_eq!(FREE_FRAMES.load(Ordering::Relaxed), snap.free_frames + 64);
+    assert_eq!(frame_range().1, MAX_FRAMES);
+    // the frames are not real memory
+    ba.remove(MAX_FRAMES - 64..MAX_FRAMES);
+    drop(ba);
+    let regions = MEMORY_REGIONS.lock();
+    assert_eq!(
+        regions.regions[..regions.len],
+        [(MAX_FRAMES - 64, MAX_FRAMES)]
+    );
+    drop(regions);
+
+    *MEMORY_REGIONS.lock() = old_regions;
+    MIN_FRAME.store(old_min, Ordering::Relaxed);
+    MAX_FRAME.store(old_max, Ordering::Relaxed);
+    restore(&snap);
+    assert_eq!(snapshot(), snap);
+}
+
+/// Frames round-trip through the frame allocator numbered from a memory
+/// base other than the default.
+fn memory_base_change() {
+    let snap = snapshot();
+    let (old_min, old_max) = frame_range();
+    let old_base = memory_base();
+    // number the frames from the largest free run
+    let &(start, end) = snap
+        .free_runs
+        .iter()
+        .max_by_key(|(start, end)| end - start)
+        .expect("no free frames");
+    let base = frame_to_phys(start);
+    // few enough to be zeroed quickly
+    let count = (end - start).min(1024);
+    let mut ba = FRAME_ALLOCATOR.lock();
+    ba.remove(old_min..old_max);
+    MEMORY_BASE.store(base, Ordering::Relaxed);
+    MIN_FRAME.store(usize::MAX, Ordering::Relaxed);
+    MAX_FRAME.store(0, Ordering::Relaxed);
+    TOTAL_FRAMES.store(0, Ordering::Relaxed);
+    FREE_FRAMES.store(0, Ordering::Relaxed);
+    insert_frames(&mut ba, 0, count);
+    drop(ba);
+
+    let paddr = hal_frame_alloc().expect("failed to alloc frame");
+    assert!((base..base + count * PAGE_SIZE).contains(&paddr));
+    assert_eq!(checked_frame_id(paddr, 1), Some((paddr - base) / PAGE_SIZE));
+    hal_frame_dealloc(&paddr);
+    flush_frame_caches();
+    let contiguous = hal_frame_alloc_contiguous(count, 0).expect("failed to alloc frames");
+    assert_eq!(contiguous, base);
+    assert_eq!(checked_frame_id(contiguous, count), Some(0));
+    hal_frame_dealloc_contiguous(contiguous, count);
+
+    flush_frame_caches();
+    FRAME_ALLOCATOR.lock().remove(0..count);
+    MEMORY_BASE.store(old_base, Ordering::Relaxed);
+    MIN_FRAME.store(old_min, Ordering::Relaxed);
+    MAX_FRAME.store(old_max, Ordering::Relaxed);
+    restore(&snap);
+    assert_eq!(snapshot(), snap);
+}
+
+/// The HAL follows the physical memory mapping set by `init_pmem_base`.
+#[cfg(target_arch = "x86_64")]
+fn pmem_base_change() {
+    let old_base = pmem_base();
+    // a misaligned offset is rejected, and the base is kept
+    assert!(!set_pmem_base(old_base + PAGE_SIZE));
+    assert_eq!(pmem_base(), old_base);
+
+    let offset = old_base + PHYSICAL_MEMORY_ALIGN;
+    map_pmem_alias(offset);
+    let paddr = hal_frame_alloc().expect("failed to alloc frame");
+    let vaddr = phys_to_virt(paddr) as *mut u64;
+    unsafe { vaddr.write_volatile(0x1234_5678) };
+    init_pmem_base(offset);
+    assert_eq!(pmem_base(), offset);
+    // the HAL reads and writes through the new mapping
+    let mut buf = [0u8; 8];
+    kernel_hal::pmem_read(paddr, &mut buf);
+    assert_eq!(u64::from_ne_bytes(buf), 0x1234_5678);
+    kernel_hal::pmem_write(paddr, &0x8765_4321u64.to_ne_bytes());
+    assert_eq!(unsafe { vaddr.read_volatile() }, 0x8765_4321);
+    assert_eq!(phys_to_virt(paddr), offset + paddr);
+
+    // `init_pmem_base` ignores the default offset
+    assert!(set_pmem_base(old_base));
+    unmap_pmem_alias(offset);
+    hal_frame_dealloc(&paddr);
+}
+
+/// Allocate all free frames, and link them through their first word, so no
+/// heap memory is needed to remember them. Return the first frame.
+fn exhaust_frames() -> usize {
+    let mut head = END;
+    while let Some(paddr) = hal_frame_alloc() {
+        unsafe {
+            *(phys_to_virt(paddr) as *mut usize) = head;
+        }
+        head = paddr;
+    }
+    head
+}
+
+/// Free the frames allocated by `exhaust_frames`.
+fn free_frames(mut head: usize) {
+    while head != END {
+        let paddr = head;
+        head = unsafe { *(phys_to_virt(paddr) as *const usize) };
+        hal_frame_dealloc(&paddr);
+    }
+}
+
+/// The heap is grown with frames past the small boot heap, only as far as
+/// it is asked to.
+fn heap_from_frames() {
+    let total = heap_total_bytes();
+    assert!(total >= MIN_KERNEL_HEAP_SIZE);
+    let (free, _) = frame_stats();
+    // already large enough
+    grow_heap_to(MIN_KERNEL_HEAP_SIZE);
+    grow_heap_to(total);
+    assert_eq!(heap_total_bytes(), total);
+    assert_eq!(frame_stats().0, free);
+
+    let size = total + 16 * PAGE_SIZE;
+    grow_heap_to(size);
+    assert_eq!(heap_total_bytes(), size);
+    assert_eq!(frame_stats().0, free - 16);
+    // growing to the same size again changes nothing
+    grow_heap_to(size);
+    assert_eq!(heap_total_bytes(), size);
+    assert_eq!(frame_stats().0, free - 16);
+}
+
+/// The shared zero frame is a frame of RAM that reads as zeros.
+fn zero_frame() {
+    let paddr = kernel_hal_bare::Frame::zero_frame_addr();
+    let (min_frame, max_frame) = frame_range();
+    assert!(frame_id_in_range(paddr, 1, min_frame, max_frame).is_some());
+    let page = unsafe { core::slice::from_raw_parts(phys_to_virt(paddr) as *const u8, PAGE_SIZE) };
+    assert!(page.iter().all(|&b| b == 0));
+}
+
+/// The heap grows by enough frames to satisfy the failed allocation.
+fn heap_growth() {
+    let log2 = |size: usize, align: usize| {
+        heap_growth_frames_log2(&Layout::from_size_align(size, align).unwrap())
+    };
+    let growth = PAGE_SIZE << HEAP_GROWTH_FRAMES_LOG2;
+    assert_eq!(log2(8, 8), HEAP_GROWTH_FRAMES_LOG2);
+    assert_eq!(log2(growth, PAGE_SIZE), HEAP_GROWTH_FRAMES_LOG2);
+    assert_eq!(log2(growth + 1, PAGE_SIZE), HEAP_GROWTH_FRAMES_LOG2 + 1);
+    assert_eq!(log2(PAGE_SIZE, growth * 4), HEAP_GROWTH_FRAMES_LOG2 + 2);
+
+    // larger than the whole heap, so it only fits in the grown part
+    let size = HEAP_ALLOCATOR.lock().stats_total_bytes() + PAGE_SIZE;
+    let layout = Layout::from_size_align(size, PAGE_SIZE).unwrap();
+    let ptr = unsafe { alloc::alloc::alloc(layout) };
+    assert!(!ptr.is_null());
+    unsafe { alloc::alloc::dealloc(ptr, layout) };
+}
+
+/// Chunks the heap grew by are given back to the frame allocator once idle.
+fn heap_shrink() {
+    // larger than the whole heap, so the heap grows to fit it
+    let size = HEAP_ALLOCATOR.lock().stats_total_bytes() + PAGE_SIZE;
+    let layout = Layout::from_size_align(size, PAGE_SIZE).unwrap();
+    let ptr = unsafe { alloc::alloc::alloc(layout) };
+    assert!(!ptr.is_null());
+    unsafe { alloc::alloc::dealloc(ptr, layout) };
+
+    let free = FREE_FRAMES.load(Ordering::Relaxed);
+    let returned = HEAP_RETURNED_BYTES.load(Ordering::Relaxed);
+    shrink_heap();
+    let shrunk = HEAP_RETURNED_BYTES.load(Ordering::Relaxed) - returned;
+    assert!(shrunk >= size);
+    assert_eq!(
+        FREE_FRAMES.load(Ordering::Relaxed) - free,
+        shrunk / PAGE_SIZE
+    );
+}
+
+/// Once frames run out, the heap can not grow: the allocation fails and the
+/// OOM callback is called.
+fn heap_exhaustion() {
+    static OOM_CALLS: AtomicUsize = AtomicUsize::new(0);
+    set_oom_callback(|| {
+        OOM_CALLS.fetch_add(1, Ordering::Relaxed);
+    });
+    let frames = exhaust_frames();
+    assert_ne!(frames, END);
+    // larger than the whole heap, so it can only fit after growing
+    let size = HEAP_ALLOCATOR.lock().stats_total_bytes() + PAGE_SIZE;
+    let layout = Layout::from_size_align(size, PAGE_SIZE).unwrap();
+    let ptr = unsafe { alloc::alloc::alloc(layout) };
+    assert!(ptr.is_null());
+    assert_eq!(OOM_CALLS.load(Ordering::Relaxed), 1);
+    free_frames(frames);
+
+    // the heap grows again once frames are free
+    let ptr = unsafe { alloc::alloc::alloc(layout) };
+    assert!(!ptr.is_null());
+    unsafe { alloc::alloc::dealloc(ptr, layout) };
+    assert_eq!(OOM_CALLS.load(Ordering::Relaxed), 1);
+}
+
+/// The heap grows from scattered frames if no contiguous chunk is free,
+/// however many runs they make.
+fn heap_scattered_frames() {
+    static OOM_CALLS: AtomicUsize = AtomicUsize::new(0);
+    set_oom_callback(|| {
+        OOM_CALLS.fetch_add(1, Ordering::Relaxed);
+    });
+    // give back 256 frames, no two of them adjacent
+    let mut frames = exhaust_frames();
+    let mut kept = END;
+    let mut freed = 0;
+    while frames != END {
+        let paddr = frames;
+        frames = unsafe { *(phys_to_virt(paddr) as *const usize) };
+        if freed < 256 && (paddr / PAGE_SIZE) % 2 == 0 {
+            hal_frame_dealloc(&paddr);
+            freed += 1;
+        } else {
+            unsafe {
+                *(phys_to_virt(paddr) as *mut usize) = kept;
+            }
+            kept = paddr;
+        }
+    }
+    assert_eq!(freed, 256);
+    let total = heap_total_bytes();
+    // larger than a block made of single frames
+    let size = HEAP_ALLOCATOR.lock().stats_total_bytes() + PAGE_SIZE;
+    let layout = Layout::from_size_align(size, PAGE_SIZE).unwrap();
+    let ptr = unsafe { alloc::alloc::alloc(layout) };
+    assert!(ptr.is_null());
+    assert_eq!(OOM_CALLS.load(Ordering::Relaxed), 1);
+    assert_eq!(heap_total_bytes(), total + freed * PAGE_SIZE);
+    free_frames(kept);
+}
+
+/// The low memory callback is called once per drop below the watermark, and
+/// again only after free frames rise above it.
+fn low_memory_callback() {
+    static LOW_MEMORY_CALLS: AtomicUsize = AtomicUsize::new(0);
+    set_low_memory_callback(|| {
+        LOW_MEMORY_CALLS.fetch_add(1, Ordering::Relaxed);
+    });
+    let frames = exhaust_frames();
+    assert_eq!(LOW_MEMORY_CALLS.load(Ordering::Relaxed), 1);
+    free_frames(frames);
+    // re-armed by the next allocation above the watermark
+    let paddr = hal_frame_alloc().expect("failed to alloc frame");
+    hal_frame_dealloc(&paddr);
+    assert_eq!(LOW_MEMORY_CALLS.load(Ordering::Relaxed), 1);
+
+    let frames = exhaust_frames();
+    assert_eq!(LOW_MEMORY_CALLS.load(Ordering::Relaxed), 2);
+    free_frames(frames);
+}
+
+/// The committed frames of a process are given back when it exits, even if
+/// the process itself is still referenced.
+fn process_exit_frames() {
+    use zircon_object::{
+        task::{Job, Process},
+        vm::{MMUFlags, VmObject},
+    };
+    const PAGES: usize = 16;
+    let free_frames = || {
+        flush_frame_caches();
+        frame_stats().0
+    };
+    // the heap may grow for the first process, and it never shrinks
+    for round in 0..2 {
+        let proc = Process::create(&Job::root(), "proc", 0).expect("failed to create process");
+        let baseline = free_frames();
+        let vmo = VmObject::new_paged(PAGES);
+        for i in 0..PAGES {
+            vmo.write(i * PAGE_SIZE, &[1]).unwrap();
+        }
+        let flags = MMUFlags::READ | MMUFlags::WRITE | MMUFlags::USER;
+        proc.vmar()
+            .map(None, vmo, 0, PAGES * PAGE_SIZE, flags)
+            .unwrap();
+        assert!(free_frames() <= baseline - PAGES);
+        proc.exit(0);
+        let free = free_frames();
+        if round != 0 {
+            // only the page tables of the mapping are left, 3 levels at most
+            assert!(free <= baseline && free + 3 >= baseline);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Update the memory pressure level and kill processes when memory runs low.
+//!
+//! The low memory and OOM callbacks run in the allocation path, where the
+//! heap may be locked, so they only set a flag. `low_memory_handler` picks it
+//! up, updates [`MemoryPressureEvent`], and kills processes in kill-on-OOM
+//! jobs while the level is `Critical`.
+
+use {
+    alloc::sync::Arc,
+    core::sync::atomic::{AtomicBool, Ordering},
+    core::time::Duration,
+    zircon_object::signal::{MemoryPressureEvent, PressureLevel},
+    zircon_object::task::Job,
+};
+
+/// Set by the low memory and OOM callbacks, cleared by `low_memory_handler`.
+static LOW_MEMORY: AtomicBool = AtomicBool::new(false);
+
+/// How often `low_memory_handler` checks for low memory.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Register the low memory and OOM callbacks.
+pub fn init() {
+    super::memory::set_low_memory_callback(on_low_memory);
+    super::memory::set_oom_callback(on_low_memory);
+}
+
+fn on_low_memory() {
+    LOW_MEMORY.store(true, Ordering::Release);
+}
+
+/// Update the pressure level every `CHECK_INTERVAL` since free physical
+/// memory drops low, until it is back to `Normal`. Kill a process in a
+/// kill-on-OOM job under `root_job` each time the level is `Critical`.
+pub async fn low_memory_handler(root_job: Arc<Job>) {
+    let pressure = MemoryPressureEvent::get();
+    loop {
+        kernel_hal::sleep_until(kernel_hal::timer_now() + CHECK_INTERVAL).await;
+        if !LOW_MEMORY.load(Ordering::Acquire) {
+            continue;
+        }
+        let stats = kernel_hal::kmem_stats();
+        pressure.update(stats.free_bytes, stats.total_bytes);
+        match pressure.level() {
+            PressureLevel::Normal => LOW_MEMORY.store(false, Ordering::Release),
+            PressureLevel::Warning => {}
+            PressureLevel::Critical => {
+                if !root_job.oom_kill() {
+                    warn!("free physical memory is critical, but no process can be killed");
+                }
+            }
+        }
+    }
+}
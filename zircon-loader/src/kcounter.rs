@@ -69,7 +69,7 @@ pub fn create_kcounter_vmo() -> (Arc<VmObject>, Arc<VmObject>) {
 
 #[repr(C)]
 struct KCounterDescItem {
-    name: [u8; 56],
+    name: [u8; KCOUNTER_NAME_LEN],
     type_: KCounterType,
 }
 
@@ -80,11 +80,8 @@ enum KCounterType {
 
 impl From<&KCounterDescriptor> for KCounterDescItem {
     fn from(desc: &KCounterDescriptor) -> Self {
-        let mut name = [0u8; 56];
-        let length = desc.name.len().min(56);
-        name[..length].copy_from_slice(&desc.name.as_bytes()[..length]);
         KCounterDescItem {
-            name,
+            name: desc.padded_name(),
             type_: KCounterType::Sum,
         }
     }
@@ -1,6 +1,7 @@
 #![no_std]
 #![feature(asm)]
 #![feature(global_asm)]
+#![feature(const_in_array_repeat_expressions)]
 #![deny(warnings, unused_must_use)]
 
 #[macro_use]
@@ -10,7 +11,13 @@ extern crate log;
 
 use {
     alloc::{boxed::Box, sync::Arc, vec::Vec},
-    kernel_hal::GeneralRegs,
+    core::{
+        future::Future,
+        pin::Pin,
+        sync::atomic::{AtomicPtr, Ordering},
+        task::{Context, Poll},
+    },
+    kernel_hal::{GeneralRegs, UserContext, MAX_CPU_NUM},
     xmas_elf::ElfFile,
     zircon_object::{
         ipc::*,
@@ -53,6 +60,8 @@ pub struct Images<T: AsRef<[u8]>> {
 }
 
 pub fn run_userboot(images: &Images<impl AsRef<[u8]>>, cmdline: &str) -> Arc<Process> {
+    kernel_hal::user::set_page_fault_handler(handle_kernel_page_fault);
+    kernel_hal::user::set_user_range_checker(check_user_range);
     let job = Job::root();
     let proc = Process::create(&job, "proc", 0).unwrap();
     let thread = Thread::create(&proc, "thread", 0).unwrap();
@@ -184,25 +193,38 @@ pub fn run_userboot(images: &Images<impl AsRef<[u8]>>, cmdline: &str) -> Arc<Pro
 kcounter!(EXCEPTIONS_USER, "exceptions.user");
 kcounter!(EXCEPTIONS_TIMER, "exceptions.timer");
 kcounter!(EXCEPTIONS_PGFAULT, "exceptions.pgfault");
+kcounter!(CONTEXT_SWITCHES, "thread.context_switches");
 
 fn spawn(thread: Arc<Thread>) {
     let vmtoken = thread.proc().vmar().table_phys();
+    let current = thread.clone();
     let future = async move {
         kernel_hal::Thread::set_tid(thread.id(), thread.proc().id());
         loop {
             let mut cx = thread.wait_for_run().await;
             trace!("go to user: {:#x?}", cx);
             debug!("switch to {}|{}", thread.proc().name(), thread.name());
+            if kernel_hal::CpuStats::current().switch_to(thread.id()) {
+                CONTEXT_SWITCHES.add(1);
+            }
+            // for the page tables changed by the last syscall or fault
+            kernel_hal::flush_tlb_shootdown();
             kernel_hal::context_run(&mut cx);
             trace!("back from user: {:#x?}", cx);
+            let cpu_stats = kernel_hal::CpuStats::current();
             EXCEPTIONS_USER.add(1);
             let mut exit = false;
+            let mut fault = None;
             match cx.trap_num {
-                0x100 => exit = handle_syscall(&thread, &mut cx.general).await,
+                0x100 => {
+                    cpu_stats.inc_syscalls();
+                    exit = handle_syscall(&thread, &mut cx.general).await;
+                }
                 0x20..=0x3f => {
                     kernel_hal::irq_handle(cx.trap_num as u8 - 0x20);
                     if cx.trap_num == 0x20 {
                         EXCEPTIONS_TIMER.add(1);
+                        cpu_stats.inc_reschedules();
                         kernel_hal::yield_now().await;
                     }
                 }
@@ -225,22 +247,119 @@ fn spawn(thread: Arc<Thread>) {
                     {
                         Ok(()) => {}
                         Err(e) => {
-                            error!("{:?}", e);
-                            panic!("Page Fault from user mode {:#x?}", cx);
+                            error!("Page Fault from user mode: {:?} {:#x?}", e, cx);
+                            fault = Some(exception_report(&cx));
                         }
                     }
                 }
-                _ => panic!("not supported interrupt from user mode. {:#x?}", cx),
+                _ => {
+                    error!("not supported interrupt from user mode. {:#x?}", cx);
+                    fault = Some(exception_report(&cx));
+                }
             }
             thread.end_running(cx);
+            if let Some(report) = fault {
+                thread.handle_fatal_exception(report).await;
+                break;
+            }
             if exit {
                 break;
             }
         }
     };
+    let future = CurrentThreadWrapper {
+        thread: current,
+        inner: Box::pin(future),
+    };
     kernel_hal::Thread::spawn(Box::pin(future), vmtoken);
 }
 
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_THREAD: AtomicPtr<Thread> = AtomicPtr::new(core::ptr::null_mut());
+
+/// The thread whose future is being polled on each CPU, null if none.
+static CURRENT_THREAD: [AtomicPtr<Thread>; MAX_CPU_NUM] = [NO_THREAD; MAX_CPU_NUM];
+
+/// Mark `thread` as the current thread of this CPU while polling its future,
+/// so page faults the kernel raises on its user memory can be resolved.
+struct CurrentThreadWrapper {
+    thread: Arc<Thread>,
+    inner: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+impl Future for CurrentThreadWrapper {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let current = &CURRENT_THREAD[kernel_hal::cpu_id() as usize % MAX_CPU_NUM];
+        current.store(
+            &*self.thread as *const Thread as *mut Thread,
+            Ordering::Relaxed,
+        );
+        let ret = self.inner.as_mut().poll(cx);
+        current.store(core::ptr::null_mut(), Ordering::Relaxed);
+        ret
+    }
+}
+
+/// Resolve a page fault the kernel raised on the user memory of the current thread.
+///
+/// The kernel must not touch user memory with the locks of its address space held.
+fn handle_kernel_page_fault(vaddr: usize, flags: MMUFlags) -> bool {
+    let current =
+        CURRENT_THREAD[kernel_hal::cpu_id() as usize % MAX_CPU_NUM].load(Ordering::Relaxed);
+    if current.is_null() {
+        return false;
+    }
+    // the thread is kept alive by the future being polled
+    let thread = unsafe { &*current };
+    EXCEPTIONS_PGFAULT.add(1);
+    match thread.proc().vmar().handle_page_fault(vaddr, flags) {
+        Ok(()) => true,
+        Err(e) => {
+            error!("Page Fault from kernel mode at {:#x}: {:?}", vaddr, e);
+            false
+        }
+    }
+}
+
+/// Check the user memory the kernel accesses for the current thread, so a bad
+/// pointer fails the syscall rather than faulting in the kernel.
+fn check_user_range(vaddr: usize, len: usize, flags: MMUFlags) -> bool {
+    let current =
+        CURRENT_THREAD[kernel_hal::cpu_id() as usize % MAX_CPU_NUM].load(Ordering::Relaxed);
+    if current.is_null() {
+        return true;
+    }
+    // the thread is kept alive by the future being polled
+    let thread = unsafe { &*current };
+    thread.proc().vmar().check_access(vaddr, len, flags)
+}
+
+/// Make the report of the exception `cx` trapped with.
+fn exception_report(cx: &UserContext) -> ExceptionReport {
+    let type_ = match cx.trap_num {
+        0x1 => ExceptionType::HwBreakpoint,
+        0x3 => ExceptionType::SwBreakpoint,
+        0x6 => ExceptionType::UndefinedInstruction,
+        0xe => ExceptionType::FatalPageFault,
+        0x11 => ExceptionType::UnalignedAccess,
+        _ => ExceptionType::General,
+    };
+    let cr2 = if cx.trap_num == 0xe {
+        kernel_hal::fetch_fault_vaddr()
+    } else {
+        0
+    };
+    let context = ExceptionContext {
+        vector: cx.trap_num as u64,
+        err_code: cx.error_code as u64,
+        cr2: cr2 as u64,
+        ..Default::default()
+    };
+    ExceptionReport::new(type_, context)
+}
+
 async fn handle_syscall(thread: &Arc<Thread>, regs: &mut GeneralRegs) -> bool {
     let num = regs.rax as u32;
     // LibOS: Function call ABI
@@ -1,9 +1,20 @@
 use {
-    super::*, crate::object::*, alloc::sync::Arc, core::convert::TryInto, kernel_hal::serial_write,
-    kernel_hal::timer_now, spin::Mutex,
+    super::*,
+    crate::object::*,
+    alloc::sync::{Arc, Weak},
+    alloc::vec::Vec,
+    core::convert::TryInto,
+    kernel_hal::serial_write,
+    kernel_hal::timer_now,
+    spin::Mutex,
 };
 
 static DLOG: Mutex<DlogBuffer> = Mutex::new(DlogBuffer::new());
+/// Readable debuglogs, to be signaled when a new record is written.
+static READERS: Mutex<Vec<Weak<DebugLog>>> = Mutex::new(Vec::new());
+
+/// Create a debuglog that can read records.
+pub const FLAG_READABLE: u32 = 0x4000_0000u32;
 
 #[cfg(not(test))]
 const DLOG_SIZE: usize = 128usize * 1024usize;
@@ -22,36 +33,97 @@ struct DlogHeader {
     tid: u64,
 }
 
+/// Kernel log
+///
+/// ## SYNOPSIS
+///
+/// Records written to any debuglog go to a ring buffer shared by the kernel.
+/// When the buffer is full the oldest records are dropped. A debuglog
+/// created with `FLAG_READABLE` reads the records in order from its own
+/// position, and is `READABLE` while there are records it has not read.
 pub struct DebugLog {
     base: KObjectBase,
     flags: u32,
+    cursor: Mutex<DlogCursor>,
 }
 
 impl_kobject!(DebugLog);
 
 impl DebugLog {
     pub fn create(flags: u32) -> Arc<Self> {
-        Arc::new(DebugLog {
+        let dlog = DLOG.lock();
+        let debuglog = Arc::new(DebugLog {
             base: KObjectBase::new(),
             flags,
-        })
+            cursor: Mutex::new(dlog.cursor()),
+        });
+        if flags & FLAG_READABLE != 0 {
+            if dlog.has_records() {
+                debuglog.base.signal_set(Signal::READABLE);
+            }
+            READERS.lock().push(Arc::downgrade(&debuglog));
+        }
+        debuglog
     }
 
     pub fn write(&self, flags: u32, data: &str, tid: u64, pid: u64) -> ZxResult<usize> {
         let flags = flags | self.flags;
         DLOG.lock().write(flags, data.as_bytes(), tid, pid);
+        READERS.lock().retain(|reader| match reader.upgrade() {
+            Some(reader) => {
+                reader.base.signal_set(Signal::READABLE);
+                true
+            }
+            None => false,
+        });
         serial_write(data);
         if data.as_bytes().last() != Some(&b'\n') {
             serial_write("\n");
         }
         Ok(0)
     }
+
+    /// Read the next record, including its header.
+    ///
+    /// Returns `ZxError::SHOULD_WAIT` if all records have been read.
+    pub fn read(&self) -> ZxResult<Vec<u8>> {
+        if self.flags & FLAG_READABLE == 0 {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let mut cursor = self.cursor.lock();
+        let dlog = DLOG.lock();
+        let record = dlog.read(&mut cursor).ok_or(ZxError::SHOULD_WAIT)?;
+        if !dlog.has_records_after(&cursor) {
+            // cleared with the buffer locked, so a new record can't be missed
+            self.base.signal_clear(Signal::READABLE);
+        }
+        Ok(record)
+    }
+
+    /// Get the number of records dropped before this debuglog read them.
+    pub fn dropped(&self) -> usize {
+        self.cursor.lock().dropped
+    }
+}
+
+/// The position of a reader in `DlogBuffer`.
+struct DlogCursor {
+    /// Offset of the next record.
+    pos: usize,
+    /// Sequence number of the next record.
+    seq: usize,
+    /// Number of records dropped before they were read.
+    dropped: usize,
 }
 
 struct DlogBuffer {
     buf: [u8; DLOG_SIZE],
     head: usize,
     tail: usize,
+    /// Number of records written.
+    written: usize,
+    /// Number of records dropped on overflow.
+    evicted: usize,
 }
 
 impl DlogBuffer {
@@ -60,9 +132,53 @@ impl DlogBuffer {
             buf: [0u8; DLOG_SIZE],
             head: 0usize,
             tail: 0usize,
+            written: 0usize,
+            evicted: 0usize,
+        }
+    }
+
+    /// Get a cursor at the oldest record.
+    fn cursor(&self) -> DlogCursor {
+        DlogCursor {
+            pos: self.tail,
+            seq: self.evicted,
+            dropped: 0,
         }
     }
 
+    fn has_records(&self) -> bool {
+        self.written > self.evicted
+    }
+
+    fn has_records_after(&self, cursor: &DlogCursor) -> bool {
+        cursor.seq < self.written
+    }
+
+    /// Read the record at `cursor` and move it to the next one.
+    ///
+    /// A cursor behind the oldest record skips to it, counting the dropped records.
+    fn read(&self, cursor: &mut DlogCursor) -> Option<Vec<u8>> {
+        if cursor.seq < self.evicted {
+            cursor.dropped += self.evicted - cursor.seq;
+            cursor.seq = self.evicted;
+            cursor.pos = self.tail;
+        }
+        if cursor.seq == self.written {
+            return None;
+        }
+        let start = cursor.pos & DLOG_MASK;
+        let header = u32::from_ne_bytes(self.buf[start..start + 4].try_into().unwrap());
+        let wire_size = (header & 0xFFF) as usize;
+        let size = ((header >> 12) & 0xFFF) as usize;
+        let first = size.min(DLOG_SIZE - start);
+        let mut record = Vec::with_capacity(size);
+        record.extend_from_slice(&self.buf[start..start + first]);
+        record.extend_from_slice(&self.buf[..size - first]);
+        cursor.pos += wire_size;
+        cursor.seq += 1;
+        Some(record)
+    }
+
     #[allow(unsafe_code)]
     pub fn write(&mut self, flags: u32, data: &[u8], tid: u64, pid: u64) {
         let wire_size = DLOG_MIN_RECORD + ((data.len() + 3) & !3);
@@ -72,7 +188,7 @@ impl DlogBuffer {
             header: header_flag,
             datalen: data.len() as u16,
             flags: flags as u16,
-            timestamp: timer_now().as_secs(),
+            timestamp: timer_now().as_nanos() as u64,
             pid,
             tid,
         };
@@ -84,6 +200,7 @@ impl DlogBuffer {
             let header: u32 =
                 u32::from_ne_bytes(self.buf[tail_index..tail_index + 4].try_into().unwrap());
             self.tail += (header & 0xFFF) as usize;
+            self.evicted += 1;
         }
         let mut offset = head & DLOG_MASK;
         let fifo_size = DLOG_SIZE - offset;
@@ -106,6 +223,7 @@ impl DlogBuffer {
             }
         }
         self.head += wire_size;
+        self.written += 1;
     }
 
     fn copy_and_write(&mut self, start: usize, data: &[u8]) {
@@ -195,4 +313,56 @@ mod tests {
         let tail = buffer.get_tail();
         assert_eq!(tail, 2016usize);
     }
+
+    #[test]
+    fn read_wraparound() {
+        let mut buffer = DlogBuffer::new();
+        let mut cursor = buffer.cursor();
+        assert!(buffer.read(&mut cursor).is_none());
+        buffer.write(0u32, &[1u8; 1000], 7, 9);
+        let record = buffer.read(&mut cursor).unwrap();
+        assert_eq!(record.len(), DLOG_MIN_RECORD + 1000);
+        assert_eq!(u64::from_ne_bytes(record[16..24].try_into().unwrap()), 9);
+        assert_eq!(u64::from_ne_bytes(record[24..32].try_into().unwrap()), 7);
+        assert!(record[DLOG_MIN_RECORD..].iter().all(|&b| b == 1));
+        assert!(buffer.read(&mut cursor).is_none());
+
+        buffer.write(0u32, &[2u8; 900], 0, 0);
+        let record = buffer.read(&mut cursor).unwrap();
+        assert!(record[DLOG_MIN_RECORD..].iter().all(|&b| b == 2));
+
+        // the third record wraps around
+        buffer.write(0u32, &[3u8; 1000], 0, 0);
+        let record = buffer.read(&mut cursor).unwrap();
+        assert_eq!(record.len(), DLOG_MIN_RECORD + 1000);
+        assert!(record[DLOG_MIN_RECORD..].iter().all(|&b| b == 3));
+
+        // the fifth record drops the fourth before it is read
+        buffer.write(0u32, &[4u8; 1500], 0, 0);
+        buffer.write(0u32, &[5u8; 900], 0, 0);
+        let record = buffer.read(&mut cursor).unwrap();
+        assert!(record[DLOG_MIN_RECORD..].iter().all(|&b| b == 5));
+        assert_eq!(cursor.dropped, 1);
+        assert!(buffer.read(&mut cursor).is_none());
+    }
+
+    #[async_std::test]
+    async fn blocking_read() {
+        let reader = DebugLog::create(FLAG_READABLE);
+        while reader.read().is_ok() {}
+        assert_eq!(reader.read().err(), Some(ZxError::SHOULD_WAIT));
+        assert!(!reader.signal().contains(Signal::READABLE));
+
+        async_std::task::spawn(async {
+            DebugLog::create(0).write(0, "hello", 1, 2).unwrap();
+        });
+        let object: Arc<dyn KernelObject> = reader.clone();
+        object.wait_signal(Signal::READABLE).await;
+        let record = reader.read().unwrap();
+        assert!(!record.is_empty());
+        assert_eq!(
+            DebugLog::create(0).read().err(),
+            Some(ZxError::ACCESS_DENIED)
+        );
+    }
 }
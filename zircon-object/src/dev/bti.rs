@@ -1,4 +1,13 @@
-use {crate::object::*, alloc::sync::Arc, dev::Iommu};
+use {
+    super::*,
+    crate::object::*,
+    crate::vm::*,
+    alloc::{
+        sync::{Arc, Weak},
+        vec::Vec,
+    },
+    spin::Mutex,
+};
 
 // Iommu refers to DummyIommu in fuchsia
 #[allow(dead_code)]
@@ -6,6 +15,7 @@ pub struct Bti {
     base: KObjectBase,
     iommu: Arc<Iommu>,
     bti_id: u64,
+    pmts: Mutex<Vec<Weak<Pmt>>>,
 }
 
 impl_kobject!(Bti);
@@ -16,6 +26,105 @@ impl Bti {
             base: KObjectBase::new(),
             iommu,
             bti_id,
+            pmts: Mutex::new(Vec::new()),
         })
     }
+
+    /// Get information of the BTI.
+    pub fn get_info(&self) -> BtiInfo {
+        BtiInfo {
+            minimum_contiguity: self.iommu.minimum_contiguity() as u64,
+            aspace_size: self.iommu.aspace_size() as u64,
+            pmo_count: self.pmo_count() as u64,
+            quarantine_count: 0,
+        }
+    }
+
+    /// The granularity of contiguous device address ranges.
+    pub fn minimum_contiguity(&self) -> usize {
+        self.iommu.minimum_contiguity()
+    }
+
+    /// Pin pages of `vmo` in range for device access.
+    pub fn pin(
+        self: &Arc<Self>,
+        vmo: Arc<VmObject>,
+        offset: usize,
+        size: usize,
+    ) -> ZxResult<Arc<Pmt>> {
+        if size == 0 || !page_aligned(offset) || !page_aligned(size) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        // in case integer addition overflows
+        if offset > vmo.len() || size > vmo.len() - offset {
+            return Err(ZxError::OUT_OF_RANGE);
+        }
+        let pmt = Pmt::create(self, vmo, offset, size)?;
+        let mut pmts = self.pmts.lock();
+        pmts.retain(|pmt| pmt.strong_count() != 0);
+        pmts.push(Arc::downgrade(&pmt));
+        Ok(pmt)
+    }
+
+    /// The number of PMTs still pinning pages.
+    fn pmo_count(&self) -> usize {
+        self.pmts
+            .lock()
+            .iter()
+            .filter_map(|pmt| pmt.upgrade())
+            .filter(|pmt| pmt.is_pinned())
+            .count()
+    }
+}
+
+/// Information of a BTI, returned by `ZX_INFO_BTI`.
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct BtiInfo {
+    /// The granularity in bytes of contiguous ranges returned by pinning.
+    pub minimum_contiguity: u64,
+    /// The size of the device address space.
+    pub aspace_size: u64,
+    /// The number of pinned memory objects.
+    pub pmo_count: u64,
+    /// The number of quarantined pinned memory objects.
+    pub quarantine_count: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pin() {
+        let iommu = Iommu::create(0, Vec::new(), 0);
+        let bti = Bti::create(iommu, 0);
+        let vmo = VmObject::new_paged(4);
+        assert_eq!(
+            bti.pin(vmo.clone(), 0, 0x1800).err(),
+            Some(ZxError::INVALID_ARGS)
+        );
+        assert_eq!(
+            bti.pin(vmo.clone(), 0x1000, 0x4000).err(),
+            Some(ZxError::OUT_OF_RANGE)
+        );
+
+        let pmt = bti.pin(vmo.clone(), 0x1000, 0x2000).unwrap();
+        assert_eq!(pmt.encode_addrs(false, false).unwrap().len(), 2);
+        assert_eq!(bti.get_info().pmo_count, 1);
+
+        // decommit skips pinned pages
+        vmo.decommit(0, 0x4000).unwrap();
+        assert_eq!(vmo.committed_bytes(), 0x2000);
+
+        pmt.unpin();
+        assert!(!pmt.is_pinned());
+        assert_eq!(
+            pmt.encode_addrs(false, false).err(),
+            Some(ZxError::BAD_STATE)
+        );
+        assert_eq!(bti.get_info().pmo_count, 0);
+        vmo.decommit(0, 0x4000).unwrap();
+        assert_eq!(vmo.committed_bytes(), 0);
+    }
 }
@@ -1,8 +1,12 @@
 use {
     crate::object::*,
+    crate::vm::PAGE_SIZE,
     alloc::{sync::Arc, vec::Vec},
 };
 
+/// Device Virtual Address
+pub type DevVAddr = usize;
+
 // Iommu refers to DummyIommu in fuchsia
 
 pub struct Iommu {
@@ -26,4 +30,14 @@ impl Iommu {
     pub fn is_valid_bus_txn_id(&self) -> bool {
         true
     }
+
+    /// The granularity of contiguous device address ranges.
+    pub fn minimum_contiguity(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    /// The size of the device address space.
+    pub fn aspace_size(&self) -> usize {
+        usize::MAX
+    }
 }
@@ -1,4 +1,5 @@
 mod bti;
 mod iommu;
+mod pmt;
 
-pub use self::{bti::*, iommu::*};
+pub use self::{bti::*, iommu::*, pmt::*};
@@ -0,0 +1,87 @@
+use {
+    super::*,
+    crate::object::*,
+    crate::vm::*,
+    alloc::{
+        sync::{Arc, Weak},
+        vec::Vec,
+    },
+    spin::Mutex,
+};
+
+/// Pinned Memory Token.
+///
+/// It keeps pages of a VMO pinned for device access through a BTI,
+/// until it is unpinned.
+pub struct Pmt {
+    base: KObjectBase,
+    bti: Weak<Bti>,
+    vmo: Arc<VmObject>,
+    offset: usize,
+    size: usize,
+    /// Device addresses of the pinned pages, or `None` once unpinned.
+    mapped_addrs: Mutex<Option<Vec<DevVAddr>>>,
+}
+
+impl_kobject!(Pmt);
+
+impl Pmt {
+    /// Pin pages of `vmo` in range for `bti`.
+    pub(super) fn create(
+        bti: &Arc<Bti>,
+        vmo: Arc<VmObject>,
+        offset: usize,
+        size: usize,
+    ) -> ZxResult<Arc<Self>> {
+        // the dummy IOMMU maps device addresses to physical addresses one-to-one
+        let mapped_addrs = vmo.pin(offset, size)?;
+        Ok(Arc::new(Pmt {
+            base: KObjectBase::new(),
+            bti: Arc::downgrade(bti),
+            vmo,
+            offset,
+            size,
+            mapped_addrs: Mutex::new(Some(mapped_addrs)),
+        }))
+    }
+
+    /// Get device addresses of the pinned pages.
+    ///
+    /// If `compress`, return one address per `minimum_contiguity` bytes.
+    /// If `contiguous`, return the only address of a contiguous range.
+    /// Otherwise return one address per page.
+    pub fn encode_addrs(&self, compress: bool, contiguous: bool) -> ZxResult<Vec<DevVAddr>> {
+        let inner = self.mapped_addrs.lock();
+        let addrs = inner.as_ref().ok_or(ZxError::BAD_STATE)?;
+        if contiguous {
+            if addrs.windows(2).any(|w| w[0] + PAGE_SIZE != w[1]) {
+                return Err(ZxError::INVALID_ARGS);
+            }
+            return Ok(addrs[..1].to_vec());
+        }
+        let step = match (compress, self.bti.upgrade()) {
+            (true, Some(bti)) => bti.minimum_contiguity() / PAGE_SIZE,
+            _ => 1,
+        };
+        Ok(addrs.iter().step_by(step).cloned().collect())
+    }
+
+    /// Unpin the pages. It does nothing if already unpinned.
+    pub fn unpin(&self) {
+        if self.mapped_addrs.lock().take().is_some() {
+            self.vmo.unpin(self.offset, self.size);
+        }
+    }
+
+    /// Whether the pages are still pinned.
+    pub fn is_pinned(&self) -> bool {
+        self.mapped_addrs.lock().is_some()
+    }
+}
+
+impl Drop for Pmt {
+    fn drop(&mut self) {
+        // TODO: quarantine the pages instead, like zircon
+        self.unpin();
+    }
+}
@@ -1,11 +1,23 @@
 use {
     crate::object::*,
+    alloc::collections::VecDeque,
     alloc::sync::{Arc, Weak},
+    core::sync::atomic::{AtomicUsize, Ordering},
+    spin::Mutex,
 };
 
+/// Bidirectional streaming IPC transport.
 pub struct Socket {
     base: KObjectBase,
     peer: Weak<Socket>,
+    options: u32,
+    recv_queue: Mutex<VecDeque<u8>>,
+    /// Assert `SOCKET_READ_THRESHOLD` when the receive buffer has at least
+    /// this many bytes. Zero means disabled.
+    rx_threshold: AtomicUsize,
+    /// Assert `SOCKET_WRITE_THRESHOLD` when the peer's receive buffer has at
+    /// least this many free bytes. Zero means disabled.
+    tx_threshold: AtomicUsize,
 }
 
 impl_kobject!(Socket
@@ -18,16 +30,28 @@ impl_kobject!(Socket
     }
 );
 
+/// The maximum number of bytes in the receive buffer of each end.
+const SOCKET_BUFFER_SIZE: usize = 256 * 1024;
+
 impl Socket {
+    /// Create a socket and return a pair of its endpoints.
     #[allow(unsafe_code)]
     pub fn create() -> (Arc<Self>, Arc<Self>) {
         let mut end0 = Arc::new(Socket {
             base: KObjectBase::with_signal(Signal::WRITABLE),
             peer: Weak::default(),
+            options: 0,
+            recv_queue: Default::default(),
+            rx_threshold: AtomicUsize::new(0),
+            tx_threshold: AtomicUsize::new(0),
         });
         let end1 = Arc::new(Socket {
             base: KObjectBase::with_signal(Signal::WRITABLE),
             peer: Arc::downgrade(&end0),
+            options: 0,
+            recv_queue: Default::default(),
+            rx_threshold: AtomicUsize::new(0),
+            tx_threshold: AtomicUsize::new(0),
         });
         // no other reference of `end0`
         unsafe {
@@ -35,4 +59,245 @@ impl Socket {
         }
         (end0, end1)
     }
+
+    /// Write data to the socket.
+    ///
+    /// Fewer bytes may be written than requested if there is insufficient room
+    /// in the peer's receive buffer. The number of bytes written is returned.
+    pub fn write(&self, data: &[u8]) -> ZxResult<usize> {
+        let peer = self.peer.upgrade().ok_or(ZxError::PEER_CLOSED)?;
+        let mut recv_queue = peer.recv_queue.lock();
+        let rest_capacity = SOCKET_BUFFER_SIZE - recv_queue.len();
+        if rest_capacity == 0 {
+            return Err(ZxError::SHOULD_WAIT);
+        }
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if recv_queue.is_empty() {
+            peer.base.signal_set(Signal::READABLE);
+        }
+        let write_len = data.len().min(rest_capacity);
+        recv_queue.extend(&data[..write_len]);
+        if recv_queue.len() == SOCKET_BUFFER_SIZE {
+            self.base.signal_clear(Signal::WRITABLE);
+        }
+        Self::update_threshold_signals(&peer, Some(self), recv_queue.len());
+        Ok(write_len)
+    }
+
+    /// Read data from the socket.
+    ///
+    /// Fewer bytes may be read than requested if there is not enough data
+    /// in the receive buffer. The number of bytes read is returned.
+    pub fn read(&self, data: &mut [u8]) -> ZxResult<usize> {
+        let mut recv_queue = self.recv_queue.lock();
+        if recv_queue.is_empty() {
+            return match self.peer.upgrade() {
+                Some(_) => Err(ZxError::SHOULD_WAIT),
+                None => Err(ZxError::PEER_CLOSED),
+            };
+        }
+        let read_size = data.len().min(recv_queue.len());
+        let peer = self.peer.upgrade();
+        if recv_queue.len() == SOCKET_BUFFER_SIZE {
+            if let Some(peer) = &peer {
+                peer.base.signal_set(Signal::WRITABLE);
+            }
+        }
+        for (i, x) in recv_queue.drain(..read_size).enumerate() {
+            data[i] = x;
+        }
+        if recv_queue.is_empty() {
+            self.base.signal_clear(Signal::READABLE);
+        }
+        Self::update_threshold_signals(self, peer.as_deref(), recv_queue.len());
+        Ok(read_size)
+    }
+
+    /// Get the receive threshold in bytes.
+    pub fn rx_threshold(&self) -> usize {
+        self.rx_threshold.load(Ordering::SeqCst)
+    }
+
+    /// Set the receive threshold in bytes. Zero disables it.
+    pub fn set_rx_threshold(&self, threshold: usize) -> ZxResult {
+        if threshold > SOCKET_BUFFER_SIZE {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let recv_queue = self.recv_queue.lock();
+        self.rx_threshold.store(threshold, Ordering::SeqCst);
+        Self::update_threshold_signals(self, None, recv_queue.len());
+        Ok(())
+    }
+
+    /// Get the transmit threshold in bytes.
+    pub fn tx_threshold(&self) -> usize {
+        self.tx_threshold.load(Ordering::SeqCst)
+    }
+
+    /// Set the transmit threshold in bytes. Zero disables it.
+    pub fn set_tx_threshold(&self, threshold: usize) -> ZxResult {
+        if threshold > SOCKET_BUFFER_SIZE {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let peer = self.peer.upgrade().ok_or(ZxError::PEER_CLOSED)?;
+        let recv_queue = peer.recv_queue.lock();
+        self.tx_threshold.store(threshold, Ordering::SeqCst);
+        Self::update_threshold_signals(&peer, Some(self), recv_queue.len());
+        Ok(())
+    }
+
+    /// Update threshold signals after the receive buffer of `receiver`
+    /// changes to `len` bytes.
+    ///
+    /// The caller must hold the lock of the receive buffer.
+    fn update_threshold_signals(receiver: &Socket, sender: Option<&Socket>, len: usize) {
+        let rx_threshold = receiver.rx_threshold.load(Ordering::SeqCst);
+        if rx_threshold != 0 && len >= rx_threshold {
+            receiver.base.signal_set(Signal::SOCKET_READ_THRESHOLD);
+        } else {
+            receiver.base.signal_clear(Signal::SOCKET_READ_THRESHOLD);
+        }
+        if let Some(sender) = sender {
+            let tx_threshold = sender.tx_threshold.load(Ordering::SeqCst);
+            if tx_threshold != 0 && SOCKET_BUFFER_SIZE - len >= tx_threshold {
+                sender.base.signal_set(Signal::SOCKET_WRITE_THRESHOLD);
+            } else {
+                sender.base.signal_clear(Signal::SOCKET_WRITE_THRESHOLD);
+            }
+        }
+    }
+
+    /// Get information of the socket.
+    pub fn get_info(&self) -> SocketInfo {
+        let rx_buf_size = self.recv_queue.lock().len() as u64;
+        let tx_buf_size = match self.peer.upgrade() {
+            Some(peer) => peer.recv_queue.lock().len() as u64,
+            None => 0,
+        };
+        SocketInfo {
+            options: self.options,
+            padding1: 0,
+            rx_buf_max: SOCKET_BUFFER_SIZE as u64,
+            rx_buf_size,
+            rx_buf_available: rx_buf_size,
+            tx_buf_max: SOCKET_BUFFER_SIZE as u64,
+            tx_buf_size,
+        }
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Some(peer) = self.peer.upgrade() {
+            peer.base
+                .signal_change(Signal::WRITABLE, Signal::PEER_CLOSED);
+        }
+    }
+}
+
+/// Information of a socket, returned by `ZX_INFO_SOCKET`.
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct SocketInfo {
+    /// The options passed to `zx_socket_create()`.
+    pub options: u32,
+    padding1: u32,
+    /// The maximum size of the receive buffer in bytes.
+    pub rx_buf_max: u64,
+    /// The number of bytes in the receive buffer.
+    pub rx_buf_size: u64,
+    /// The number of bytes available to read.
+    pub rx_buf_available: u64,
+    /// The maximum size of the transmit buffer in bytes.
+    pub tx_buf_max: u64,
+    /// The number of bytes in the transmit buffer.
+    pub tx_buf_size: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_write() {
+        let (end0, end1) = Socket::create();
+        assert_eq!(end1.read(&mut [0; 4]), Err(ZxError::SHOULD_WAIT));
+        assert_eq!(end0.write(&[1, 2, 3]), Ok(3));
+        assert!(end1.signal().contains(Signal::READABLE));
+
+        let mut buf = [0; 2];
+        assert_eq!(end1.read(&mut buf), Ok(2));
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(end1.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], 3);
+        assert!(!end1.signal().contains(Signal::READABLE));
+
+        drop(end0);
+        assert_eq!(end1.read(&mut buf), Err(ZxError::PEER_CLOSED));
+        assert!(end1.signal().contains(Signal::PEER_CLOSED));
+    }
+
+    #[test]
+    fn info() {
+        let (end0, end1) = Socket::create();
+        assert_eq!(end0.write(&[0; 10]), Ok(10));
+
+        let info = end1.get_info();
+        assert_eq!(info.rx_buf_max, SOCKET_BUFFER_SIZE as u64);
+        assert_eq!(info.rx_buf_size, 10);
+        assert_eq!(info.rx_buf_available, 10);
+        assert_eq!(info.tx_buf_size, 0);
+        assert_eq!(end0.get_info().tx_buf_size, 10);
+
+        end1.read(&mut [0; 4]).unwrap();
+        assert_eq!(end1.get_info().rx_buf_available, 6);
+    }
+
+    #[async_std::test]
+    async fn threshold() {
+        let (end0, end1) = Socket::create();
+        assert_eq!(
+            end1.set_rx_threshold(SOCKET_BUFFER_SIZE + 1),
+            Err(ZxError::INVALID_ARGS)
+        );
+        end1.set_rx_threshold(10).unwrap();
+        assert_eq!(end1.rx_threshold(), 10);
+        end0.write(&[0; 5]).unwrap();
+        assert!(!end1.signal().contains(Signal::SOCKET_READ_THRESHOLD));
+
+        async_std::task::spawn({
+            let end0 = end0.clone();
+            async move {
+                end0.write(&[0; 5]).unwrap();
+            }
+        });
+        let object: Arc<dyn KernelObject> = end1.clone();
+        let signal = object.wait_signal(Signal::SOCKET_READ_THRESHOLD).await;
+        assert!(signal.contains(Signal::SOCKET_READ_THRESHOLD));
+
+        // free space of the peer's buffer is below the threshold
+        end0.set_tx_threshold(SOCKET_BUFFER_SIZE).unwrap();
+        assert_eq!(end0.tx_threshold(), SOCKET_BUFFER_SIZE);
+        assert!(!end0.signal().contains(Signal::SOCKET_WRITE_THRESHOLD));
+        end1.read(&mut [0; 10]).unwrap();
+        assert!(end0.signal().contains(Signal::SOCKET_WRITE_THRESHOLD));
+    }
+
+    #[async_std::test]
+    async fn peer_closed() {
+        let (end0, end1) = Socket::create();
+        async_std::task::spawn(async move {
+            async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+            drop(end0);
+        });
+        let object: Arc<dyn KernelObject> = end1.clone();
+        let signal = object
+            .wait_signal(Signal::READABLE | Signal::PEER_CLOSED)
+            .await;
+        assert!(signal.contains(Signal::PEER_CLOSED));
+        assert!(!signal.contains(Signal::READABLE));
+        assert!(!signal.contains(Signal::WRITABLE));
+    }
 }
@@ -0,0 +1,172 @@
+//! Kernel trace of syscalls.
+//!
+//! Records are kept in a ring buffer per CPU, so recording only contends
+//! with the readers and never with other CPUs.
+//! When a ring is full the oldest record is overwritten.
+
+use {
+    crate::object::KoID,
+    alloc::vec::Vec,
+    core::sync::atomic::{AtomicUsize, Ordering},
+    core::time::Duration,
+    kernel_hal::MAX_CPU_NUM,
+    spin::Mutex,
+};
+
+/// The maximum number of records kept by each CPU.
+const RECORDS_PER_CPU: usize = 256;
+
+/// A trace record of one syscall.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallRecord {
+    /// The syscall number.
+    pub num: u32,
+    /// The CPU the syscall returned on.
+    pub cpu: u32,
+    /// The koid of the calling thread.
+    pub thread: KoID,
+    /// The raw arguments.
+    pub args: [u64; 8],
+    /// The raw return value.
+    pub result: i64,
+    /// The time the syscall was entered, in nanoseconds.
+    pub start: u64,
+    /// The time spent in the syscall, in nanoseconds.
+    pub duration: u64,
+}
+
+impl SyscallRecord {
+    /// Create a record of a syscall entered at `start` and returning now.
+    pub fn new(num: u32, thread: KoID, args: [usize; 8], result: isize, start: Duration) -> Self {
+        let now = kernel_hal::timer_now();
+        let mut raw_args = [0u64; 8];
+        for (raw, arg) in raw_args.iter_mut().zip(args.iter()) {
+            *raw = *arg as u64;
+        }
+        SyscallRecord {
+            num,
+            cpu: kernel_hal::cpu_id() as u32,
+            thread,
+            args: raw_args,
+            result: result as i64,
+            start: start.as_nanos() as u64,
+            duration: now.checked_sub(start).unwrap_or_default().as_nanos() as u64,
+        }
+    }
+}
+
+struct Ring {
+    records: Vec<SyscallRecord>,
+    /// The index of the oldest record once the ring is full.
+    head: usize,
+}
+
+impl Ring {
+    fn push(&mut self, record: SyscallRecord) -> bool {
+        if self.records.len() < RECORDS_PER_CPU {
+            self.records.push(record);
+            return true;
+        }
+        self.records[self.head] = record;
+        self.head = (self.head + 1) % RECORDS_PER_CPU;
+        false
+    }
+
+    fn drain_into(&mut self, out: &mut Vec<SyscallRecord>) {
+        let head = self.head;
+        out.extend_from_slice(&self.records[head..]);
+        out.extend_from_slice(&self.records[..head]);
+        self.records.clear();
+        self.head = 0;
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const RING_INIT: Mutex<Ring> = Mutex::new(Ring {
+    records: Vec::new(),
+    head: 0,
+});
+
+static RINGS: [Mutex<Ring>; MAX_CPU_NUM] = [RING_INIT; MAX_CPU_NUM];
+
+/// The number of records overwritten before being read.
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Add a record to the ring of the current CPU.
+pub fn record(record: SyscallRecord) {
+    let cpu = kernel_hal::cpu_id() as usize % MAX_CPU_NUM;
+    if !RINGS[cpu].lock().push(record) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Get the number of records in all rings.
+pub fn len() -> usize {
+    RINGS.iter().map(|ring| ring.lock().records.len()).sum()
+}
+
+/// Take all records out of the rings, sorted by the start time.
+pub fn drain() -> Vec<SyscallRecord> {
+    let mut records = Vec::new();
+    for ring in RINGS.iter() {
+        ring.lock().drain_into(&mut records);
+    }
+    records.sort_by_key(|r| r.start);
+    records
+}
+
+/// Get the number of records overwritten before being read.
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dummy(num: u32, start: u64) -> SyscallRecord {
+        SyscallRecord {
+            num,
+            start,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ring() {
+        let mut ring = Ring {
+            records: Vec::new(),
+            head: 0,
+        };
+        for i in 0..RECORDS_PER_CPU as u32 + 2 {
+            assert_eq!(ring.push(dummy(i, i as u64)), i < RECORDS_PER_CPU as u32);
+        }
+        let mut out = Vec::new();
+        ring.drain_into(&mut out);
+        assert_eq!(out.len(), RECORDS_PER_CPU);
+        // the 2 oldest records are overwritten
+        assert_eq!(out[0].num, 2);
+        assert_eq!(out[RECORDS_PER_CPU - 1].num, RECORDS_PER_CPU as u32 + 1);
+        assert!(out.windows(2).all(|w| w[0].num + 1 == w[1].num));
+
+        ring.drain_into(&mut out);
+        assert_eq!(out.len(), RECORDS_PER_CPU);
+    }
+
+    #[test]
+    fn record_and_drain() {
+        let start = kernel_hal::timer_now();
+        let args = [1, 2, 3, 4, 5, 6, 7, 8];
+        record(SyscallRecord::new(1, 100, args, 0, start));
+        record(SyscallRecord::new(2, 100, args, -10, start));
+        let records = drain();
+        // other tests may record at the same time
+        let mine: Vec<_> = records.iter().filter(|r| r.thread == 100).collect();
+        assert_eq!(mine.len(), 2);
+        assert_eq!(mine[0].args, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(mine[1].result, -10);
+        assert_eq!(mine[0].start, start.as_nanos() as u64);
+        assert!(drain().iter().all(|r| r.thread != 100));
+    }
+}
@@ -25,6 +25,7 @@ pub mod dev;
 mod error;
 pub mod exception;
 pub mod ipc;
+pub mod ktrace;
 pub mod object;
 pub mod resource;
 pub mod signal;
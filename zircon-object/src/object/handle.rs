@@ -11,11 +11,17 @@ pub const INVALID_HANDLE: HandleValue = 0;
 pub struct Handle {
     pub object: Arc<dyn KernelObject>,
     pub rights: Rights,
+    counter: HandleCounter,
 }
 
 impl Handle {
     pub fn new(object: Arc<dyn KernelObject>, rights: Rights) -> Self {
-        Handle { object, rights }
+        let counter = HandleCounter::new(object.clone());
+        Handle {
+            object,
+            rights,
+            counter,
+        }
     }
 
     pub fn get_info(&self) -> HandleBasicInfo {
@@ -33,6 +39,20 @@ impl Handle {
         }
     }
 
+    /// Get information of the handle with `handle_value` in a process.
+    pub fn get_extended_info(&self, handle_value: HandleValue) -> HandleInfoExtended {
+        HandleInfoExtended {
+            obj_type: obj_type(&self.object),
+            handle_value,
+            rights: self.rights.bits(),
+            koid: self.object.id(),
+            related_koid: self.object.related_koid(),
+            // TODO: find the process owning the peer
+            peer_owner_koid: 0,
+            ..Default::default()
+        }
+    }
+
     pub fn get_handle_info(&self) -> HandleInfo {
         HandleInfo {
             obj_type: obj_type(&self.object),
@@ -42,6 +62,36 @@ impl Handle {
     }
 }
 
+/// Tracks the handle count of the object while the handle is alive.
+///
+/// Kept as a separate field so that `object` can still be moved out of a `Handle`.
+struct HandleCounter(Arc<dyn KernelObject>);
+
+impl HandleCounter {
+    fn new(object: Arc<dyn KernelObject>) -> Self {
+        object.inc_handle_count();
+        HandleCounter(object)
+    }
+}
+
+impl Clone for HandleCounter {
+    fn clone(&self) -> Self {
+        HandleCounter::new(self.0.clone())
+    }
+}
+
+impl Drop for HandleCounter {
+    fn drop(&mut self) {
+        self.0.dec_handle_count();
+    }
+}
+
+impl core::fmt::Debug for HandleCounter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0.handle_count())
+    }
+}
+
 #[repr(C)]
 #[derive(Default, Debug)]
 pub struct HandleBasicInfo {
@@ -53,7 +103,27 @@ pub struct HandleBasicInfo {
     padding: u32,
 }
 
-fn obj_type(object: &Arc<dyn KernelObject>) -> u32 {
+/// Information of a handle in a process, returned by `ZX_INFO_HANDLE_TABLE`.
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct HandleInfoExtended {
+    /// The `ZX_OBJ_TYPE_*` of the object.
+    pub obj_type: u32,
+    /// The handle value in the process.
+    pub handle_value: HandleValue,
+    /// The rights of the handle.
+    pub rights: u32,
+    reserved: u32,
+    /// The koid of the object.
+    pub koid: u64,
+    /// The koid of the related object, see `ZX_INFO_HANDLE_BASIC`.
+    pub related_koid: u64,
+    /// The koid of the process owning the peer of the object, or 0.
+    pub peer_owner_koid: u64,
+}
+
+/// Get the `ZX_OBJ_TYPE_*` value of the object.
+pub fn obj_type(object: &Arc<dyn KernelObject>) -> u32 {
     match object.type_name() {
         "Process" => 1,
         "Thread" => 2,
@@ -94,3 +164,23 @@ pub struct HandleInfo {
     rights: u32,
     unused: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::Channel;
+
+    #[test]
+    fn handle_count() {
+        let (channel, _) = Channel::create();
+        assert_eq!(channel.handle_count(), 0);
+        let handle = Handle::new(channel.clone(), Rights::DEFAULT_CHANNEL);
+        assert_eq!(channel.handle_count(), 1);
+        let handle1 = handle.clone();
+        assert_eq!(channel.handle_count(), 2);
+        drop(handle);
+        assert_eq!(channel.handle_count(), 1);
+        drop(handle1);
+        assert_eq!(channel.handle_count(), 0);
+    }
+}
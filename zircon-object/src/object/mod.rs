@@ -105,6 +105,7 @@ use {
         task::{Context, Poll},
     },
     downcast_rs::{impl_downcast, DowncastSync},
+    futures::channel::oneshot::Receiver,
     spin::Mutex,
 };
 
@@ -128,6 +129,10 @@ pub trait KernelObject: DowncastSync + Debug {
     fn signal_set(&self, signal: Signal);
     fn signal_change(&self, clear: Signal, set: Signal);
     fn add_signal_callback(&self, callback: SignalHandler);
+    fn signal_callback_count(&self) -> usize;
+    fn handle_count(&self) -> u32;
+    fn inc_handle_count(&self);
+    fn dec_handle_count(&self);
     fn get_child(&self, _id: KoID) -> ZxResult<Arc<dyn KernelObject>> {
         Err(ZxError::WRONG_TYPE)
     }
@@ -147,6 +152,7 @@ impl_downcast!(sync KernelObject);
 /// The base struct of a kernel object.
 pub struct KObjectBase {
     pub id: KoID,
+    handle_count: AtomicU32,
     inner: Mutex<KObjectBaseInner>,
 }
 
@@ -162,6 +168,7 @@ impl Default for KObjectBase {
     fn default() -> Self {
         KObjectBase {
             id: Self::new_koid(),
+            handle_count: AtomicU32::new(0),
             inner: Default::default(),
         }
     }
@@ -177,6 +184,7 @@ impl KObjectBase {
     pub fn with_signal(signal: Signal) -> Self {
         KObjectBase {
             id: Self::new_koid(),
+            handle_count: AtomicU32::new(0),
             inner: Mutex::new(KObjectBaseInner {
                 signal,
                 ..Default::default()
@@ -188,6 +196,7 @@ impl KObjectBase {
     pub fn with_name(name: &str) -> Self {
         KObjectBase {
             id: Self::new_koid(),
+            handle_count: AtomicU32::new(0),
             inner: Mutex::new(KObjectBaseInner {
                 name: String::from(name),
                 ..Default::default()
@@ -199,6 +208,7 @@ impl KObjectBase {
     pub fn with(name: &str, signal: Signal) -> Self {
         KObjectBase {
             id: Self::new_koid(),
+            handle_count: AtomicU32::new(0),
             inner: Mutex::new(KObjectBaseInner {
                 name: String::from(name),
                 signal,
@@ -219,8 +229,31 @@ impl KObjectBase {
     }
 
     /// Set object's name.
+    ///
+    /// The name is truncated at the first NUL and to at most
+    /// `MAX_NAME_LEN - 1` bytes, so that it fits in a NUL-terminated buffer.
     pub fn set_name(&self, name: &str) {
-        self.inner.lock().name = String::from(name);
+        let name = name.split('\0').next().unwrap();
+        let mut len = name.len().min(MAX_NAME_LEN - 1);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.inner.lock().name = String::from(&name[..len]);
+    }
+
+    /// Get the number of handles referring to the object.
+    pub fn handle_count(&self) -> u32 {
+        self.handle_count.load(Ordering::Relaxed)
+    }
+
+    /// Increase the handle count when a handle to the object is created.
+    pub fn inc_handle_count(&self) {
+        self.handle_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Decrease the handle count when a handle to the object is dropped.
+    pub fn dec_handle_count(&self) {
+        self.handle_count.fetch_sub(1, Ordering::Relaxed);
     }
 
     /// Get the signal status.
@@ -262,6 +295,14 @@ impl KObjectBase {
         let mut inner = self.inner.lock();
         inner.signal_callbacks.push(callback);
     }
+
+    /// Get the number of signal callbacks, e.g. pending async waits.
+    ///
+    /// A callback is only removed when it is called, so a canceled wait
+    /// is counted until the next signal change.
+    pub fn signal_callback_count(&self) -> usize {
+        self.inner.lock().signal_callbacks.len()
+    }
 }
 
 impl dyn KernelObject {
@@ -309,39 +350,96 @@ impl dyn KernelObject {
 
     /// Once one of the `signal` asserted, push a packet with `key` into the `port`,
     ///
-    /// It's used to implement `sys_object_wait_async`.
+    /// If `options` contains `TIMESTAMP`, the time when the signal asserted
+    /// is recorded into the packet.
+    pub fn send_signal_to_port_async(
+        self: &Arc<Self>,
+        signal: Signal,
+        port: &Arc<Port>,
+        key: u64,
+        options: WaitAsyncOptions,
+    ) {
+        self.send_signal_to_port_async_inner(signal, port, key, options, None);
+    }
+
+    /// Same as `send_signal_to_port_async`, but no packet will be pushed
+    /// once `cancel_token` completes or is dropped.
+    ///
+    /// It's used to implement `sys_object_wait_async`, so that the wait is
+    /// canceled when the handle is closed.
+    pub fn cancelable_send_signal_to_port_async(
+        self: &Arc<Self>,
+        signal: Signal,
+        port: &Arc<Port>,
+        key: u64,
+        options: WaitAsyncOptions,
+        cancel_token: Receiver<()>,
+    ) {
+        self.send_signal_to_port_async_inner(signal, port, key, options, Some(cancel_token));
+    }
+
     #[allow(unsafe_code)]
-    pub fn send_signal_to_port_async(self: &Arc<Self>, signal: Signal, port: &Arc<Port>, key: u64) {
+    fn send_signal_to_port_async_inner(
+        self: &Arc<Self>,
+        signal: Signal,
+        port: &Arc<Port>,
+        key: u64,
+        options: WaitAsyncOptions,
+        cancel_token: Option<Receiver<()>>,
+    ) {
+        let timestamp = options.contains(WaitAsyncOptions::TIMESTAMP);
+        let source = self.id();
+        let canceled = port.add_wait(source, key);
         let current_signal = self.signal();
         if !(current_signal & signal).is_empty() {
-            port.push(PortPacketRepr {
-                key,
-                status: ZxError::OK,
-                data: PayloadRepr::Signal(PacketSignal {
-                    trigger: signal,
-                    observed: current_signal,
-                    count: 1,
-                    timestamp: 0,
-                }),
-            });
+            port.push_wait_packet(
+                source,
+                &canceled,
+                timestamp,
+                PortPacketRepr {
+                    key,
+                    status: ZxError::OK,
+                    data: PayloadRepr::Signal(PacketSignal {
+                        trigger: signal,
+                        observed: current_signal,
+                        count: 1,
+                        timestamp: 0,
+                    }),
+                },
+            );
             return;
         }
+        let cancel_token = cancel_token.map(Mutex::new);
         self.add_signal_callback(Box::new({
             let port = port.clone();
             move |s| {
+                if let Some(token) = &cancel_token {
+                    if !matches!(token.lock().try_recv(), Ok(None)) {
+                        // canceled: drop the callback without a packet
+                        return true;
+                    }
+                }
+                if canceled.load(Ordering::SeqCst) {
+                    return true;
+                }
                 if (s & signal).is_empty() {
                     return false;
                 }
-                port.push(PortPacketRepr {
-                    key,
-                    status: ZxError::OK,
-                    data: PayloadRepr::Signal(PacketSignal {
-                        trigger: signal,
-                        observed: s,
-                        count: 1,
-                        timestamp: 0,
-                    }),
-                });
+                port.push_wait_packet(
+                    source,
+                    &canceled,
+                    timestamp,
+                    PortPacketRepr {
+                        key,
+                        status: ZxError::OK,
+                        data: PayloadRepr::Signal(PacketSignal {
+                            trigger: signal,
+                            observed: s,
+                            count: 1,
+                            timestamp: 0,
+                        }),
+                    },
+                );
                 true
             }
         }));
@@ -431,6 +529,18 @@ macro_rules! impl_kobject {
             fn add_signal_callback(&self, callback: SignalHandler) {
                 self.base.add_signal_callback(callback);
             }
+            fn signal_callback_count(&self) -> usize {
+                self.base.signal_callback_count()
+            }
+            fn handle_count(&self) -> u32 {
+                self.base.handle_count()
+            }
+            fn inc_handle_count(&self) {
+                self.base.inc_handle_count();
+            }
+            fn dec_handle_count(&self) {
+                self.base.dec_handle_count();
+            }
             $( $fn )*
         }
         impl core::fmt::Debug for $class {
@@ -469,6 +579,9 @@ macro_rules! define_count_helper {
     };
 }
 
+/// The maximum length of an object's name, including the trailing NUL.
+pub const MAX_NAME_LEN: usize = 32;
+
 /// The type of kernel object ID.
 pub type KoID = u64;
 
@@ -495,6 +608,19 @@ mod tests {
     use super::*;
     use std::time::Duration;
 
+    #[test]
+    fn name() {
+        let object = DummyObject::new();
+        object.set_name(&"a".repeat(31));
+        assert_eq!(object.name(), "a".repeat(31));
+        object.set_name(&"b".repeat(32));
+        assert_eq!(object.name(), "b".repeat(31));
+        object.set_name(&"c".repeat(40));
+        assert_eq!(object.name(), "c".repeat(31));
+        object.set_name("abc\0def");
+        assert_eq!(object.name(), "abc");
+    }
+
     #[async_std::test]
     async fn wait() {
         let object = DummyObject::new();
@@ -561,4 +687,22 @@ mod tests {
         assert_eq!(signals, [Signal::READABLE, Signal::WRITABLE]);
         assert_eq!(flag.load(Ordering::SeqCst), 2);
     }
+
+    #[test]
+    fn signal_callback_count() {
+        let port = Port::new();
+        let object = DummyObject::new() as Arc<dyn KernelObject>;
+        assert_eq!(object.signal_callback_count(), 0);
+        for key in 0..3 {
+            object.send_signal_to_port_async(
+                Signal::READABLE,
+                &port,
+                key,
+                WaitAsyncOptions::empty(),
+            );
+        }
+        assert_eq!(object.signal_callback_count(), 3);
+        object.signal_set(Signal::READABLE);
+        assert_eq!(object.signal_callback_count(), 0);
+    }
 }
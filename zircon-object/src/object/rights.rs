@@ -91,10 +91,14 @@ bitflags! {
         const DEFAULT_PORT = (Self::BASIC.bits & !Self::WAIT.bits) | Self::IO.bits;
         const DEFAULT_TIMER = Self::BASIC.bits | Self::WRITE.bits | Self::SIGNAL.bits;
         const DEFAULT_EVENT = Self::BASIC.bits | Self::SIGNAL.bits;
+        const DEFAULT_SYSTEM_EVENT_LOW_MEMORY = Self::WAIT.bits | Self::DUPLICATE.bits | Self::TRANSFER.bits;
         const DEFAULT_EVENTPAIR = Self::BASIC.bits | Self::SIGNAL.bits | Self::SIGNAL_PEER.bits;
         const DEFAULT_FIFO = Self::BASIC.bits | Self::IO.bits | Self::SIGNAL.bits | Self::SIGNAL_PEER.bits;
         const DEFAULT_SOCKET = Self::BASIC.bits | Self::IO.bits | Self::PROPERTY.bits | Self::SIGNAL.bits | Self::SIGNAL_PEER.bits;
+        const DEFAULT_STREAM = (Self::BASIC.bits & !Self::WAIT.bits) | Self::PROPERTY.bits | Self::SIGNAL.bits;
+        const DEFAULT_PROFILE = Self::BASIC.bits | Self::APPLY_PROFILE.bits;
         const DEFAULT_BTI = (Self::BASIC.bits & !Self::WAIT.bits) | Self::IO.bits | Self::MAP.bits;
+        const DEFAULT_PMT = Self::INSPECT.bits;
     }
 }
 
@@ -105,3 +109,39 @@ impl TryFrom<u32> for Rights {
         Self::from_bits(x).ok_or(ZxError::INVALID_ARGS)
     }
 }
+
+/// Get the rights of a new handle derived from a handle with `current` rights.
+///
+/// `Rights::SAME_RIGHTS` keeps all of `current`. Otherwise `requested` must be
+/// a subset of `current`, or `ZxError::ACCESS_DENIED` is returned.
+pub fn reduce_rights(current: Rights, requested: Rights) -> ZxResult<Rights> {
+    if requested.contains(Rights::SAME_RIGHTS) {
+        return Ok(current);
+    }
+    if !current.contains(requested) {
+        return Err(ZxError::ACCESS_DENIED);
+    }
+    Ok(current & requested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduce() {
+        let current = Rights::READ | Rights::WRITE | Rights::DUPLICATE;
+        assert_eq!(reduce_rights(current, Rights::READ), Ok(Rights::READ));
+        assert_eq!(reduce_rights(current, Rights::empty()), Ok(Rights::empty()));
+        assert_eq!(reduce_rights(current, current), Ok(current));
+        assert_eq!(reduce_rights(current, Rights::SAME_RIGHTS), Ok(current));
+        assert_eq!(
+            reduce_rights(current, Rights::READ | Rights::EXECUTE),
+            Err(ZxError::ACCESS_DENIED)
+        );
+        assert_eq!(
+            reduce_rights(Rights::empty(), Rights::READ),
+            Err(ZxError::ACCESS_DENIED)
+        );
+    }
+}
@@ -33,6 +33,8 @@ bitflags! {
         const THREAD_SUSPENDED              = 1 << 5;
 
         const VMO_ZERO_CHILDREN             = Self::SIGNALED.bits;
+        /// zCore specific: the committed bytes reached the threshold.
+        const VMO_COMMIT_THRESHOLD          = 1 << 4;
 
         // for Linux
         const SIGCHLD                       = 1 << 6;
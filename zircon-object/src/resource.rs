@@ -82,6 +82,30 @@ impl Resource {
         }
     }
 
+    /// Get information of the resource.
+    ///
+    /// Root resources have zero base and size.
+    pub fn get_info(&self) -> ResourceInfo {
+        let (base, size) = match self.kind {
+            ResourceKind::ROOT => (0, 0),
+            _ => (self.addr as u64, self.len as u64),
+        };
+        ResourceInfo {
+            kind: self.kind as u32,
+            flags: self.flags.bits(),
+            base,
+            size,
+            name: {
+                let mut arr = [0u8; MAX_NAME_LEN];
+                let name = self.base.name();
+                // keep the last byte for NUL
+                let length = name.len().min(MAX_NAME_LEN - 1);
+                arr[..length].copy_from_slice(&name.as_bytes()[..length]);
+                arr
+            },
+        }
+    }
+
     pub fn check_exclusive(&self, flags: ResourceFlags) -> ZxResult {
         if self.kind != ResourceKind::ROOT
             && (self.flags.contains(ResourceFlags::EXCLUSIVE)
@@ -93,3 +117,59 @@ impl Resource {
         }
     }
 }
+
+/// Information of a resource, returned by `ZX_INFO_RESOURCE`.
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct ResourceInfo {
+    /// The kind of the resource.
+    pub kind: u32,
+    /// The resource's creation flags.
+    pub flags: u32,
+    /// The resource's base location.
+    pub base: u64,
+    /// The size of the resource.
+    pub size: u64,
+    /// The name of the resource.
+    pub name: [u8; MAX_NAME_LEN],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info() {
+        let root = Resource::create(
+            "root",
+            ResourceKind::ROOT,
+            0x1000,
+            0x1000,
+            ResourceFlags::empty(),
+        );
+        let info = root.get_info();
+        assert_eq!(info.kind, ResourceKind::ROOT as u32);
+        assert_eq!((info.base, info.size), (0, 0));
+        assert_eq!(&info.name[..5], b"root\0");
+
+        let irq = Resource::create("irq", ResourceKind::IRQ, 1, 2, ResourceFlags::EXCLUSIVE);
+        let info = irq.get_info();
+        assert_eq!(info.kind, ResourceKind::IRQ as u32);
+        assert_eq!(info.flags, ResourceFlags::EXCLUSIVE.bits());
+        assert_eq!((info.base, info.size), (1, 2));
+
+        let long = Resource::create(
+            core::str::from_utf8(&[b'a'; MAX_NAME_LEN]).unwrap(),
+            ResourceKind::MMIO,
+            0,
+            0x1000,
+            ResourceFlags::empty(),
+        );
+        let info = long.get_info();
+        assert_eq!(
+            &info.name[..MAX_NAME_LEN - 1],
+            &[b'a'; MAX_NAME_LEN - 1][..]
+        );
+        assert_eq!(info.name[MAX_NAME_LEN - 1], 0);
+    }
+}
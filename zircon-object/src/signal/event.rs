@@ -29,3 +29,32 @@ impl Event {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn user_signal() {
+        let event = Event::new();
+        let allowed = event.allowed_signals();
+        let set = Signal::verify_user_signal(allowed, Signal::USER_SIGNAL_0.bits()).unwrap();
+        assert_eq!(
+            Signal::verify_user_signal(allowed, Signal::READABLE.bits()),
+            Err(ZxError::INVALID_ARGS)
+        );
+
+        async_std::task::spawn({
+            let event = event.clone();
+            async move {
+                event.signal_change(Signal::empty(), set);
+            }
+        });
+        let object: Arc<dyn KernelObject> = event.clone();
+        let signal = object.wait_signal(Signal::USER_SIGNAL_0).await;
+        assert!(signal.contains(Signal::USER_SIGNAL_0));
+
+        event.signal_change(set, Signal::SIGNALED);
+        assert_eq!(event.signal(), Signal::SIGNALED);
+    }
+}
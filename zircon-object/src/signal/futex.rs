@@ -69,7 +69,7 @@ impl Futex {
             if let Some(waiter) = inner.waiter_queue.pop_front() {
                 waiter.wake();
             } else {
-                return i + 1;
+                return i;
             }
         }
         wake_count
@@ -130,13 +130,13 @@ impl Futex {
                 }
                 // first time?
                 if inner.waker.is_none() {
-                    // check value
+                    // check value under the lock, so that no wake can get in between
+                    let mut futex = inner.futex.inner.lock();
                     let value = inner.futex.value.load(Ordering::SeqCst);
                     if value != self.current_value {
                         return Poll::Ready(Err(ZxError::BAD_STATE));
                     }
                     // check new owner
-                    let mut futex = inner.futex.inner.lock();
                     if !futex.is_valid_new_owner(&self.new_owner) {
                         return Poll::Ready(Err(ZxError::INVALID_ARGS));
                     }
@@ -148,6 +148,13 @@ impl Futex {
             }
         }
 
+        impl Drop for FutexFuture {
+            fn drop(&mut self) {
+                // timed out or cancelled, don't take a wake from others
+                self.waiter.cancel();
+            }
+        }
+
         FutexFuture {
             waiter: Arc::new(Waiter {
                 thread,
@@ -194,6 +201,11 @@ impl Futex {
     ///
     /// The owner of this futex is set to nothing, regardless of the wake count.
     /// The owner of the `requeue_futex` is set to the thread `new_requeue_owner`.
+    ///
+    /// # Errors
+    ///
+    /// - `INVALID_ARGS`: `requeue_futex` is this futex.
+    /// - `BAD_STATE`: `current_value` does not match the value of this futex.
     pub fn requeue(
         &self,
         current_value: i32,
@@ -202,6 +214,9 @@ impl Futex {
         requeue_futex: &Arc<Futex>,
         new_requeue_owner: Option<Arc<Thread>>,
     ) -> ZxResult {
+        if core::ptr::eq(self, requeue_futex.as_ref()) {
+            return Err(ZxError::INVALID_ARGS);
+        }
         let mut inner = self.inner.lock();
         // check value
         if self.value.load(Ordering::SeqCst) != current_value {
@@ -272,6 +287,21 @@ impl Waiter {
         inner.waker.as_ref().unwrap().wake_by_ref();
     }
 
+    /// Remove the waiter from the queue of its futex, if still there.
+    fn cancel(self: &Arc<Self>) {
+        loop {
+            let futex = self.inner.lock().futex.clone();
+            let mut futex_inner = futex.inner.lock();
+            // it may have been requeued to another futex
+            if Arc::ptr_eq(&self.inner.lock().futex, &futex) {
+                futex_inner
+                    .waiter_queue
+                    .retain(|waiter| !Arc::ptr_eq(waiter, self));
+                return;
+            }
+        }
+    }
+
     /// Reset futex on requeue.
     fn reset_futex(&self, futex: Arc<Futex>) {
         self.inner.lock().futex = futex;
@@ -281,7 +311,9 @@ impl Waiter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec::Vec;
     use core::time::Duration;
+    use futures::FutureExt;
 
     #[async_std::test]
     async fn wait() {
@@ -305,4 +337,86 @@ mod tests {
         futex.wait(1).await.unwrap();
         assert_eq!(VALUE.load(Ordering::SeqCst), 2);
     }
+
+    #[async_std::test]
+    async fn wake_before_wait() {
+        static VALUE: AtomicI32 = AtomicI32::new(0);
+        let futex = Futex::new(&VALUE);
+
+        // the value is changed and woken before the waiter comes
+        VALUE.store(1, Ordering::SeqCst);
+        assert_eq!(futex.wake(1), 0);
+        assert_eq!(futex.wait(0).await, Err(ZxError::BAD_STATE));
+
+        // a cancelled waiter doesn't take the wake of others
+        assert!(futex.wait(1).now_or_never().is_none());
+        assert_eq!(futex.wake(1), 0);
+    }
+
+    #[async_std::test]
+    async fn wake_n() {
+        static VALUE: AtomicI32 = AtomicI32::new(0);
+        static WOKEN: AtomicUsize = AtomicUsize::new(0);
+        let futex = Futex::new(&VALUE);
+
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let futex = futex.clone();
+                async_std::task::spawn(async move {
+                    futex.wait(0).await.unwrap();
+                    WOKEN.fetch_add(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        while futex.inner.lock().waiter_queue.len() < 3 {
+            async_std::task::sleep(Duration::from_millis(1)).await;
+        }
+
+        assert_eq!(futex.wake(2), 2);
+        async_std::task::sleep(Duration::from_millis(10)).await;
+        assert_eq!(WOKEN.load(Ordering::SeqCst), 2);
+
+        assert_eq!(futex.wake(5), 1);
+        for waiter in waiters {
+            waiter.await;
+        }
+        assert_eq!(WOKEN.load(Ordering::SeqCst), 3);
+    }
+
+    #[async_std::test]
+    async fn requeue() {
+        static VALUE: AtomicI32 = AtomicI32::new(0);
+        static REQUEUE_VALUE: AtomicI32 = AtomicI32::new(0);
+        let futex = Futex::new(&VALUE);
+        let requeue_futex = Futex::new(&REQUEUE_VALUE);
+
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let futex = futex.clone();
+                async_std::task::spawn(async move { futex.wait(0).await })
+            })
+            .collect();
+        while futex.inner.lock().waiter_queue.len() < 3 {
+            async_std::task::sleep(Duration::from_millis(1)).await;
+        }
+
+        assert_eq!(
+            futex.requeue(1, 1, 2, &requeue_futex, None),
+            Err(ZxError::BAD_STATE)
+        );
+        assert_eq!(
+            futex.requeue(0, 1, 2, &futex, None),
+            Err(ZxError::INVALID_ARGS)
+        );
+        futex.requeue(0, 1, 2, &requeue_futex, None).unwrap();
+        assert_eq!(futex.inner.lock().waiter_queue.len(), 0);
+        assert_eq!(requeue_futex.inner.lock().waiter_queue.len(), 2);
+
+        // the rest are parked on the other futex
+        assert_eq!(futex.wake(5), 0);
+        assert_eq!(requeue_futex.wake(5), 2);
+        for waiter in waiters {
+            assert_eq!(waiter.await, Ok(()));
+        }
+    }
 }
@@ -0,0 +1,141 @@
+use super::*;
+use crate::object::*;
+use alloc::sync::Arc;
+use spin::{Mutex, Once};
+
+/// Memory pressure level, from the most to the least free memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PressureLevel {
+    Normal = 0,
+    Warning = 1,
+    Critical = 2,
+}
+
+/// Events signaled when free memory crosses the pressure watermarks.
+///
+/// There is one [`Event`] for each [`PressureLevel`], and only the event of
+/// the current level is `SIGNALED`. A level is entered as soon as free memory
+/// drops below its watermark, but it is left only after free memory rises
+/// `DEBOUNCE_PERCENT` above the watermark, so the signals don't flap at the
+/// boundary.
+pub struct MemoryPressureEvent {
+    events: [Arc<Event>; 3],
+    level: Mutex<PressureLevel>,
+}
+
+/// Percent of free memory below which the level is at least `Warning`.
+const WARNING_PERCENT: usize = 10;
+/// Percent of free memory below which the level is `Critical`.
+const CRITICAL_PERCENT: usize = 5;
+/// Extra percent of free memory required to leave a level.
+const DEBOUNCE_PERCENT: usize = 2;
+
+impl MemoryPressureEvent {
+    /// Create a new set of events at the `Normal` level.
+    pub fn new() -> Self {
+        let events = [Event::new(), Event::new(), Event::new()];
+        events[PressureLevel::Normal as usize].signal_set(Signal::SIGNALED);
+        MemoryPressureEvent {
+            events,
+            level: Mutex::new(PressureLevel::Normal),
+        }
+    }
+
+    /// Get the system-wide memory pressure events.
+    pub fn get() -> &'static Self {
+        static INSTANCE: Once<MemoryPressureEvent> = Once::new();
+        INSTANCE.call_once(MemoryPressureEvent::new)
+    }
+
+    /// Get the event signaled while in `level`.
+    pub fn event(&self, level: PressureLevel) -> Arc<Event> {
+        self.events[level as usize].clone()
+    }
+
+    /// Get the current pressure level.
+    pub fn level(&self) -> PressureLevel {
+        *self.level.lock()
+    }
+
+    /// Update the pressure level from the amount of free memory.
+    pub fn update(&self, free_bytes: usize, total_bytes: usize) {
+        if total_bytes == 0 {
+            return;
+        }
+        let below = |percent: usize| free_bytes * 100 < total_bytes * percent;
+        let mut level = self.level.lock();
+        let old = *level;
+        let mut new = if below(CRITICAL_PERCENT) {
+            PressureLevel::Critical
+        } else if below(WARNING_PERCENT) {
+            PressureLevel::Warning
+        } else {
+            PressureLevel::Normal
+        };
+        if new < old {
+            // only leave a level when well above its watermark
+            new = match old {
+                PressureLevel::Critical if below(CRITICAL_PERCENT + DEBOUNCE_PERCENT) => old,
+                PressureLevel::Critical if below(WARNING_PERCENT + DEBOUNCE_PERCENT) => {
+                    PressureLevel::Warning
+                }
+                PressureLevel::Warning if below(WARNING_PERCENT + DEBOUNCE_PERCENT) => old,
+                _ => new,
+            };
+        }
+        if new == old {
+            return;
+        }
+        *level = new;
+        self.events[old as usize].signal_change(Signal::SIGNALED, Signal::empty());
+        self.events[new as usize].signal_set(Signal::SIGNALED);
+        info!("memory pressure level: {:?} -> {:?}", old, new);
+    }
+}
+
+impl Default for MemoryPressureEvent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signaled(pressure: &MemoryPressureEvent, level: PressureLevel) -> bool {
+        pressure.event(level).signal().contains(Signal::SIGNALED)
+    }
+
+    #[test]
+    fn update() {
+        let pressure = MemoryPressureEvent::new();
+        assert_eq!(pressure.level(), PressureLevel::Normal);
+        assert!(signaled(&pressure, PressureLevel::Normal));
+
+        pressure.update(50, 100);
+        assert_eq!(pressure.level(), PressureLevel::Normal);
+
+        pressure.update(9, 100);
+        assert_eq!(pressure.level(), PressureLevel::Warning);
+        assert!(signaled(&pressure, PressureLevel::Warning));
+        assert!(!signaled(&pressure, PressureLevel::Normal));
+
+        // debounced at the boundary
+        pressure.update(11, 100);
+        assert_eq!(pressure.level(), PressureLevel::Warning);
+        pressure.update(12, 100);
+        assert_eq!(pressure.level(), PressureLevel::Normal);
+        assert!(signaled(&pressure, PressureLevel::Normal));
+        assert!(!signaled(&pressure, PressureLevel::Warning));
+
+        pressure.update(4, 100);
+        assert_eq!(pressure.level(), PressureLevel::Critical);
+        assert!(signaled(&pressure, PressureLevel::Critical));
+        pressure.update(6, 100);
+        assert_eq!(pressure.level(), PressureLevel::Critical);
+        pressure.update(7, 100);
+        assert_eq!(pressure.level(), PressureLevel::Warning);
+        assert!(!signaled(&pressure, PressureLevel::Critical));
+    }
+}
@@ -5,7 +5,8 @@ use super::*;
 mod event;
 mod eventpair;
 mod futex;
+mod memory_pressure;
 mod port;
 mod timer;
 
-pub use self::{event::*, eventpair::*, futex::*, port::*, timer::*};
+pub use self::{event::*, eventpair::*, futex::*, memory_pressure::*, port::*, timer::*};
@@ -2,7 +2,10 @@ pub use self::port_packet::*;
 use super::*;
 use crate::object::*;
 use alloc::collections::vec_deque::VecDeque;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use bitflags::bitflags;
+use core::sync::atomic::{AtomicBool, Ordering};
 use spin::Mutex;
 
 #[path = "port_packet.rs"]
@@ -16,6 +19,11 @@ mod port_packet;
 /// events. These events include explicit queueing on the port,
 /// asynchronous waits on other handles bound to the port, and
 /// asynchronous message delivery from IPC transports.
+///
+/// Packets are dequeued in the order they are queued. For asynchronous waits
+/// it's the order in which the signals asserted, and waits fired by the same
+/// signal change are queued in the order they were registered. Timestamps
+/// recorded with `WaitAsyncOptions::TIMESTAMP` never decrease along the queue.
 pub struct Port {
     base: KObjectBase,
     inner: Mutex<PortInner>,
@@ -23,9 +31,23 @@ pub struct Port {
 
 impl_kobject!(Port);
 
-#[derive(Default, Debug)]
+bitflags! {
+    /// Options of `object_wait_async`.
+    pub struct WaitAsyncOptions: u32 {
+        /// Record the time when the signal asserted into the packet.
+        #[allow(clippy::identity_op)]
+        const TIMESTAMP = 1 << 0;
+    }
+}
+
+#[derive(Default)]
 struct PortInner {
-    queue: VecDeque<PortPacket>,
+    /// Packets with the koid of their async wait source, or 0 if queued by user.
+    queue: VecDeque<(KoID, PortPacket)>,
+    /// Pending async waits: `(source, key, canceled)`.
+    waits: Vec<(KoID, u64, Weak<AtomicBool>)>,
+    /// The latest timestamp recorded into a packet.
+    last_timestamp: u64,
 }
 
 impl Port {
@@ -40,18 +62,86 @@ impl Port {
     /// Push a `packet` into the port.
     pub fn push(&self, packet: impl Into<PortPacket>) {
         let mut inner = self.inner.lock();
-        inner.queue.push_back(packet.into());
+        inner.queue.push_back((0, packet.into()));
         drop(inner);
         self.base.signal_set(Signal::READABLE);
     }
 
+    /// Register an async wait on `source` with `key`.
+    ///
+    /// Returns the flag set when the wait is canceled by `cancel`.
+    pub(crate) fn add_wait(&self, source: KoID, key: u64) -> Arc<AtomicBool> {
+        let canceled = Arc::new(AtomicBool::new(false));
+        let mut inner = self.inner.lock();
+        inner.waits.retain(|(_, _, flag)| flag.strong_count() != 0);
+        inner.waits.push((source, key, Arc::downgrade(&canceled)));
+        canceled
+    }
+
+    /// Push a `packet` of an async wait on `source`, unless it's `canceled`.
+    ///
+    /// If `timestamp` is true, the current time is recorded into the signal packet.
+    pub(crate) fn push_wait_packet(
+        &self,
+        source: KoID,
+        canceled: &AtomicBool,
+        timestamp: bool,
+        mut packet: PortPacketRepr,
+    ) {
+        let mut inner = self.inner.lock();
+        // checked with the lock held, so no packet is pushed after `cancel`
+        if canceled.load(Ordering::SeqCst) {
+            return;
+        }
+        if let (true, PayloadRepr::Signal(signal)) = (timestamp, &mut packet.data) {
+            // taken with the lock held, so that it's monotonic along the queue
+            let now = kernel_hal::timer_now().as_nanos() as u64;
+            signal.timestamp = now.max(inner.last_timestamp);
+            inner.last_timestamp = signal.timestamp;
+        }
+        inner.queue.push_back((source, packet.into()));
+        drop(inner);
+        self.base.signal_set(Signal::READABLE);
+    }
+
+    /// Cancel the pending async waits on `source` with `key`,
+    /// and remove their packets from the queue.
+    ///
+    /// Returns `ZxError::NOT_FOUND` if there is neither.
+    pub fn cancel(&self, source: KoID, key: u64) -> ZxResult {
+        let mut inner = self.inner.lock();
+        let mut found = false;
+        inner.waits.retain(|(s, k, flag)| match flag.upgrade() {
+            Some(flag) if *s == source && *k == key => {
+                flag.store(true, Ordering::SeqCst);
+                found = true;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        });
+        let len = inner.queue.len();
+        inner
+            .queue
+            .retain(|(s, packet)| *s != source || packet.key() != key);
+        found |= inner.queue.len() != len;
+        if inner.queue.is_empty() {
+            self.base.signal_clear(Signal::READABLE);
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(ZxError::NOT_FOUND)
+        }
+    }
+
     /// Asynchronous wait until at least one packet is available, then take out all packets.
     pub async fn wait(self: &Arc<Self>) -> PortPacket {
         let object = self.clone() as Arc<dyn KernelObject>;
         loop {
             object.wait_signal(Signal::READABLE).await;
             let mut inner = self.inner.lock();
-            if let Some(packet) = inner.queue.pop_front() {
+            if let Some((_, packet)) = inner.queue.pop_front() {
                 if inner.queue.is_empty() {
                     self.base.signal_clear(Signal::READABLE);
                 }
@@ -76,7 +166,7 @@ mod tests {
     async fn wait() {
         let port = Port::new();
         let object = DummyObject::new() as Arc<dyn KernelObject>;
-        object.send_signal_to_port_async(Signal::READABLE, &port, 1);
+        object.send_signal_to_port_async(Signal::READABLE, &port, 1, WaitAsyncOptions::empty());
 
         let packet2 = PortPacketRepr {
             key: 2,
@@ -117,4 +207,98 @@ mod tests {
         let packet = port.wait().await;
         assert_eq!(PortPacketRepr::from(&packet), packet2);
     }
+
+    #[async_std::test]
+    async fn wait_timestamp() {
+        let port = Port::new();
+        let object = DummyObject::new() as Arc<dyn KernelObject>;
+        object.send_signal_to_port_async(Signal::READABLE, &port, 1, WaitAsyncOptions::TIMESTAMP);
+        let start = kernel_hal::timer_now();
+
+        async_std::task::spawn({
+            let object = object.clone();
+            async move {
+                async_std::task::sleep(Duration::from_millis(10)).await;
+                object.signal_set(Signal::READABLE);
+            }
+        });
+
+        let packet = port.wait().await;
+        let end = kernel_hal::timer_now();
+        match PortPacketRepr::from(&packet).data {
+            PayloadRepr::Signal(signal) => {
+                let timestamp = Duration::from_nanos(signal.timestamp);
+                assert!(timestamp >= start + Duration::from_millis(10));
+                assert!(timestamp <= end);
+            }
+            _ => panic!("unexpected packet"),
+        }
+    }
+
+    #[test]
+    fn cancel_on_handle_close() {
+        use crate::task::{Job, Process};
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let port = Port::new();
+        let object = DummyObject::new() as Arc<dyn KernelObject>;
+        let handle_value = proc.add_handle(Handle::new(object.clone(), Rights::WAIT));
+        let cancel_token = proc.get_cancel_token(handle_value).unwrap();
+        object.cancelable_send_signal_to_port_async(
+            Signal::READABLE,
+            &port,
+            1,
+            WaitAsyncOptions::empty(),
+            cancel_token,
+        );
+
+        proc.remove_handle(handle_value).unwrap();
+        object.signal_set(Signal::READABLE);
+        assert_eq!(port.len(), 0);
+    }
+
+    #[test]
+    fn cancel() {
+        let port = Port::new();
+        let object = DummyObject::new() as Arc<dyn KernelObject>;
+        object.send_signal_to_port_async(Signal::READABLE, &port, 1, WaitAsyncOptions::empty());
+        object.send_signal_to_port_async(Signal::READABLE, &port, 2, WaitAsyncOptions::empty());
+        assert_eq!(port.cancel(object.id(), 1), Ok(()));
+        assert_eq!(port.cancel(object.id(), 1), Err(ZxError::NOT_FOUND));
+        assert_eq!(port.cancel(object.id() + 1, 2), Err(ZxError::NOT_FOUND));
+
+        object.signal_set(Signal::READABLE);
+        assert_eq!(port.len(), 1);
+
+        // packets already queued are removed
+        assert_eq!(port.cancel(object.id(), 2), Ok(()));
+        assert_eq!(port.len(), 0);
+        assert!(!port.signal().contains(Signal::READABLE));
+    }
+
+    #[async_std::test]
+    async fn order() {
+        let port = Port::new();
+        let object1 = DummyObject::new() as Arc<dyn KernelObject>;
+        let object2 = DummyObject::new() as Arc<dyn KernelObject>;
+        let options = WaitAsyncOptions::TIMESTAMP;
+        object1.send_signal_to_port_async(Signal::READABLE, &port, 1, options);
+        object2.send_signal_to_port_async(Signal::READABLE, &port, 2, options);
+        object2.send_signal_to_port_async(Signal::READABLE, &port, 3, options);
+
+        object2.signal_set(Signal::READABLE);
+        object1.signal_set(Signal::READABLE);
+        let mut last_timestamp = 0;
+        for &key in [2, 3, 1].iter() {
+            let packet = PortPacketRepr::from(&port.wait().await);
+            assert_eq!(packet.key, key);
+            match packet.data {
+                PayloadRepr::Signal(signal) => {
+                    assert!(signal.timestamp >= last_timestamp);
+                    last_timestamp = signal.timestamp;
+                }
+                _ => panic!("unexpected packet"),
+            }
+        }
+    }
 }
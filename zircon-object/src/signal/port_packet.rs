@@ -113,6 +113,13 @@ impl PayloadRepr {
     }
 }
 
+impl PortPacket {
+    /// Get the key of the packet.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+}
+
 impl From<PortPacketRepr> for PortPacket {
     fn from(r: PortPacketRepr) -> Self {
         PortPacket {
@@ -14,7 +14,6 @@ use spin::Mutex;
 pub struct Timer {
     base: KObjectBase,
     _counter: CountHelper,
-    #[allow(dead_code)]
     slack: Slack,
     inner: Mutex<TimerInner>,
 }
@@ -25,13 +24,14 @@ define_count_helper!(Timer);
 #[derive(Default)]
 struct TimerInner {
     deadline: Option<Duration>,
+    slack: Duration,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Slack {
-    Center,
-    Early,
-    Late,
+    Center = 0,
+    Early = 1,
+    Late = 2,
 }
 
 impl Timer {
@@ -61,9 +61,10 @@ impl Timer {
     ///
     /// If a previous call to `set` was pending, the previous timer is canceled
     /// and `Signal::SIGNALED` is de-asserted as needed.
-    pub fn set(self: &Arc<Self>, deadline: Duration, _slack: Duration) {
+    pub fn set(self: &Arc<Self>, deadline: Duration, slack: Duration) {
         let mut inner = self.inner.lock();
         inner.deadline = Some(deadline);
+        inner.slack = slack;
         self.base.signal_clear(Signal::SIGNALED);
         let me = Arc::downgrade(self);
         kernel_hal::timer_set(
@@ -78,6 +79,22 @@ impl Timer {
         inner.deadline = None;
     }
 
+    /// Get information of the timer.
+    ///
+    /// The deadline is zero if the timer is not armed.
+    pub fn get_info(&self) -> TimerInfo {
+        let inner = self.inner.lock();
+        TimerInfo {
+            options: self.slack as u32,
+            padding1: 0,
+            deadline: inner.deadline.map(|d| d.as_nanos() as i64).unwrap_or(0),
+            slack: inner
+                .deadline
+                .map(|_| inner.slack.as_nanos() as i64)
+                .unwrap_or(0),
+        }
+    }
+
     /// Called by HAL timer.
     fn touch(&self, now: Duration) {
         let mut inner = self.inner.lock();
@@ -90,6 +107,19 @@ impl Timer {
     }
 }
 
+/// Information of a timer, returned by `ZX_INFO_TIMER`.
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct TimerInfo {
+    /// The slack policy passed to `zx_timer_create()`.
+    pub options: u32,
+    padding1: u32,
+    /// The deadline in nanoseconds, or zero if the timer is not armed.
+    pub deadline: i64,
+    /// The slack of the armed deadline in nanoseconds.
+    pub slack: i64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,4 +152,21 @@ mod tests {
         std::thread::sleep(Duration::from_millis(50));
         assert_eq!(timer.signal(), Signal::empty());
     }
+
+    #[test]
+    fn info() {
+        let timer = Timer::with_slack(Slack::Late);
+        let info = timer.get_info();
+        assert_eq!(info.options, Slack::Late as u32);
+        assert_eq!(info.deadline, 0);
+
+        let deadline = timer_now() + Duration::from_secs(10);
+        timer.set(deadline, Duration::from_millis(1));
+        let info = timer.get_info();
+        assert_eq!(info.deadline, deadline.as_nanos() as i64);
+        assert_eq!(info.slack, 1_000_000);
+
+        timer.cancel();
+        assert_eq!(timer.get_info().deadline, 0);
+    }
 }
@@ -38,6 +38,10 @@ impl Exceptionate {
         let inner = self.inner.lock();
         inner.channel.clone()
     }
+
+    pub fn type_(&self) -> ExceptionChannelType {
+        self.type_
+    }
 }
 
 #[repr(C)]
@@ -49,6 +53,7 @@ pub struct ExceptionInfo {
 }
 
 #[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExceptionChannelType {
     None = 0,
     Debugger = 1,
@@ -58,6 +63,62 @@ pub enum ExceptionChannelType {
     JobDebugger = 5,
 }
 
+/// The type of an exception, `ZX_EXCP_*`.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionType {
+    General = 0x008,
+    FatalPageFault = 0x108,
+    UndefinedInstruction = 0x208,
+    SwBreakpoint = 0x308,
+    HwBreakpoint = 0x408,
+    UnalignedAccess = 0x508,
+    Synth = 0x8000,
+    ThreadStarting = 0x8008,
+    ThreadExiting = 0x8108,
+    PolicyError = 0x8208,
+    ProcessStarting = 0x8308,
+}
+
+/// Architecture-specific information of an exception.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExceptionContext {
+    /// The trap number.
+    pub vector: u64,
+    /// The error code pushed by the CPU.
+    pub err_code: u64,
+    /// The faulting address of a page fault.
+    pub cr2: u64,
+    /// Used for synthetic exceptions.
+    pub synth_code: u32,
+    /// Used for synthetic exceptions.
+    pub synth_data: u32,
+}
+
+/// The report of an exception, returned by `ZX_INFO_THREAD_EXCEPTION_REPORT`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExceptionReport {
+    /// The size of the report in bytes.
+    pub size: u32,
+    /// The type of the exception.
+    pub type_: ExceptionType,
+    /// Architecture-specific information.
+    pub context: ExceptionContext,
+}
+
+impl ExceptionReport {
+    /// Create a report of an exception of `type_`.
+    pub fn new(type_: ExceptionType, context: ExceptionContext) -> Self {
+        ExceptionReport {
+            size: size_of::<ExceptionReport>() as u32,
+            type_,
+            context,
+        }
+    }
+}
+
 impl ExceptionInfo {
     #[allow(unsafe_code)]
     pub fn pack(&self) -> Vec<u8> {
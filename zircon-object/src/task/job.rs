@@ -58,8 +58,15 @@ struct JobInner {
     processes: Vec<Arc<Process>>,
     critical_proc: Option<(KoID, bool)>,
     timer_policy: TimerSlack,
+    kill_on_oom: bool,
 }
 
+/// The return code of processes killed by the OOM killer.
+pub const TASK_RETCODE_OOM_KILL: i64 = -1025;
+
+/// The return code of processes killed by an unhandled exception.
+pub const TASK_RETCODE_EXCEPTION_KILL: i64 = -1028;
+
 impl Job {
     /// Create the root job.
     pub fn root() -> Arc<Self> {
@@ -169,8 +176,56 @@ impl Job {
         }
     }
 
+    /// Get information of the job.
+    ///
+    /// Jobs can not be killed yet, so a job never reports exited.
     pub fn get_info(&self) -> JobInfo {
-        JobInfo::default()
+        JobInfo {
+            kill_on_oom: self.inner.lock().kill_on_oom,
+            debugger_attached: self.debug_exceptionate.get_channel().is_some(),
+            ..Default::default()
+        }
+    }
+
+    /// Set whether processes in the job can be killed under memory pressure.
+    pub fn set_kill_on_oom(&self, kill_on_oom: bool) {
+        self.inner.lock().kill_on_oom = kill_on_oom;
+    }
+
+    /// Get whether processes in the job can be killed under memory pressure.
+    pub fn get_kill_on_oom(&self) -> bool {
+        self.inner.lock().kill_on_oom
+    }
+
+    /// Get the parent job.
+    pub fn parent(&self) -> Option<Arc<Job>> {
+        self.parent.clone()
+    }
+
+    /// Kill a process in the job tree to free memory.
+    ///
+    /// Only processes in jobs flagged kill-on-OOM are considered, the job
+    /// tree is searched depth-first starting with this job.
+    /// Returns whether a process was killed.
+    pub fn oom_kill(&self) -> bool {
+        match self.find_oom_victim() {
+            Some(proc) => {
+                warn!("OOM: kill process {:?}", proc.id());
+                proc.exit(TASK_RETCODE_OOM_KILL);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn find_oom_victim(&self) -> Option<Arc<Process>> {
+        let inner = self.inner.lock();
+        if inner.kill_on_oom {
+            if let Some(proc) = inner.processes.first() {
+                return Some(proc.clone());
+            }
+        }
+        inner.children.iter().find_map(|job| job.find_oom_victim())
     }
 
     pub fn check_root_job(&self) -> ZxResult {
@@ -185,6 +240,10 @@ impl Job {
         self.exceptionate.clone()
     }
 
+    pub fn get_debug_exceptionate(&self) -> Arc<Exceptionate> {
+        self.debug_exceptionate.clone()
+    }
+
     pub fn enumerate_process(&self, mut f: impl FnMut(KoID) -> bool) {
         self.inner
             .lock()
@@ -208,19 +267,25 @@ impl JobInner {
     }
 }
 
+/// Information of a job, returned by `ZX_INFO_JOB`.
 #[repr(C)]
-#[derive(Default)]
+#[derive(Default, Debug)]
 pub struct JobInfo {
-    return_code: i64,
-    exited: bool,
-    kill_on_oom: bool,
-    debugger_attached: bool,
+    /// The return code of the job, valid if exited.
+    pub return_code: i64,
+    /// Whether the job has exited.
+    pub exited: bool,
+    /// Whether processes in the job can be killed under memory pressure.
+    pub kill_on_oom: bool,
+    /// Whether a debugger is attached to the job.
+    pub debugger_attached: bool,
     padding: [u8; 5],
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::task::Status;
 
     #[test]
     fn create() {
@@ -324,4 +389,34 @@ mod tests {
             Some(ZxError::NOT_FOUND)
         );
     }
+
+    #[test]
+    fn info() {
+        let root_job = Job::root();
+        let job = Job::create_child(&root_job, 0).expect("failed to create job");
+        let info = job.get_info();
+        assert!(!info.exited);
+        assert_eq!(info.return_code, 0);
+        assert!(!info.kill_on_oom);
+        assert!(!info.debugger_attached);
+    }
+
+    #[test]
+    fn kill_on_oom() {
+        let root_job = Job::root();
+        let job = Job::create_child(&root_job, 0).expect("failed to create job");
+        let proc = Process::create(&job, "proc", 0).expect("failed to create process");
+        assert!(!job.get_kill_on_oom());
+        assert!(!root_job.oom_kill());
+
+        job.set_kill_on_oom(true);
+        assert!(job.get_kill_on_oom());
+        assert!(job.get_info().kill_on_oom);
+        assert!(root_job.oom_kill());
+        assert_eq!(proc.status(), Status::Exited(TASK_RETCODE_OOM_KILL));
+        assert!(!root_job.oom_kill());
+
+        job.set_kill_on_oom(false);
+        assert!(!job.get_kill_on_oom());
+    }
 }
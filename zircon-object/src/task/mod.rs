@@ -6,13 +6,17 @@ mod exception;
 mod job;
 mod job_policy;
 mod process;
+mod profile;
 mod suspend_token;
 mod thread;
 
 pub use {
-    self::job::*, self::job_policy::*, self::process::*, self::suspend_token::*, self::thread::*,
+    self::job::*, self::job_policy::*, self::process::*, self::profile::*, self::suspend_token::*,
+    self::thread::*,
 };
 
+pub use self::exception::{ExceptionContext, ExceptionReport, ExceptionType};
+
 /// Task (Thread, Process, or Job)
 pub trait Task {
     /// Kill the task.
@@ -7,6 +7,8 @@ use {
     spin::Mutex,
 };
 
+kcounter!(PROC_FRAMES_RECLAIMED, "proc.frames_reclaimed");
+
 /// Process abstraction
 ///
 /// ## SYNOPSIS
@@ -55,6 +57,7 @@ pub struct Process {
     vmar: Arc<VmAddressRegion>,
     ext: Box<dyn Any + Send + Sync>,
     exceptionate: Arc<Exceptionate>,
+    debug_exceptionate: Arc<Exceptionate>,
     inner: Mutex<ProcessInner>,
 }
 
@@ -77,6 +80,8 @@ struct ProcessInner {
     handles: BTreeMap<HandleValue, (Handle, Vec<Sender<()>>)>,
     futexes: BTreeMap<usize, Arc<Futex>>,
     threads: Vec<Arc<Thread>>,
+    /// The number of existing `SuspendToken` of the process.
+    suspend_count: usize,
 
     // special info
     debug_addr: usize,
@@ -117,6 +122,7 @@ impl Process {
             vmar: VmAddressRegion::new_root(),
             ext: Box::new(ext),
             exceptionate: Exceptionate::new(ExceptionChannelType::Process),
+            debug_exceptionate: Exceptionate::new(ExceptionChannelType::Debugger),
             inner: Mutex::new(ProcessInner::default()),
         });
         job.add_process(proc.clone());
@@ -165,10 +171,35 @@ impl Process {
             thread.internal_exit();
         }
         inner.threads.clear();
-        inner.handles.clear();
+        drop(inner);
+        self.close_all_handles();
+        match self.reclaim_memory() {
+            Ok(frames) => PROC_FRAMES_RECLAIMED.add(frames),
+            Err(err) => warn!(
+                "failed to reclaim the memory of process {}: {:?}",
+                self.base.id, err
+            ),
+        }
         self.job.process_exit(self.base.id, retcode);
     }
 
+    /// Unmap the whole address space, so that VMOs only mapped by this
+    /// process are freed now instead of when the last reference to the
+    /// process is dropped.
+    ///
+    /// Returns the number of committed frames released.
+    fn reclaim_memory(&self) -> ZxResult<usize> {
+        let mut vmos = self.vmar.get_vmos();
+        vmos.sort_by_key(|vmo| &**vmo as *const VmObject as usize);
+        vmos.dedup_by(|a, b| Arc::ptr_eq(a, b));
+        self.vmar.clear()?;
+        Ok(vmos
+            .iter()
+            .filter(|vmo| Arc::strong_count(vmo) == 1)
+            .map(|vmo| vmo.committed_bytes() / PAGE_SIZE)
+            .sum())
+    }
+
     /// Check whether `condition` is allowed in the parent job's policy.
     pub fn check_policy(&self, condition: PolicyCondition) -> ZxResult {
         match self
@@ -230,6 +261,28 @@ impl Process {
             .collect()
     }
 
+    /// Close all handles of the process in the order of their handle values.
+    ///
+    /// The handles are dropped without holding the process lock, so objects
+    /// whose last handle is closed can run their teardown.
+    /// Returns the number of handles closed.
+    pub fn close_all_handles(&self) -> usize {
+        let handles = core::mem::take(&mut self.inner.lock().handles);
+        let count = handles.len();
+        for (_, (handle, queue)) in handles {
+            for sender in queue {
+                let _ = sender.send(());
+            }
+            drop(handle);
+        }
+        count
+    }
+
+    /// Check whether `handle_value` refers to a handle in the process.
+    pub fn has_handle(&self, handle_value: HandleValue) -> bool {
+        self.inner.lock().handles.contains_key(&handle_value)
+    }
+
     /// Get a handle from the process
     fn get_handle(&self, handle_value: HandleValue) -> ZxResult<Handle> {
         self.inner.lock().get_handle(handle_value)
@@ -266,6 +319,33 @@ impl Process {
         Ok(new_handle_value)
     }
 
+    /// Replace a handle with a new one to the same object with new `rights`,
+    /// return the new handle value.
+    ///
+    /// It is done under the handle table lock, so the object is always
+    /// reachable through one of them. Like zircon, the source handle is
+    /// closed even if `operation` fails.
+    pub fn replace_handle_operating_rights(
+        &self,
+        handle_value: HandleValue,
+        operation: impl FnOnce(Rights) -> ZxResult<Rights>,
+    ) -> ZxResult<HandleValue> {
+        let mut inner = self.inner.lock();
+        let mut handle = inner.remove_handle(handle_value)?;
+        match operation(handle.rights) {
+            Ok(rights) => {
+                handle.rights = rights;
+                Ok(inner.add_handle(handle))
+            }
+            Err(err) => {
+                // the object may be destroyed with the handle
+                drop(inner);
+                drop(handle);
+                Err(err)
+            }
+        }
+    }
+
     /// Get the kernel object corresponding to this `handle_value`,
     /// after checking that this handle has the `desired_rights`.
     pub fn get_object_with_rights<T: KernelObject>(
@@ -339,6 +419,54 @@ impl Process {
         object.validate(kind)
     }
 
+    /// Get information of all VMOs referenced by the process.
+    ///
+    /// Includes VMOs mapped in the address space and VMOs held by handles.
+    /// Each VMO is reported once, with `VIA_MAPPING` and/or `VIA_HANDLE` set.
+    pub fn get_vmos_info(&self) -> Vec<ZxInfoVmo> {
+        let mut infos: BTreeMap<KoID, ZxInfoVmo> = BTreeMap::new();
+        for vmo in self.vmar().get_vmos() {
+            infos
+                .entry(vmo.id())
+                .or_insert_with(|| vmo.get_info())
+                .flags |= VmoInfoFlags::VIA_MAPPING;
+        }
+        let handles: Vec<Handle> = self
+            .inner
+            .lock()
+            .handles
+            .values()
+            .map(|(handle, _)| handle.clone())
+            .collect();
+        for handle in handles {
+            if let Ok(vmo) = handle.object.downcast_arc::<VmObject>() {
+                let info = infos.entry(vmo.id()).or_insert_with(|| vmo.get_info());
+                info.flags |= VmoInfoFlags::VIA_HANDLE;
+                info.rights |= handle.rights;
+            }
+        }
+        infos.into_iter().map(|(_, info)| info).collect()
+    }
+
+    /// Get the number of handles in the process, grouped by object type.
+    pub fn get_handle_stats(&self) -> ProcessHandleStats {
+        let mut stats = ProcessHandleStats::default();
+        for (handle, _) in self.inner.lock().handles.values() {
+            stats.handle_count[obj_type(&handle.object) as usize] += 1;
+        }
+        stats
+    }
+
+    /// Get information of all handles in the process.
+    pub fn get_handle_table(&self) -> Vec<HandleInfoExtended> {
+        self.inner
+            .lock()
+            .handles
+            .iter()
+            .map(|(&handle_value, (handle, _))| handle.get_extended_info(handle_value))
+            .collect()
+    }
+
     pub fn get_handle_info(&self, handle_value: HandleValue) -> ZxResult<HandleBasicInfo> {
         let handle = self.get_handle(handle_value)?;
         Ok(handle.get_info())
@@ -350,9 +478,32 @@ impl Process {
         if let Status::Exited(_) = inner.status {
             panic!("can not add thread to exited process");
         }
+        // a thread created in a suspended process starts suspended
+        for _ in 0..inner.suspend_count {
+            thread.suspend();
+        }
         inner.threads.push(thread);
     }
 
+    /// Suspend all threads, including those created later.
+    pub(super) fn suspend(&self) {
+        let mut inner = self.inner.lock();
+        inner.suspend_count += 1;
+        for thread in inner.threads.iter() {
+            thread.suspend();
+        }
+    }
+
+    /// Undo a `suspend`.
+    pub(super) fn resume(&self) {
+        let mut inner = self.inner.lock();
+        assert_ne!(inner.suspend_count, 0);
+        inner.suspend_count -= 1;
+        for thread in inner.threads.iter() {
+            thread.resume();
+        }
+    }
+
     /// Remove a thread to from process.
     ///
     /// If no more threads left, exit the process.
@@ -366,23 +517,21 @@ impl Process {
         }
     }
 
+    /// Get information of the process.
+    ///
+    /// An exited process is also reported as started, as Zircon does.
     pub fn get_info(&self) -> ProcessInfo {
-        let mut info = ProcessInfo::default();
-        // TODO correct debugger_attached setting
-        info.debugger_attached = false;
+        let mut info = ProcessInfo {
+            debugger_attached: self.debug_exceptionate.get_channel().is_some(),
+            ..Default::default()
+        };
         match self.inner.lock().status {
-            Status::Init => {
-                info.started = false;
-                info.has_exited = false;
-            }
-            Status::Running => {
-                info.started = true;
-                info.has_exited = false;
-            }
+            Status::Init => {}
+            Status::Running => info.started = true,
             Status::Exited(ret) => {
                 info.return_code = ret;
+                info.started = true;
                 info.has_exited = true;
-                info.started = false;
             }
         }
         info
@@ -412,6 +561,20 @@ impl Process {
         self.exceptionate.clone()
     }
 
+    pub fn get_debug_exceptionate(&self) -> Arc<Exceptionate> {
+        self.debug_exceptionate.clone()
+    }
+
+    /// Get the KOIDs of at most `max` threads, and the number of all threads.
+    ///
+    /// The lock is held only to copy the KOIDs, so a large process can be
+    /// enumerated into a small buffer page by page.
+    pub fn thread_ids(&self, max: usize) -> (Vec<KoID>, usize) {
+        let inner = self.inner.lock();
+        let ids = inner.threads.iter().take(max).map(|t| t.id()).collect();
+        (ids, inner.threads.len())
+    }
+
     pub fn enumerate_thread(&self, mut f: impl FnMut(KoID) -> bool) {
         self.inner
             .lock()
@@ -474,9 +637,17 @@ pub struct ProcessInfo {
     pub padding1: [u8; 5],
 }
 
+/// Number of handles held by a process, indexed by `ZX_OBJ_TYPE_*`.
+#[repr(C)]
+#[derive(Default)]
+pub struct ProcessHandleStats {
+    pub handle_count: [u32; 64],
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{ipc::Channel, signal::Event};
 
     #[test]
     fn create() {
@@ -484,6 +655,23 @@ mod tests {
         let _proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
     }
 
+    #[test]
+    fn suspend() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+
+        let token = SuspendToken::create_for_process(&proc);
+        assert_eq!(thread.state(), ThreadState::Suspended);
+        // threads created later are suspended too
+        let thread1 = Thread::create(&proc, "thread1", 0).expect("failed to create thread");
+        assert_eq!(thread1.state(), ThreadState::Suspended);
+
+        drop(token);
+        assert_eq!(thread.state(), ThreadState::New);
+        assert_eq!(thread1.state(), ThreadState::New);
+    }
+
     #[test]
     fn handle() {
         let root_job = Job::root();
@@ -491,6 +679,7 @@ mod tests {
         let handle = Handle::new(proc.clone(), Rights::DEFAULT_PROCESS);
 
         let handle_value = proc.add_handle(handle);
+        assert!(proc.has_handle(handle_value));
 
         // getting object should success
         let object: Arc<Process> = proc
@@ -513,6 +702,7 @@ mod tests {
         );
 
         proc.remove_handle(handle_value).unwrap();
+        assert!(!proc.has_handle(handle_value));
 
         // getting object with invalid handle should fail.
         assert_eq!(
@@ -563,6 +753,195 @@ mod tests {
         );
     }
 
+    #[test]
+    fn replace_handle() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let rights = Rights::DUPLICATE | Rights::READ;
+        let handle_value = proc.add_handle(Handle::new(proc.clone(), rights));
+
+        let new_handle_value = proc
+            .replace_handle_operating_rights(handle_value, |old_rights| {
+                reduce_rights(old_rights, Rights::READ)
+            })
+            .unwrap();
+        assert_eq!(
+            proc.get_handle(handle_value).err(),
+            Some(ZxError::BAD_HANDLE)
+        );
+        let handle = proc.get_handle(new_handle_value).unwrap();
+        assert_eq!(handle.rights, Rights::READ);
+        assert_eq!(handle.object.id(), proc.id());
+
+        // rights beyond the source are rejected, and the source is closed anyway
+        assert_eq!(
+            proc.replace_handle_operating_rights(new_handle_value, |old_rights| {
+                reduce_rights(old_rights, Rights::WRITE)
+            }),
+            Err(ZxError::ACCESS_DENIED)
+        );
+        assert!(!proc.has_handle(new_handle_value));
+        assert_eq!(
+            proc.replace_handle_operating_rights(new_handle_value, Ok),
+            Err(ZxError::BAD_HANDLE)
+        );
+    }
+
+    #[test]
+    fn thread_ids() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let threads: Vec<_> = (0..2000)
+            .map(|_| Thread::create(&proc, "thread", 0).expect("failed to create thread"))
+            .collect();
+
+        let (ids, count) = proc.thread_ids(100);
+        assert_eq!(count, threads.len());
+        assert_eq!(ids.len(), 100);
+        for (id, thread) in ids.iter().zip(threads.iter()) {
+            assert_eq!(*id, thread.id());
+        }
+        let (ids, count) = proc.thread_ids(usize::MAX);
+        assert_eq!((ids.len(), count), (threads.len(), threads.len()));
+        assert_eq!(proc.thread_ids(0), (Vec::new(), threads.len()));
+    }
+
+    #[test]
+    fn reclaim_on_exit() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let vmar = proc.vmar();
+        let flags = MMUFlags::READ | MMUFlags::WRITE;
+        let private = VmObject::new_paged(2);
+        private.write(0, &[1]).unwrap();
+        private.write(PAGE_SIZE, &[1]).unwrap();
+        vmar.map(None, private.clone(), 0, 0x2000, flags).unwrap();
+        let shared = VmObject::new_paged(1);
+        shared.write(0, &[1]).unwrap();
+        vmar.map(None, shared.clone(), 0, 0x1000, flags).unwrap();
+        let private = Arc::downgrade(&private);
+
+        let reclaimed = PROC_FRAMES_RECLAIMED.get();
+        proc.exit(0);
+        assert!(private.upgrade().is_none());
+        assert_eq!(shared.committed_bytes(), PAGE_SIZE);
+        assert!(PROC_FRAMES_RECLAIMED.get() >= reclaimed + 2);
+        assert!(vmar.get_vmos().is_empty());
+    }
+
+    #[test]
+    fn close_all_handles() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let vmo = VmObject::new_paged(1);
+        vmo.write(0, &[1]).unwrap();
+        let event = Event::new();
+        proc.add_handle(Handle::new(vmo.clone(), Rights::DEFAULT_VMO));
+        proc.add_handle(Handle::new(vmo.clone(), Rights::DEFAULT_VMO));
+        let handle_value = proc.add_handle(Handle::new(event.clone(), Rights::DEFAULT_EVENT));
+        let vmo = Arc::downgrade(&vmo);
+        let event = Arc::downgrade(&event);
+
+        assert_eq!(proc.close_all_handles(), 3);
+        assert_eq!(vmo.strong_count(), 0);
+        assert_eq!(event.strong_count(), 0);
+        assert!(!proc.has_handle(handle_value));
+        assert_eq!(proc.close_all_handles(), 0);
+
+        // exiting closes the remaining handles
+        let vmo = VmObject::new_paged(1);
+        proc.add_handle(Handle::new(vmo.clone(), Rights::DEFAULT_VMO));
+        let vmo = Arc::downgrade(&vmo);
+        proc.exit(0);
+        assert_eq!(vmo.strong_count(), 0);
+    }
+
+    #[test]
+    fn info() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let info = proc.get_info();
+        assert!(!info.started && !info.has_exited && !info.debugger_attached);
+        assert_eq!(info.return_code, 0);
+
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        let handle = Handle::new(proc.clone(), Rights::DEFAULT_PROCESS);
+        fn spawn(_thread: Arc<Thread>) {}
+        proc.start(&thread, 0, 0, handle, 0, spawn)
+            .expect("failed to start process");
+        let info = proc.get_info();
+        assert!(info.started && !info.has_exited);
+
+        let (channel, _peer) = Channel::create();
+        proc.get_debug_exceptionate().set_channel(channel);
+        assert!(proc.get_info().debugger_attached);
+
+        proc.exit(-1);
+        let info = proc.get_info();
+        assert!(info.started && info.has_exited);
+        assert_eq!(info.return_code, -1);
+    }
+
+    #[test]
+    fn get_vmos_info() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let vmar = proc.vmar();
+        let vmo = VmObject::new_paged(2);
+        let flags = MMUFlags::READ | MMUFlags::WRITE;
+        vmar.map(None, vmo.clone(), 0, 0x1000, flags).unwrap();
+        vmar.map(None, vmo.clone(), 0x1000, 0x1000, flags).unwrap();
+        proc.add_handle(Handle::new(vmo.clone(), Rights::DEFAULT_VMO));
+        proc.add_handle(Handle::new(VmObject::new_paged(1), Rights::DEFAULT_VMO));
+
+        let infos = proc.get_vmos_info();
+        assert_eq!(infos.len(), 2);
+        let mapped = infos
+            .iter()
+            .find(|info| info.flags.contains(VmoInfoFlags::VIA_MAPPING))
+            .unwrap();
+        assert!(mapped.flags.contains(VmoInfoFlags::VIA_HANDLE));
+        assert_eq!(mapped.rights, Rights::DEFAULT_VMO);
+    }
+
+    #[test]
+    fn handle_stats() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        proc.add_handle(Handle::new(proc.clone(), Rights::DEFAULT_PROCESS));
+        proc.add_handle(Handle::new(proc.clone(), Rights::DEFAULT_PROCESS));
+        proc.add_handle(Handle::new(root_job.clone(), Rights::DEFAULT_JOB));
+
+        let stats = proc.get_handle_stats();
+        assert_eq!(stats.handle_count[1], 2);
+        assert_eq!(stats.handle_count[17], 1);
+        assert_eq!(stats.handle_count.iter().sum::<u32>(), 3);
+    }
+
+    #[test]
+    fn handle_table() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        assert!(proc.get_handle_table().is_empty());
+        let proc_handle = proc.add_handle(Handle::new(proc.clone(), Rights::DEFAULT_PROCESS));
+        let job_handle = proc.add_handle(Handle::new(root_job.clone(), Rights::DEFAULT_JOB));
+        proc.add_handle(Handle::new(root_job.clone(), Rights::BASIC));
+
+        let table = proc.get_handle_table();
+        assert_eq!(table.len(), 3);
+        let info = table
+            .iter()
+            .find(|i| i.handle_value == proc_handle)
+            .unwrap();
+        assert_eq!(info.obj_type, 1);
+        assert_eq!(info.koid, proc.id());
+        assert_eq!(info.related_koid, root_job.id());
+        assert_eq!(info.rights, Rights::DEFAULT_PROCESS.bits());
+        let info = table.iter().find(|i| i.handle_value == job_handle).unwrap();
+        assert_eq!(info.obj_type, 17);
+        assert_eq!(info.koid, root_job.id());
+    }
+
     #[test]
     fn get_child() {
         let root_job = Job::root();
@@ -0,0 +1,124 @@
+use {crate::object::*, alloc::sync::Arc, bitflags::bitflags};
+
+/// Scheduling parameters that can be applied to threads
+///
+/// ## SYNOPSIS
+///
+/// A profile is an immutable set of scheduling parameters, such as priority
+/// and CPU affinity. It is applied to threads by `object_set_profile`.
+pub struct Profile {
+    base: KObjectBase,
+    info: ProfileInfo,
+}
+
+impl_kobject!(Profile);
+
+bitflags! {
+    /// Which fields of `ProfileInfo` are valid.
+    pub struct ProfileInfoFlags: u32 {
+        #[allow(clippy::identity_op)]
+        const PRIORITY      = 1 << 0;
+        const CPU_MASK      = 1 << 1;
+        const DEADLINE      = 1 << 2;
+    }
+}
+
+/// The lowest thread priority.
+pub const PRIORITY_LOWEST: i32 = 0;
+/// The default thread priority.
+pub const PRIORITY_DEFAULT: i32 = 16;
+/// The highest thread priority.
+pub const PRIORITY_HIGHEST: i32 = 31;
+
+/// Parameters to create a profile, `zx_profile_info_t`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProfileInfo {
+    /// A combination of `ProfileInfoFlags`.
+    pub flags: u32,
+    padding1: u32,
+    /// The priority of threads, valid with `PRIORITY`.
+    pub priority: i32,
+    padding2: [u8; 20],
+    /// CPUs threads may run on, valid with `CPU_MASK`.
+    pub cpu_affinity_mask: [u64; 8],
+}
+
+impl Profile {
+    /// Create a profile from `info`.
+    pub fn create(info: ProfileInfo) -> ZxResult<Arc<Self>> {
+        let flags = ProfileInfoFlags::from_bits(info.flags).ok_or(ZxError::INVALID_ARGS)?;
+        if flags.contains(ProfileInfoFlags::PRIORITY | ProfileInfoFlags::DEADLINE) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        if flags.contains(ProfileInfoFlags::DEADLINE) {
+            // TODO: deadline scheduling
+            return Err(ZxError::NOT_SUPPORTED);
+        }
+        if flags.contains(ProfileInfoFlags::PRIORITY)
+            && !(PRIORITY_LOWEST..=PRIORITY_HIGHEST).contains(&info.priority)
+        {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        Ok(Arc::new(Profile {
+            base: KObjectBase::new(),
+            info,
+        }))
+    }
+
+    /// Get the flags of valid parameters.
+    pub fn flags(&self) -> ProfileInfoFlags {
+        ProfileInfoFlags::from_bits_truncate(self.info.flags)
+    }
+
+    /// Get the parameters of the profile.
+    pub fn info(&self) -> &ProfileInfo {
+        &self.info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::*;
+
+    #[test]
+    fn create() {
+        let info = ProfileInfo {
+            flags: ProfileInfoFlags::PRIORITY.bits(),
+            priority: PRIORITY_HIGHEST + 1,
+            ..Default::default()
+        };
+        assert_eq!(Profile::create(info).err(), Some(ZxError::INVALID_ARGS));
+        let info = ProfileInfo {
+            flags: 1 << 31,
+            ..Default::default()
+        };
+        assert_eq!(Profile::create(info).err(), Some(ZxError::INVALID_ARGS));
+    }
+
+    #[test]
+    fn apply() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        assert_eq!(thread.priority(), PRIORITY_DEFAULT);
+        assert_eq!(thread.get_thread_info().cpu_affinity_mask, [u64::MAX; 8]);
+
+        let mut cpu_affinity_mask = [0; 8];
+        cpu_affinity_mask[0] = 0b11;
+        let info = ProfileInfo {
+            flags: (ProfileInfoFlags::PRIORITY | ProfileInfoFlags::CPU_MASK).bits(),
+            priority: PRIORITY_HIGHEST,
+            cpu_affinity_mask,
+            ..Default::default()
+        };
+        let profile = Profile::create(info).unwrap();
+        thread.set_profile(&profile);
+        assert_eq!(thread.priority(), PRIORITY_HIGHEST);
+        assert_eq!(
+            thread.get_thread_info().cpu_affinity_mask,
+            cpu_affinity_mask
+        );
+    }
+}
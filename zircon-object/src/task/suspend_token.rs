@@ -1,31 +1,60 @@
 use {
+    super::process::Process,
     super::thread::Thread,
     super::*,
     crate::object::*,
     alloc::sync::{Arc, Weak},
 };
 
+/// Suspend token keeps a task suspended until it is closed.
+///
+/// Tokens nest: the task runs again only after all its tokens are closed.
 pub struct SuspendToken {
     base: KObjectBase,
-    thread: Weak<Thread>,
+    task: SuspendedTask,
+}
+
+enum SuspendedTask {
+    Thread(Weak<Thread>),
+    Process(Weak<Process>),
 }
 
 impl_kobject!(SuspendToken);
 
 impl SuspendToken {
+    /// Suspend `thread` until the token is closed.
     pub fn create(thread: &Arc<Thread>) -> Arc<Self> {
         thread.suspend();
         Arc::new(SuspendToken {
             base: KObjectBase::new(),
-            thread: Arc::downgrade(thread),
+            task: SuspendedTask::Thread(Arc::downgrade(thread)),
+        })
+    }
+
+    /// Suspend all threads of `process` until the token is closed,
+    /// including those created later.
+    pub fn create_for_process(process: &Arc<Process>) -> Arc<Self> {
+        process.suspend();
+        Arc::new(SuspendToken {
+            base: KObjectBase::new(),
+            task: SuspendedTask::Process(Arc::downgrade(process)),
         })
     }
 }
 
 impl Drop for SuspendToken {
     fn drop(&mut self) {
-        if let Some(thread) = self.thread.upgrade() {
-            thread.resume();
+        match &self.task {
+            SuspendedTask::Thread(thread) => {
+                if let Some(thread) = thread.upgrade() {
+                    thread.resume();
+                }
+            }
+            SuspendedTask::Process(process) => {
+                if let Some(process) = process.upgrade() {
+                    process.resume();
+                }
+            }
         }
     }
 }
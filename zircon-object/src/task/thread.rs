@@ -2,8 +2,9 @@ use {
     super::exception::*,
     super::process::Process,
     super::*,
+    crate::ipc::MessagePacket,
     crate::object::*,
-    alloc::{boxed::Box, sync::Arc},
+    alloc::{boxed::Box, sync::Arc, vec::Vec},
     core::{
         any::Any,
         future::Future,
@@ -12,7 +13,7 @@ use {
         time::Duration,
     },
     futures::{channel::oneshot::Receiver, future::FutureExt, select_biased},
-    kernel_hal::{sleep_until, GeneralRegs, UserContext},
+    kernel_hal::{sleep_until, timer_now, GeneralRegs, UserContext},
     spin::Mutex,
 };
 
@@ -111,11 +112,72 @@ struct ThreadInner {
     suspend_count: usize,
     /// The waker of task when suspending.
     waker: Option<Waker>,
+    /// The waker of task sleeping, until the thread is suspended.
+    sleep_waker: Option<Waker>,
     /// Thread state
     ///
     /// NOTE: This variable will never be `Suspended`. On suspended, the
     /// `suspend_count` is non-zero, and this represents the state before suspended.
     state: ThreadState,
+
+    /// The accumulated running time, not including the current run.
+    runtime: Duration,
+    /// The time when the thread started its current run. `None` if not running.
+    run_start: Option<Duration>,
+
+    /// Scheduling priority, set by profiles.
+    priority: i32,
+    /// CPUs the thread may run on, set by profiles.
+    cpu_affinity_mask: [u64; 8],
+
+    /// The report of the last exception the thread faulted on.
+    exception_report: Option<ExceptionReport>,
+}
+
+impl ThreadInner {
+    /// Total running time, including the current run if any.
+    fn total_runtime(&self) -> Duration {
+        match self.run_start {
+            Some(start) => self.runtime + timer_now().checked_sub(start).unwrap_or_default(),
+            None => self.runtime,
+        }
+    }
+
+    /// Enter the blocking `state`, and stop counting the runtime meanwhile.
+    ///
+    /// Return the old state and whether it was running, for `unblock`.
+    fn block(&mut self, state: ThreadState) -> (ThreadState, bool) {
+        let old_state = core::mem::replace(&mut self.state, state);
+        let running = self.run_start.is_some();
+        self.runtime = self.total_runtime();
+        self.run_start = None;
+        (old_state, running)
+    }
+
+    /// Restore the state blocked with `block`.
+    ///
+    /// The thread can only be killed meanwhile, e.g. when its process exits
+    /// while it is stopped in an exception, and then it stays `Dead`.
+    fn unblock(&mut self, state: ThreadState, (old_state, running): (ThreadState, bool)) {
+        if self.state == ThreadState::Dead {
+            return;
+        }
+        assert_eq!(self.state, state);
+        self.state = old_state;
+        if running {
+            self.run_start = Some(timer_now());
+        }
+    }
+
+    /// Fail with `BAD_STATE` unless the thread is suspended or stopped in an exception.
+    fn check_stopped(&self) -> ZxResult {
+        match self.state {
+            ThreadState::Dying | ThreadState::Dead => Err(ZxError::BAD_STATE),
+            ThreadState::BlockedException => Ok(()),
+            _ if self.suspend_count != 0 => Ok(()),
+            _ => Err(ZxError::BAD_STATE),
+        }
+    }
 }
 
 impl Thread {
@@ -139,6 +201,8 @@ impl Thread {
             exceptionate: Exceptionate::new(ExceptionChannelType::Thread),
             inner: Mutex::new(ThreadInner {
                 context: Some(Box::new(UserContext::default())),
+                priority: PRIORITY_DEFAULT,
+                cpu_affinity_mask: [u64::MAX; 8],
                 ..Default::default()
             }),
         });
@@ -213,23 +277,43 @@ impl Thread {
     }
 
     /// Read one aspect of thread state.
+    ///
+    /// The thread must be suspended or stopped in an exception.
     pub fn read_state(&self, kind: ThreadStateKind, buf: &mut [u8]) -> ZxResult<usize> {
         let inner = self.inner.lock();
+        inner.check_stopped()?;
         let context = inner.context.as_ref().ok_or(ZxError::BAD_STATE)?;
         context.read_state(kind, buf)
     }
 
     /// Write one aspect of thread state.
+    ///
+    /// The thread must be suspended or stopped in an exception.
     pub fn write_state(&self, kind: ThreadStateKind, buf: &[u8]) -> ZxResult {
         let mut inner = self.inner.lock();
+        inner.check_stopped()?;
         let context = inner.context.as_mut().ok_or(ZxError::BAD_STATE)?;
         context.write_state(kind, buf)
     }
 
+    /// Get the FS base saved in the thread context.
+    ///
+    /// It is 0 if the thread hasn't set one yet.
+    /// Fail with `BAD_STATE` if the context is not saved, e.g. the thread is running.
+    pub fn fsbase(&self) -> ZxResult<usize> {
+        let inner = self.inner.lock();
+        let context = inner.context.as_ref().ok_or(ZxError::BAD_STATE)?;
+        Ok(context.general.fsbase)
+    }
+
     pub(super) fn suspend(&self) {
         let mut inner = self.inner.lock();
         inner.suspend_count += 1;
-        self.base.signal_set(Signal::THREAD_SUSPENDED);
+        self.base
+            .signal_change(Signal::THREAD_RUNNING, Signal::THREAD_SUSPENDED);
+        if let Some(waker) = inner.sleep_waker.take() {
+            waker.wake();
+        }
         info!(
             "thread {:?} suspend: count={}",
             self.base.name(),
@@ -242,13 +326,47 @@ impl Thread {
         assert_ne!(inner.suspend_count, 0);
         inner.suspend_count -= 1;
         if inner.suspend_count == 0 {
-            self.base.signal_set(Signal::THREAD_RUNNING);
+            self.base
+                .signal_change(Signal::THREAD_SUSPENDED, Signal::THREAD_RUNNING);
             if let Some(waker) = inner.waker.take() {
                 waker.wake();
             }
         }
     }
 
+    /// Wait until the thread is suspended, or resumed if `suspended` is false.
+    ///
+    /// It parks on the wakers of the thread, rather than adding a signal callback,
+    /// so nothing is left behind when it is dropped early.
+    fn wait_for_suspend(self: &Arc<Thread>, suspended: bool) -> impl Future<Output = ()> {
+        #[must_use = "wait_for_suspend does nothing unless polled/`await`-ed"]
+        struct SuspendChecker {
+            thread: Arc<Thread>,
+            suspended: bool,
+        }
+        impl Future for SuspendChecker {
+            type Output = ();
+
+            fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+                let mut inner = self.thread.inner.lock();
+                if (inner.suspend_count != 0) == self.suspended {
+                    return Poll::Ready(());
+                }
+                let waker = Some(cx.waker().clone());
+                if self.suspended {
+                    inner.sleep_waker = waker;
+                } else {
+                    inner.waker = waker;
+                }
+                Poll::Pending
+            }
+        }
+        SuspendChecker {
+            thread: self.clone(),
+            suspended,
+        }
+    }
+
     pub fn wait_for_run(self: &Arc<Thread>) -> impl Future<Output = Box<UserContext>> {
         #[must_use = "wait_for_run does nothing unless polled/`await`-ed"]
         struct RunnableChecker {
@@ -261,6 +379,7 @@ impl Thread {
                 let mut inner = self.thread.inner.lock();
                 if inner.suspend_count == 0 {
                     // resume:  return the context token from thread object
+                    inner.run_start = Some(timer_now());
                     Poll::Ready(inner.context.take().unwrap())
                 } else {
                     // suspend: put waker into the thread object
@@ -275,15 +394,52 @@ impl Thread {
     }
 
     pub fn end_running(&self, context: Box<UserContext>) {
-        self.inner.lock().context = Some(context);
+        let mut inner = self.inner.lock();
+        inner.runtime = inner.total_runtime();
+        inner.run_start = None;
+        inner.context = Some(context);
     }
 
+    /// Get the information of the thread.
+    ///
+    /// The reported state takes suspension into account.
     pub fn get_thread_info(&self) -> ThreadInfo {
-        let inner = self.inner.lock();
+        let state = self.state();
+        let wait_exception_channel_type = match state {
+            ThreadState::BlockedException => self.exceptionate.type_(),
+            _ => ExceptionChannelType::None,
+        };
         ThreadInfo {
-            state: inner.state as u32,
-            wait_exception_type: 0,
-            cpu_affnity_mask: [0u64; 8],
+            state: state as u32,
+            wait_exception_channel_type: wait_exception_channel_type as u32,
+            cpu_affinity_mask: self.inner.lock().cpu_affinity_mask,
+        }
+    }
+
+    /// Apply the scheduling parameters of `profile` to the thread.
+    ///
+    /// NOTE: They are recorded only, the executor does not honor them yet.
+    pub fn set_profile(&self, profile: &Profile) {
+        let flags = profile.flags();
+        let info = profile.info();
+        let mut inner = self.inner.lock();
+        if flags.contains(ProfileInfoFlags::PRIORITY) {
+            inner.priority = info.priority;
+        }
+        if flags.contains(ProfileInfoFlags::CPU_MASK) {
+            inner.cpu_affinity_mask = info.cpu_affinity_mask;
+        }
+    }
+
+    /// Get the scheduling priority of the thread.
+    pub fn priority(&self) -> i32 {
+        self.inner.lock().priority
+    }
+
+    /// Get the runtime statistics of the thread.
+    pub fn get_thread_stats(&self) -> ThreadStats {
+        ThreadStats {
+            total_runtime: self.inner.lock().total_runtime().as_nanos() as u64,
         }
     }
 
@@ -298,17 +454,100 @@ impl Thread {
         F: Future<Output = FT> + Unpin,
         FT: IntoResult<T>,
     {
-        let old_state = core::mem::replace(&mut self.inner.lock().state, state);
+        let blocked = self.inner.lock().block(state);
         let ret = select_biased! {
             ret = future.fuse() => ret.into_result(),
             _ = sleep_until(deadline).fuse() => Err(ZxError::TIMED_OUT),
         };
-        let mut inner = self.inner.lock();
-        assert_eq!(inner.state, state);
-        inner.state = old_state;
+        self.inner.lock().unblock(state, blocked);
         ret
     }
 
+    /// Sleep until `deadline`, blocked as `BlockedSleeping`.
+    ///
+    /// A suspension interrupts the sleep, which goes on after the thread is resumed.
+    /// So it never returns while suspended, even if the deadline has passed.
+    pub async fn sleep_until(self: &Arc<Self>, deadline: Duration) -> ZxResult {
+        let thread = self.clone();
+        let future = async move {
+            loop {
+                let suspended = select_biased! {
+                    _ = thread.wait_for_suspend(true).fuse() => true,
+                    _ = sleep_until(deadline).fuse() => false,
+                };
+                if !suspended {
+                    break;
+                }
+                thread.wait_for_suspend(false).await;
+            }
+        };
+        let forever = Duration::from_nanos(u64::max_value());
+        self.blocking_run(Box::pin(future), ThreadState::BlockedSleeping, forever)
+            .await
+    }
+
+    /// Block the thread in the exception of `report` until `future` is done.
+    pub async fn blocking_run_exception<F, T, FT>(
+        &self,
+        report: ExceptionReport,
+        future: F,
+    ) -> ZxResult<T>
+    where
+        F: Future<Output = FT> + Unpin,
+        FT: IntoResult<T>,
+    {
+        self.inner.lock().exception_report = Some(report);
+        let deadline = Duration::from_nanos(i64::max_value() as u64);
+        self.blocking_run(future, ThreadState::BlockedException, deadline)
+            .await
+    }
+
+    /// Get the report of the exception the thread is blocked in.
+    pub fn get_exception_report(&self) -> ZxResult<ExceptionReport> {
+        let inner = self.inner.lock();
+        if inner.state != ThreadState::BlockedException {
+            return Err(ZxError::BAD_STATE);
+        }
+        inner.exception_report.ok_or(ZxError::BAD_STATE)
+    }
+
+    /// Handle a fatal exception of `report` raised by the thread.
+    ///
+    /// If a debugger is attached to the process, it is told about the exception
+    /// and the thread stays blocked in it until the debugger closes the channel
+    /// or the thread is killed. Exceptions can not be resumed yet, so the process
+    /// is killed afterwards.
+    pub async fn handle_fatal_exception(self: &Arc<Self>, report: ExceptionReport) {
+        let proc = self.proc().clone();
+        if let Some(channel) = proc.get_debug_exceptionate().get_channel() {
+            let info = ExceptionInfo {
+                tid: self.id(),
+                pid: proc.id(),
+                type_: ExceptionChannelType::Debugger,
+                padding: 0,
+            };
+            let msg = MessagePacket {
+                data: info.pack(),
+                handles: Vec::new(),
+            };
+            if channel.write(msg).is_ok() {
+                let channel: Arc<dyn KernelObject> = channel;
+                let thread: Arc<dyn KernelObject> = self.clone();
+                let future = async move {
+                    select_biased! {
+                        _ = thread.wait_signal(Signal::THREAD_TERMINATED).fuse() => {},
+                        _ = channel.wait_signal(Signal::PEER_CLOSED).fuse() => {},
+                    }
+                };
+                let _ = self.blocking_run_exception(report, Box::pin(future)).await;
+            }
+        }
+        if let Status::Exited(_) = proc.status() {
+            return;
+        }
+        proc.exit(TASK_RETCODE_EXCEPTION_KILL);
+    }
+
     /// Run a cancelable async future and change state while blocking.
     pub async fn cancelable_blocking_run<F, T, FT>(
         &self,
@@ -321,24 +560,22 @@ impl Thread {
         F: Future<Output = FT> + Unpin,
         FT: IntoResult<T>,
     {
-        let old_state = core::mem::replace(&mut self.inner.lock().state, state);
+        let blocked = self.inner.lock().block(state);
         let ret = select_biased! {
             ret = future.fuse() => ret.into_result(),
             _ = sleep_until(deadline).fuse() => Err(ZxError::TIMED_OUT),
             _ = cancel_token.fuse() => Err(ZxError::CANCELED),
         };
-        let mut inner = self.inner.lock();
-        assert_eq!(inner.state, state);
-        inner.state = old_state;
+        self.inner.lock().unblock(state, blocked);
         ret
     }
 
     pub fn state(&self) -> ThreadState {
         let inner = self.inner.lock();
-        if inner.suspend_count == 0 {
-            inner.state
-        } else {
-            ThreadState::Suspended
+        match inner.state {
+            ThreadState::Dying | ThreadState::Dead => inner.state,
+            _ if inner.suspend_count != 0 => ThreadState::Suspended,
+            state => state,
         }
     }
 
@@ -403,11 +640,23 @@ impl Default for ThreadState {
     }
 }
 
+/// Information of a thread, returned by `ZX_INFO_THREAD`.
 #[repr(C)]
 pub struct ThreadInfo {
-    state: u32,
-    wait_exception_type: u32,
-    cpu_affnity_mask: [u64; 8],
+    /// One of `ZX_THREAD_STATE_*`.
+    pub state: u32,
+    /// If the thread is blocked in an exception, the type of the exception
+    /// channel the exception is waiting on. Otherwise `ZX_EXCEPTION_CHANNEL_TYPE_NONE`.
+    pub wait_exception_channel_type: u32,
+    /// CPUs the thread may run on.
+    pub cpu_affinity_mask: [u64; 8],
+}
+
+/// Statistics of a thread, returned by `ZX_INFO_THREAD_STATS`.
+#[repr(C)]
+pub struct ThreadStats {
+    /// Total accumulated running time of the thread in nanoseconds.
+    total_runtime: u64,
 }
 
 #[cfg(test)]
@@ -424,6 +673,339 @@ mod tests {
         let _thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
     }
 
+    #[test]
+    fn fsbase() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        assert_eq!(thread.fsbase(), Ok(0));
+        let _token = SuspendToken::create(&thread);
+        thread
+            .write_state(ThreadStateKind::FS, &0x1234usize.to_ne_bytes())
+            .unwrap();
+        assert_eq!(thread.fsbase(), Ok(0x1234));
+    }
+
+    #[test]
+    fn max_state_size() {
+        use core::convert::TryFrom;
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        let _token = SuspendToken::create(&thread);
+        // every supported kind fits in a buffer of `MAX_STATE_SIZE`
+        let mut buf = [0u8; MAX_STATE_SIZE];
+        for kind in 0..8 {
+            if let Ok(kind) = ThreadStateKind::try_from(kind) {
+                match thread.read_state(kind, &mut buf) {
+                    Ok(len) => assert!(len <= MAX_STATE_SIZE),
+                    Err(err) => assert_eq!(err, ZxError::NOT_SUPPORTED),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn read_write_state() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        fn spawn(_thread: Arc<Thread>) {}
+        thread.start(0x1000, 0x2000, 0, 0, spawn).unwrap();
+
+        // the thread must be stopped
+        let mut regs = GeneralRegs::default();
+        assert_eq!(
+            thread.read_state(ThreadStateKind::General, as_bytes_mut(&mut regs)),
+            Err(ZxError::BAD_STATE)
+        );
+
+        let token = SuspendToken::create(&thread);
+        assert_eq!(
+            thread.read_state(ThreadStateKind::General, as_bytes_mut(&mut regs)),
+            Ok(core::mem::size_of::<GeneralRegs>())
+        );
+        assert_eq!(regs.rip, 0x1000);
+        assert_eq!(regs.rsp, 0x2000);
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            regs.rip = 0x8000_0000_0000;
+            assert_eq!(
+                thread.write_state(ThreadStateKind::General, as_bytes_mut(&mut regs)),
+                Err(ZxError::INVALID_ARGS)
+            );
+        }
+        regs.rip = 0x3000;
+        let buf = as_bytes_mut(&mut regs);
+        assert_eq!(
+            thread.write_state(ThreadStateKind::General, &buf[1..]),
+            Err(ZxError::INVALID_ARGS)
+        );
+        thread.write_state(ThreadStateKind::General, buf).unwrap();
+
+        let mut regs1 = GeneralRegs::default();
+        thread
+            .read_state(ThreadStateKind::General, as_bytes_mut(&mut regs1))
+            .unwrap();
+        assert_eq!(regs1.rip, 0x3000);
+        assert_eq!(regs1.rsp, 0x2000);
+
+        drop(token);
+        assert_eq!(thread.get_thread_info().state, ThreadState::Running as u32);
+    }
+
+    #[allow(unsafe_code)]
+    fn as_bytes_mut<T>(value: &mut T) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(value as *mut T as *mut u8, core::mem::size_of::<T>())
+        }
+    }
+
+    #[test]
+    fn info() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        assert_eq!(thread.get_thread_info().state, ThreadState::New as u32);
+
+        fn spawn(_thread: Arc<Thread>) {}
+        thread.start(0, 0, 0, 0, spawn).unwrap();
+        let info = thread.get_thread_info();
+        assert_eq!(info.state, ThreadState::Running as u32);
+        assert_eq!(
+            info.wait_exception_channel_type,
+            ExceptionChannelType::None as u32
+        );
+
+        // suspend tokens nest
+        let token = SuspendToken::create(&thread);
+        assert_eq!(
+            thread.get_thread_info().state,
+            ThreadState::Suspended as u32
+        );
+        let token1 = SuspendToken::create(&thread);
+        drop(token);
+        assert_eq!(
+            thread.get_thread_info().state,
+            ThreadState::Suspended as u32
+        );
+        drop(token1);
+        assert_eq!(thread.get_thread_info().state, ThreadState::Running as u32);
+
+        thread.internal_exit();
+        assert_eq!(thread.get_thread_info().state, ThreadState::Dead as u32);
+    }
+
+    #[test]
+    fn suspend_resume() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        fn spawn(_thread: Arc<Thread>) {}
+        thread.start(0, 0, 0, 0, spawn).unwrap();
+
+        // closing one of two tokens leaves the thread suspended
+        let token = SuspendToken::create(&thread);
+        let token1 = SuspendToken::create(&thread);
+        drop(token);
+        assert!(thread.wait_for_run().now_or_never().is_none());
+        assert!(thread.signal().contains(Signal::THREAD_SUSPENDED));
+
+        // it runs after all tokens are closed
+        drop(token1);
+        assert!(thread.signal().contains(Signal::THREAD_RUNNING));
+        assert!(thread.wait_for_run().now_or_never().is_some());
+    }
+
+    #[async_std::test]
+    async fn exception_report() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        assert_eq!(
+            thread.get_exception_report().err(),
+            Some(ZxError::BAD_STATE)
+        );
+
+        let context = ExceptionContext {
+            vector: 0xe,
+            err_code: 0x6,
+            cr2: 0x1000,
+            ..Default::default()
+        };
+        let report = ExceptionReport::new(ExceptionType::FatalPageFault, context);
+        let (sender, receiver) = futures::channel::oneshot::channel::<()>();
+        let handle = async_std::task::spawn({
+            let thread = thread.clone();
+            async move {
+                thread
+                    .blocking_run_exception(report, receiver.map(|_| ()))
+                    .await
+            }
+        });
+        while thread.state() != ThreadState::BlockedException {
+            async_std::task::yield_now().await;
+        }
+        let report = thread.get_exception_report().unwrap();
+        assert_eq!(report.type_, ExceptionType::FatalPageFault);
+        assert_eq!(report.context.err_code, 0x6);
+        assert_eq!(report.context.cr2, 0x1000);
+
+        sender.send(()).unwrap();
+        handle.await.unwrap();
+        assert_eq!(
+            thread.get_exception_report().err(),
+            Some(ZxError::BAD_STATE)
+        );
+    }
+
+    #[async_std::test]
+    async fn killed_while_blocked() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        let (sender, receiver) = futures::channel::oneshot::channel::<()>();
+        let handle = async_std::task::spawn({
+            let thread = thread.clone();
+            async move {
+                let forever = Duration::from_nanos(u64::max_value());
+                thread
+                    .blocking_run(receiver.map(|_| ()), ThreadState::BlockedFutex, forever)
+                    .await
+            }
+        });
+        while thread.state() != ThreadState::BlockedFutex {
+            async_std::task::yield_now().await;
+        }
+        proc.exit(0);
+        assert_eq!(thread.state(), ThreadState::Dead);
+        // the thread is not brought back to life when the blocking ends
+        sender.send(()).unwrap();
+        handle.await.unwrap();
+        assert_eq!(thread.state(), ThreadState::Dead);
+    }
+
+    #[async_std::test]
+    async fn fatal_exception() {
+        let root_job = Job::root();
+        let context = ExceptionContext {
+            vector: 0xe,
+            err_code: 0x4,
+            cr2: 0x1000,
+            ..Default::default()
+        };
+        let report = ExceptionReport::new(ExceptionType::FatalPageFault, context);
+        fn spawn(_thread: Arc<Thread>) {}
+
+        // no handler: the process is killed
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        let handle = Handle::new(proc.clone(), Rights::DEFAULT_PROCESS);
+        proc.start(&thread, 0, 0, handle, 0, spawn).unwrap();
+        thread.handle_fatal_exception(report).await;
+        assert_eq!(proc.status(), Status::Exited(TASK_RETCODE_EXCEPTION_KILL));
+        assert_eq!(thread.state(), ThreadState::Dead);
+
+        // a debugger sees the exception until it closes the channel
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        let handle = Handle::new(proc.clone(), Rights::DEFAULT_PROCESS);
+        proc.start(&thread, 0, 0, handle, 0, spawn).unwrap();
+        let (channel, debugger) = crate::ipc::Channel::create();
+        proc.get_debug_exceptionate().set_channel(channel);
+        let task = async_std::task::spawn({
+            let thread = thread.clone();
+            async move { thread.handle_fatal_exception(report).await }
+        });
+        while thread.state() != ThreadState::BlockedException {
+            async_std::task::yield_now().await;
+        }
+        let msg = debugger.read().unwrap();
+        assert_eq!(msg.data.len(), core::mem::size_of::<ExceptionInfo>());
+        let report = thread.get_exception_report().unwrap();
+        assert_eq!(report.type_, ExceptionType::FatalPageFault);
+        assert_eq!(report.context.cr2, 0x1000);
+        assert_eq!(proc.status(), Status::Running);
+
+        drop(debugger);
+        task.await;
+        assert_eq!(proc.status(), Status::Exited(TASK_RETCODE_EXCEPTION_KILL));
+        assert_eq!(thread.state(), ThreadState::Dead);
+    }
+
+    #[async_std::test]
+    async fn runtime() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        assert_eq!(thread.get_thread_stats().total_runtime, 0);
+
+        fn spawn(_thread: Arc<Thread>) {}
+        thread.start(0, 0, 0, 0, spawn).unwrap();
+        let context = thread.wait_for_run().await;
+        let start = timer_now();
+        while timer_now() - start < Duration::from_millis(10) {}
+
+        // runtime is readable while running
+        let runtime = thread.get_thread_stats().total_runtime;
+        assert!(runtime >= 10_000_000);
+        thread.end_running(context);
+        let runtime1 = thread.get_thread_stats().total_runtime;
+        assert!(runtime1 >= runtime);
+
+        // not accumulated while not running
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(thread.get_thread_stats().total_runtime, runtime1);
+    }
+
+    #[async_std::test]
+    async fn sleep() {
+        let root_job = Job::root();
+        let proc = Process::create(&root_job, "proc", 0).expect("failed to create process");
+        let thread = Thread::create(&proc, "thread", 0).expect("failed to create thread");
+        fn spawn(_thread: Arc<Thread>) {}
+        thread.start(0, 0, 0, 0, spawn).unwrap();
+        let context = thread.wait_for_run().await;
+
+        // blocks for about the duration, not counted as runtime
+        let start = timer_now();
+        thread
+            .sleep_until(start + Duration::from_millis(20))
+            .await
+            .unwrap();
+        let elapsed = timer_now() - start;
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < Duration::from_millis(500));
+        assert!(thread.get_thread_stats().total_runtime < 20_000_000);
+
+        // a deadline in the past returns immediately
+        thread.sleep_until(Duration::default()).await.unwrap();
+
+        // sleeping leaves no signal callbacks behind
+        let callbacks = thread.signal_callback_count();
+        for _ in 0..10 {
+            thread.sleep_until(Duration::default()).await.unwrap();
+            let deadline = timer_now() + Duration::from_millis(1);
+            thread.sleep_until(deadline).await.unwrap();
+        }
+        assert_eq!(thread.signal_callback_count(), callbacks);
+
+        // it doesn't wake up until resumed
+        let token = SuspendToken::create(&thread);
+        let deadline = timer_now() + Duration::from_millis(10);
+        let sleep = async_std::task::spawn({
+            let thread = thread.clone();
+            async move { thread.sleep_until(deadline).await }
+        });
+        async_std::task::sleep(Duration::from_millis(30)).await;
+        assert_eq!(thread.state(), ThreadState::Suspended);
+        drop(token);
+        assert_eq!(sleep.await, Ok(()));
+        assert_eq!(thread.state(), ThreadState::Running);
+        thread.end_running(context);
+    }
+
     #[test]
     #[ignore]
     fn start() {
@@ -1,5 +1,5 @@
 use crate::{ZxError, ZxResult};
-use kernel_hal::UserContext;
+use kernel_hal::{GeneralRegs, UserContext};
 use numeric_enum_macro::numeric_enum;
 
 numeric_enum! {
@@ -18,6 +18,16 @@ numeric_enum! {
     }
 }
 
+/// The number of register state kinds supported by `read_state` and `write_state`.
+#[cfg(target_arch = "x86_64")]
+pub const NUM_STATE_KINDS: u32 = 3;
+/// The number of register state kinds supported by `read_state` and `write_state`.
+#[cfg(not(target_arch = "x86_64"))]
+pub const NUM_STATE_KINDS: u32 = 1;
+
+/// The size of the largest register state kind, so larger buffers are never needed.
+pub const MAX_STATE_SIZE: usize = core::mem::size_of::<GeneralRegs>();
+
 pub trait ContextExt {
     fn read_state(&self, kind: ThreadStateKind, buf: &mut [u8]) -> ZxResult<usize>;
     fn write_state(&mut self, kind: ThreadStateKind, buf: &[u8]) -> ZxResult;
@@ -31,23 +41,65 @@ impl ContextExt for UserContext {
             ThreadStateKind::FS => buf.write_struct(&self.general.fsbase),
             #[cfg(target_arch = "x86_64")]
             ThreadStateKind::GS => buf.write_struct(&self.general.gsbase),
-            _ => unimplemented!(),
+            _ => Err(ZxError::NOT_SUPPORTED),
         }
     }
 
     fn write_state(&mut self, kind: ThreadStateKind, buf: &[u8]) -> ZxResult {
         match kind {
-            ThreadStateKind::General => self.general = buf.read_struct()?,
+            ThreadStateKind::General => {
+                self.general = check_general(&self.general, buf.read_struct()?)?
+            }
             #[cfg(target_arch = "x86_64")]
-            ThreadStateKind::FS => self.general.fsbase = buf.read_struct()?,
+            ThreadStateKind::FS => {
+                let fsbase = buf.read_struct()?;
+                if !is_canonical(fsbase) {
+                    return Err(ZxError::INVALID_ARGS);
+                }
+                self.general.fsbase = fsbase;
+            }
             #[cfg(target_arch = "x86_64")]
-            ThreadStateKind::GS => self.general.gsbase = buf.read_struct()?,
-            _ => unimplemented!(),
+            ThreadStateKind::GS => {
+                let gsbase = buf.read_struct()?;
+                if !is_canonical(gsbase) {
+                    return Err(ZxError::INVALID_ARGS);
+                }
+                self.general.gsbase = gsbase;
+            }
+            _ => return Err(ZxError::NOT_SUPPORTED),
         }
         Ok(())
     }
 }
 
+/// RFLAGS bits that user can change: CF, PF, AF, ZF, SF, TF, DF, OF, NT, AC and ID.
+#[cfg(target_arch = "x86_64")]
+const X86_FLAGS_USER: usize = 0x0024_4dd5;
+
+/// Validate general registers to be written over `old`.
+#[cfg(target_arch = "x86_64")]
+fn check_general(old: &GeneralRegs, mut regs: GeneralRegs) -> ZxResult<GeneralRegs> {
+    if !is_canonical(regs.rip) || !is_canonical(regs.fsbase) || !is_canonical(regs.gsbase) {
+        return Err(ZxError::INVALID_ARGS);
+    }
+    // system flags like IF are kept as they are
+    regs.rflags = (old.rflags & !X86_FLAGS_USER) | (regs.rflags & X86_FLAGS_USER);
+    Ok(regs)
+}
+
+/// Validate general registers to be written over `old`.
+#[cfg(not(target_arch = "x86_64"))]
+fn check_general(_old: &GeneralRegs, regs: GeneralRegs) -> ZxResult<GeneralRegs> {
+    Ok(regs)
+}
+
+/// Whether `addr` is a canonical address, i.e. bits 63:47 are all the same.
+#[cfg(target_arch = "x86_64")]
+fn is_canonical(addr: usize) -> bool {
+    let high = addr as isize >> 47;
+    high == 0 || high == -1
+}
+
 trait BufExt {
     fn read_struct<T>(&self) -> ZxResult<T>;
     fn write_struct<T: Copy>(&mut self, value: &T) -> ZxResult<usize>;
@@ -56,10 +108,10 @@ trait BufExt {
 #[allow(unsafe_code)]
 impl BufExt for [u8] {
     fn read_struct<T>(&self) -> ZxResult<T> {
-        if self.len() < core::mem::size_of::<T>() {
-            return Err(ZxError::BUFFER_TOO_SMALL);
+        if self.len() != core::mem::size_of::<T>() {
+            return Err(ZxError::INVALID_ARGS);
         }
-        Ok(unsafe { (self.as_ptr() as *const T).read() })
+        Ok(unsafe { (self.as_ptr() as *const T).read_unaligned() })
     }
 
     fn write_struct<T: Copy>(&mut self, value: &T) -> ZxResult<usize> {
@@ -67,7 +119,7 @@ impl BufExt for [u8] {
             return Err(ZxError::BUFFER_TOO_SMALL);
         }
         unsafe {
-            *(self.as_mut_ptr() as *mut T) = *value;
+            (self.as_mut_ptr() as *mut T).write_unaligned(*value);
         }
         Ok(core::mem::size_of::<T>())
     }
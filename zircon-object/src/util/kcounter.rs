@@ -29,6 +29,19 @@ pub struct KCounterDescriptor {
     pub name: &'static str,
 }
 
+/// The length of a kcounter name in the records exported to userspace.
+pub const KCOUNTER_NAME_LEN: usize = 56;
+
+impl KCounterDescriptor {
+    /// The name padded with zeros, or truncated to `KCOUNTER_NAME_LEN` bytes.
+    pub fn padded_name(&self) -> [u8; KCOUNTER_NAME_LEN] {
+        let mut name = [0u8; KCOUNTER_NAME_LEN];
+        let length = self.name.len().min(KCOUNTER_NAME_LEN);
+        name[..length].copy_from_slice(&self.name.as_bytes()[..length]);
+        name
+    }
+}
+
 /// Define a new KCounter.
 #[macro_export]
 macro_rules! kcounter {
@@ -52,6 +65,7 @@ pub struct KCounterDescriptorArray(pub &'static [KCounterDescriptor]);
 
 impl KCounterDescriptorArray {
     /// Get kcounter descriptor array from symbols.
+    #[cfg(target_os = "none")]
     #[allow(unsafe_code)]
     pub fn get() -> Self {
         extern "C" {
@@ -63,6 +77,41 @@ impl KCounterDescriptorArray {
         let descs = unsafe { core::slice::from_raw_parts(start, end.offset_from(start) as usize) };
         KCounterDescriptorArray(descs)
     }
+
+    /// Get kcounter descriptor array.
+    /// NOTE: the linker does not collect kcounters in libos, so this is empty
+    /// unless they are registered with `register`.
+    #[cfg(not(target_os = "none"))]
+    pub fn get() -> Self {
+        KCounterDescriptorArray(*LIBOS_DESCRIPTORS.lock())
+    }
+
+    /// Register the kcounter descriptors returned by `get` in libos.
+    #[cfg(not(target_os = "none"))]
+    pub fn register(descs: &'static [KCounterDescriptor]) {
+        *LIBOS_DESCRIPTORS.lock() = descs;
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+static LIBOS_DESCRIPTORS: spin::Mutex<&'static [KCounterDescriptor]> = spin::Mutex::new(&[]);
+
+/// The name and the value of a kcounter, returned by `ZX_INFO_KERNEL_COUNTERS`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct KCounterInfo {
+    /// The name, padded with zeros.
+    pub name: [u8; KCOUNTER_NAME_LEN],
+    pub value: u64,
+}
+
+impl From<&KCounterDescriptor> for KCounterInfo {
+    fn from(desc: &KCounterDescriptor) -> Self {
+        KCounterInfo {
+            name: desc.padded_name(),
+            value: desc.counter.get() as u64,
+        }
+    }
 }
 
 impl Debug for KCounterDescriptorArray {
@@ -73,3 +122,38 @@ impl Debug for KCounterDescriptorArray {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info() {
+        static COUNTER: KCounter = KCounter::new();
+        let desc = KCounterDescriptor {
+            counter: &COUNTER,
+            name: "test.counter",
+        };
+        COUNTER.add(3);
+        COUNTER.add(1);
+        let info = KCounterInfo::from(&desc);
+        assert_eq!(&info.name[..12], b"test.counter");
+        assert!(info.name[12..].iter().all(|&b| b == 0));
+        assert_eq!(info.value, 4);
+    }
+
+    #[test]
+    fn long_name() {
+        static COUNTER: KCounter = KCounter::new();
+        let name = "a.very.long.counter.name.that.does.not.fit.in.the.record.at.all";
+        let desc = KCounterDescriptor {
+            counter: &COUNTER,
+            name,
+        };
+        assert!(name.len() > KCOUNTER_NAME_LEN);
+        assert_eq!(
+            &desc.padded_name()[..],
+            &name.as_bytes()[..KCOUNTER_NAME_LEN]
+        );
+    }
+}
@@ -1,9 +1,10 @@
 //! Objects for Virtual Memory Management.
 
+mod stream;
 mod vmar;
 mod vmo;
 
-pub use self::{vmar::*, vmo::*};
+pub use self::{stream::*, vmar::*, vmo::*};
 pub use kernel_hal::MMUFlags;
 
 /// Physical Address
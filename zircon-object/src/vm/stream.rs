@@ -0,0 +1,214 @@
+use {
+    super::*,
+    crate::object::*,
+    alloc::sync::Arc,
+    bitflags::bitflags,
+    core::sync::atomic::{AtomicBool, Ordering},
+    numeric_enum_macro::numeric_enum,
+    spin::Mutex,
+};
+
+/// A readable, writable, seekable interface to some underlying storage
+///
+/// ## SYNOPSIS
+///
+/// A stream is an interface for reading and writing data to a VMO,
+/// keeping track of the current seek offset.
+pub struct Stream {
+    base: KObjectBase,
+    options: StreamOptions,
+    vmo: Arc<VmObject>,
+    seek: Mutex<usize>,
+    append: AtomicBool,
+}
+
+impl_kobject!(Stream);
+
+bitflags! {
+    pub struct StreamOptions: u32 {
+        #[allow(clippy::identity_op)]
+        const MODE_READ     = 1 << 0;
+        const MODE_WRITE    = 1 << 1;
+        const MODE_APPEND   = 1 << 2;
+    }
+}
+
+numeric_enum! {
+    #[repr(u32)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SeekOrigin {
+        Start = 0,
+        Current = 1,
+        End = 2,
+    }
+}
+
+impl Stream {
+    /// Create a stream on `vmo` with initial `seek` offset.
+    pub fn create(options: StreamOptions, vmo: Arc<VmObject>, seek: usize) -> Arc<Self> {
+        Arc::new(Stream {
+            base: KObjectBase::new(),
+            options,
+            vmo,
+            seek: Mutex::new(seek),
+            append: AtomicBool::new(options.contains(StreamOptions::MODE_APPEND)),
+        })
+    }
+
+    /// Get the options of the stream.
+    pub fn options(&self) -> StreamOptions {
+        self.options
+    }
+
+    /// Read data from the current seek offset, and advance the offset.
+    ///
+    /// Fewer bytes are read if the end of the content is reached.
+    pub fn read(&self, buf: &mut [u8]) -> ZxResult<usize> {
+        let mut seek = self.seek.lock();
+        let content_size = self.vmo.content_size();
+        if *seek >= content_size {
+            return Ok(0);
+        }
+        let len = buf.len().min(content_size - *seek);
+        self.vmo.read(*seek, &mut buf[..len])?;
+        *seek += len;
+        Ok(len)
+    }
+
+    /// Get whether the stream is in append mode.
+    pub fn is_append(&self) -> bool {
+        self.append.load(Ordering::SeqCst)
+    }
+
+    /// Set whether the stream is in append mode.
+    pub fn set_append(&self, append: bool) {
+        self.append.store(append, Ordering::SeqCst);
+    }
+
+    /// Write data at the current seek offset, and advance the offset.
+    ///
+    /// In append mode, the seek offset is moved to the content size first.
+    /// Fewer bytes are written if the end of the VMO is reached.
+    /// The content size grows if data is written beyond it.
+    pub fn write(&self, data: &[u8]) -> ZxResult<usize> {
+        let mut seek = self.seek.lock();
+        if self.is_append() {
+            *seek = self.vmo.content_size();
+        }
+        let size = self.vmo.len();
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if *seek >= size {
+            return Err(ZxError::NO_SPACE);
+        }
+        let len = data.len().min(size - *seek);
+        self.vmo.write(*seek, &data[..len])?;
+        *seek += len;
+        if *seek > self.vmo.content_size() {
+            self.vmo.set_content_size(*seek)?;
+        }
+        Ok(len)
+    }
+
+    /// Set the seek offset relative to `origin`, and return the new offset.
+    pub fn seek(&self, origin: SeekOrigin, offset: isize) -> ZxResult<usize> {
+        let mut seek = self.seek.lock();
+        let base = match origin {
+            SeekOrigin::Start => 0,
+            SeekOrigin::Current => *seek,
+            SeekOrigin::End => self.vmo.content_size(),
+        };
+        let new_seek = if offset >= 0 {
+            base.checked_add(offset as usize)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as usize)
+        };
+        *seek = new_seek.ok_or(ZxError::INVALID_ARGS)?;
+        Ok(*seek)
+    }
+
+    /// Get information of the stream.
+    pub fn get_info(&self) -> StreamInfo {
+        StreamInfo {
+            options: self.options.bits(),
+            padding1: 0,
+            seek: *self.seek.lock() as u64,
+            content_size: self.vmo.content_size() as u64,
+        }
+    }
+}
+
+/// Information of a stream, returned by `ZX_INFO_STREAM`.
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct StreamInfo {
+    /// The options passed to `zx_stream_create()`.
+    pub options: u32,
+    padding1: u32,
+    /// The current seek offset.
+    pub seek: u64,
+    /// The content size of the underlying VMO.
+    pub content_size: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seek() {
+        let vmo = VmObject::new_paged(1);
+        let stream = Stream::create(StreamOptions::MODE_READ, vmo, 0);
+        assert_eq!(stream.seek(SeekOrigin::Start, 100), Ok(100));
+        assert_eq!(stream.get_info().seek, 100);
+        assert_eq!(stream.seek(SeekOrigin::Current, -50), Ok(50));
+        assert_eq!(stream.seek(SeekOrigin::End, 0), Ok(PAGE_SIZE));
+        assert_eq!(
+            stream.seek(SeekOrigin::Start, -1),
+            Err(ZxError::INVALID_ARGS)
+        );
+
+        let info = stream.get_info();
+        assert_eq!(info.options, StreamOptions::MODE_READ.bits());
+        assert_eq!(info.seek, PAGE_SIZE as u64);
+        assert_eq!(info.content_size, PAGE_SIZE as u64);
+    }
+
+    #[test]
+    fn read_write() {
+        let vmo = VmObject::new_paged(1);
+        let stream = Stream::create(StreamOptions::MODE_READ | StreamOptions::MODE_WRITE, vmo, 0);
+        assert_eq!(stream.write(&[1, 2, 3]), Ok(3));
+        stream.seek(SeekOrigin::Start, 1).unwrap();
+        let mut buf = [0u8; 2];
+        assert_eq!(stream.read(&mut buf), Ok(2));
+        assert_eq!(buf, [2, 3]);
+        assert_eq!(stream.get_info().seek, 3);
+
+        stream.seek(SeekOrigin::End, -1).unwrap();
+        assert_eq!(stream.write(&[4, 5]), Ok(1));
+        assert_eq!(stream.write(&[6]), Err(ZxError::NO_SPACE));
+    }
+
+    #[test]
+    fn append() {
+        let vmo = VmObject::new_paged(1);
+        vmo.set_content_size(0).unwrap();
+        let stream = Stream::create(StreamOptions::MODE_READ | StreamOptions::MODE_WRITE, vmo, 0);
+        assert!(!stream.is_append());
+        stream.set_append(true);
+        assert!(stream.is_append());
+
+        assert_eq!(stream.write(&[1, 2]), Ok(2));
+        stream.seek(SeekOrigin::Start, 0).unwrap();
+        assert_eq!(stream.write(&[3, 4]), Ok(2));
+        assert_eq!(stream.get_info().seek, 4);
+        assert_eq!(stream.get_info().content_size, 4);
+
+        stream.seek(SeekOrigin::Start, 0).unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(stream.read(&mut buf), Ok(4));
+        assert_eq!(buf, [1, 2, 3, 4]);
+    }
+}
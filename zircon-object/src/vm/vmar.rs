@@ -1,7 +1,7 @@
 use core::sync::atomic::*;
 use {
-    super::*, crate::object::*, alloc::collections::VecDeque, alloc::sync::Arc, alloc::vec::Vec,
-    bitflags::bitflags, kernel_hal::PageTable, spin::Mutex,
+    super::*, crate::object::*, alloc::sync::Arc, alloc::vec::Vec, bitflags::bitflags,
+    kernel_hal::PageTable, spin::Mutex,
 };
 
 bitflags! {
@@ -145,6 +145,9 @@ impl VmAddressRegion {
     }
 
     /// Map the `vmo` into this VMAR.
+    ///
+    /// If `map_range` is false, pages are committed and mapped on the first
+    /// page fault instead, see `handle_page_fault`.
     #[allow(clippy::too_many_arguments)]
     pub fn map_ext(
         &self,
@@ -154,7 +157,7 @@ impl VmAddressRegion {
         len: usize,
         flags: MMUFlags,
         overwrite: bool,
-        _map_range: bool,
+        map_range: bool,
     ) -> ZxResult<VirtAddr> {
         if !page_aligned(vmo_offset) || !page_aligned(len) {
             return Err(ZxError::INVALID_ARGS);
@@ -165,23 +168,32 @@ impl VmAddressRegion {
         }
         let mut guard = self.inner.lock();
         let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
-        let offset = self.determine_offset(inner, vmar_offset, len, PAGE_SIZE)?;
+        let offset = match vmar_offset {
+            Some(offset) if overwrite => {
+                // the range must be in this VMAR, and mappings there are replaced
+                if !page_aligned(offset) || offset > self.size || len > self.size - offset {
+                    return Err(ZxError::INVALID_ARGS);
+                }
+                Self::unmap_inner(inner, self.addr + offset, len, false)?;
+                offset
+            }
+            _ => self.determine_offset(inner, vmar_offset, len, PAGE_SIZE)?,
+        };
         let addr = self.addr + offset;
         let mut flags = flags;
         // if vmo != 0
         {
             flags |= MMUFlags::from_bits_truncate(vmo.get_cache_policy() as u32 as usize);
         }
-        // align = 1K? 2K? 4K? 8K? ...
-        if !self.test_map(inner, offset, len, PAGE_SIZE) {
-            if overwrite {
-                self.unmap(addr, len)?;
-            } else {
-                return Err(ZxError::NO_MEMORY);
-            }
-        }
-        let mapping = VmMapping::new(addr, len, vmo, vmo_offset, flags, self.page_table.clone());
-        let map_range = true;
+        let mapping = VmMapping::new(
+            addr,
+            len,
+            vmo,
+            vmo_offset,
+            flags,
+            flags,
+            self.page_table.clone(),
+        );
         if map_range {
             mapping.map()?;
         }
@@ -189,6 +201,49 @@ impl VmAddressRegion {
         Ok(addr)
     }
 
+    /// Map the `vmo` with `guard_size` bytes left unmapped below it, e.g. for a stack.
+    ///
+    /// The guard and the mapping are put in a sub-region, so nothing else can be
+    /// mapped into the guard. Touching it always faults, and it takes no frame.
+    /// Return the address of the mapping.
+    #[allow(clippy::too_many_arguments)]
+    pub fn map_with_guard(
+        self: &Arc<Self>,
+        vmar_offset: Option<usize>,
+        vmo: Arc<VmObject>,
+        vmo_offset: usize,
+        len: usize,
+        flags: MMUFlags,
+        guard_size: usize,
+        map_range: bool,
+    ) -> ZxResult<VirtAddr> {
+        if guard_size == 0 || !page_aligned(guard_size) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let total_len = len.checked_add(guard_size).ok_or(ZxError::INVALID_ARGS)?;
+        let mut region_flags = VmarFlags::CAN_MAP_SPECIFIC;
+        region_flags.set(VmarFlags::CAN_MAP_READ, flags.contains(MMUFlags::READ));
+        region_flags.set(VmarFlags::CAN_MAP_WRITE, flags.contains(MMUFlags::WRITE));
+        region_flags.set(
+            VmarFlags::CAN_MAP_EXECUTE,
+            flags.contains(MMUFlags::EXECUTE),
+        );
+        let region = self.allocate(vmar_offset, total_len, region_flags, PAGE_SIZE)?;
+        let mapped = region.map_ext(
+            Some(guard_size),
+            vmo,
+            vmo_offset,
+            len,
+            flags,
+            false,
+            map_range,
+        );
+        if mapped.is_err() {
+            region.destroy()?;
+        }
+        mapped
+    }
+
     /// Unmaps all VMO mappings and destroys all sub-regions within the absolute range
     /// including `addr` and ending before exclusively at `addr + len`.
     /// Any sub-region that is in the range must be fully in the range
@@ -199,14 +254,29 @@ impl VmAddressRegion {
         if !page_aligned(addr) || !page_aligned(len) || len == 0 {
             return Err(ZxError::INVALID_ARGS);
         }
-        let mut guard = self.inner.lock();
-        let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
+        with_tlb_shootdown(|| {
+            let mut guard = self.inner.lock();
+            let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
+            Self::unmap_inner(inner, addr, len, true)
+        })
+    }
 
+    /// Unmap `[addr, addr + len)` of `inner`.
+    ///
+    /// If not `can_destroy_regions`, any sub-region overlapping the range is an error.
+    fn unmap_inner(
+        inner: &mut VmarInner,
+        addr: VirtAddr,
+        len: usize,
+        can_destroy_regions: bool,
+    ) -> ZxResult {
         let begin = addr;
         let end = addr + len;
         // check partial overlapped sub-regions
         for vmar in inner.children.iter() {
-            if vmar.partial_overlap(begin, end) {
+            if vmar.partial_overlap(begin, end)
+                || (!can_destroy_regions && vmar.overlap(begin, end))
+            {
                 return Err(ZxError::INVALID_ARGS);
             }
         }
@@ -224,46 +294,100 @@ impl VmAddressRegion {
         Ok(())
     }
 
+    /// Change the permissions of the mappings in `[addr, addr + len)`.
+    ///
+    /// The range must be covered by mappings of this VMAR and its sub-regions,
+    /// and `flags` must be within the permissions each of them was mapped with.
+    /// Mappings partially in the range are split.
     pub fn protect(&self, addr: usize, len: usize, flags: MMUFlags) -> ZxResult {
-        let mut guard = self.inner.lock();
-        let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
-        let end_addr = addr + len;
-        let length: usize = inner
-            .mappings
-            .iter()
-            .filter_map(|map| {
-                if map.addr() >= addr && map.end_addr() <= end_addr {
-                    Some(map.size())
-                } else {
-                    None
+        if !page_aligned(addr) || !page_aligned(len) || len == 0 {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let end_addr = addr.checked_add(len).ok_or(ZxError::INVALID_ARGS)?;
+        with_tlb_shootdown(|| {
+            let mut guard = self.inner.lock();
+            let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
+            let mut ranges = Vec::new();
+            Self::check_protect(inner, addr, end_addr, flags, &mut ranges)?;
+            ranges.sort_unstable();
+            // the range must be covered without holes
+            let mut next = addr;
+            for &(begin, end) in ranges.iter() {
+                if begin > next {
+                    return Err(ZxError::NOT_FOUND);
                 }
-            })
-            .sum();
-        if length != len {
-            return Err(ZxError::NOT_FOUND);
+                next = end;
+            }
+            if next < end_addr {
+                return Err(ZxError::NOT_FOUND);
+            }
+            Self::protect_inner(inner, addr, end_addr, flags);
+            Ok(())
+        })
+    }
+
+    /// Collect ranges of the mappings overlapping `[begin, end)` in `inner`
+    /// and its sub-regions, checking that they allow `flags`.
+    fn check_protect(
+        inner: &VmarInner,
+        begin: VirtAddr,
+        end: VirtAddr,
+        flags: MMUFlags,
+        ranges: &mut Vec<(VirtAddr, VirtAddr)>,
+    ) -> ZxResult {
+        for map in inner.mappings.iter().filter(|map| map.overlap(begin, end)) {
+            if !map.is_valid_mapping_flags(flags) {
+                return Err(ZxError::ACCESS_DENIED);
+            }
+            ranges.push((map.addr(), map.end_addr()));
         }
-        if inner
-            .mappings
+        for vmar in inner
+            .children
             .iter()
-            .filter(|map| map.addr() >= addr && map.end_addr() <= addr) // get mappings in range: [addr, end_addr]
-            .any(|map| !map.is_valid_mapping_flags(flags))
-        // check if protect flags is valid
+            .filter(|vmar| vmar.overlap(begin, end))
         {
-            return Err(ZxError::ACCESS_DENIED);
+            if let Some(inner) = vmar.inner.lock().as_ref() {
+                Self::check_protect(inner, begin, end, flags, ranges)?;
+            }
         }
-        inner
+        Ok(())
+    }
+
+    /// Protect the mappings in `[begin, end)` checked by `check_protect`.
+    fn protect_inner(inner: &mut VmarInner, begin: VirtAddr, end: VirtAddr, flags: MMUFlags) {
+        let maps: Vec<_> = inner
             .mappings
             .iter()
-            .filter(|map| map.addr() >= addr && map.end_addr() <= addr)
-            .for_each(|map| {
-                map.protect(flags);
-            });
-        Ok(())
+            .filter(|map| map.overlap(begin, end))
+            .cloned()
+            .collect();
+        for map in maps {
+            if let Some(suffix) = map.split(end) {
+                inner.mappings.push(suffix);
+            }
+            let map = match map.split(begin) {
+                Some(middle) => {
+                    inner.mappings.push(middle.clone());
+                    middle
+                }
+                None => map,
+            };
+            map.protect(flags);
+        }
+        for vmar in inner
+            .children
+            .iter()
+            .filter(|vmar| vmar.overlap(begin, end))
+        {
+            if let Some(inner) = vmar.inner.lock().as_mut() {
+                Self::protect_inner(inner, begin, end, flags);
+            }
+        }
     }
 
     /// Unmap all mappings within the VMAR, and destroy all sub-regions of the region.
     pub fn destroy(self: &Arc<Self>) -> ZxResult {
-        self.destroy_internal()?;
+        with_tlb_shootdown(|| self.destroy_internal())?;
         // remove from parent
         if let Some(parent) = &self.parent {
             let mut guard = parent.inner.lock();
@@ -286,13 +410,15 @@ impl VmAddressRegion {
 
     /// Unmap all mappings and destroy all sub-regions of VMAR.
     pub fn clear(&self) -> ZxResult {
-        let mut guard = self.inner.lock();
-        let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
-        for vmar in inner.children.drain(..) {
-            vmar.destroy_internal()?;
-        }
-        inner.mappings.clear();
-        Ok(())
+        with_tlb_shootdown(|| {
+            let mut guard = self.inner.lock();
+            let inner = guard.as_mut().ok_or(ZxError::BAD_STATE)?;
+            for vmar in inner.children.drain(..) {
+                vmar.destroy_internal()?;
+            }
+            inner.mappings.clear();
+            Ok(())
+        })
     }
 
     /// Get physical address of the underlying page table.
@@ -402,6 +528,15 @@ impl VmAddressRegion {
         }
     }
 
+    /// Get the zCore specific flags of the VMAR, which are not in `VmarInfo`.
+    pub fn get_info_flags(&self) -> VmarInfoFlags {
+        if self.parent.is_none() {
+            VmarInfoFlags::ROOT
+        } else {
+            VmarInfoFlags::empty()
+        }
+    }
+
     pub fn get_flags(&self) -> VmarFlags {
         self.flags
     }
@@ -434,9 +569,11 @@ impl VmAddressRegion {
         None
     }
 
-    /// Handle page fault happened on this VMAR.
+    /// Handle a page fault at `vaddr` from an access of `flags`.
     ///
-    /// The fault virtual address is `vaddr` and the reason is in `flags`.
+    /// An uncommitted page is committed and mapped, so the access can be retried.
+    /// Return `NOT_FOUND` if nothing is mapped at `vaddr`, or `ACCESS_DENIED`
+    /// if the mapping doesn't allow the access. Both are faults of the program.
     pub fn handle_page_fault(&self, vaddr: VirtAddr, flags: MMUFlags) -> ZxResult {
         let guard = self.inner.lock();
         let inner = guard.as_ref().unwrap();
@@ -449,31 +586,144 @@ impl VmAddressRegion {
         Err(ZxError::NOT_FOUND)
     }
 
+    /// Check if `[vaddr, vaddr + len)` is covered by mappings allowing an access of `flags`.
+    ///
+    /// Pages not committed yet are accessible, as a page fault commits them.
+    pub fn check_access(&self, vaddr: VirtAddr, len: usize, flags: MMUFlags) -> bool {
+        let end = match vaddr.checked_add(len) {
+            Some(end) => end,
+            None => return false,
+        };
+        let mut addr = vaddr;
+        while addr < end {
+            match self.accessible_end(addr, flags) {
+                Some(mapping_end) => addr = mapping_end,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Get the end of the mapping at `vaddr`, if it allows an access of `flags`.
+    fn accessible_end(&self, vaddr: VirtAddr, flags: MMUFlags) -> Option<VirtAddr> {
+        let guard = self.inner.lock();
+        let inner = guard.as_ref()?;
+        if let Some(child) = inner.children.iter().find(|ch| ch.contains(vaddr)) {
+            return child.accessible_end(vaddr, flags);
+        }
+        let mapping = inner.mappings.iter().find(|map| map.contains(vaddr))?;
+        let inner = mapping.inner.lock();
+        if !inner.flags.contains(flags) {
+            return None;
+        }
+        Some(inner.end_addr())
+    }
+
+    /// Get memory usage statistics of mappings in this VMAR and its descendants.
     pub fn get_task_stats(&self) -> ZxInfoTaskStats {
         let mut task_stats = ZxInfoTaskStats::default();
-        let mut list = VecDeque::new();
-        self.inner
-            .lock()
-            .as_ref()
-            .unwrap()
-            .children
-            .iter()
-            .for_each(|child| {
-                list.push_back(child.clone());
-            });
-        while let Some(vmar) = list.pop_front() {
-            let vmar_inner = vmar.inner.lock();
-            let inner = vmar_inner.as_ref().unwrap();
-            inner.children.iter().for_each(|child| {
-                list.push_back(child.clone());
-            });
-            inner.mappings.iter().for_each(|map| {
-                map.fill_in_task_status(&mut task_stats);
-            });
-        }
+        self.fill_in_task_stats(&mut task_stats);
         task_stats
     }
 
+    fn fill_in_task_stats(&self, task_stats: &mut ZxInfoTaskStats) {
+        let (children, mappings) = match self.inner.lock().as_ref() {
+            Some(inner) => (inner.children.clone(), inner.mappings.clone()),
+            None => return,
+        };
+        for map in mappings.iter() {
+            map.fill_in_task_status(task_stats);
+        }
+        for child in children.iter() {
+            child.fill_in_task_stats(task_stats);
+        }
+    }
+
+    /// Get the flattened VMAR and mapping tree of this root VMAR.
+    ///
+    /// Records are in depth-first order, starting with the address space itself.
+    pub fn get_maps(&self) -> Vec<ZxInfoMaps> {
+        let mut maps = Vec::new();
+        maps.push(ZxInfoMaps::new(
+            "root",
+            self.addr,
+            self.size,
+            0,
+            ZxInfoMapsType::Aspace,
+        ));
+        self.collect_maps(1, usize::MAX, &mut maps);
+        maps
+    }
+
+    /// Get the flattened tree rooted at this VMAR, down to `max_depth` levels below it.
+    ///
+    /// Records are in depth-first order, starting with this VMAR at depth 0.
+    /// Nothing deeper is visited, so the cost is bounded by the records returned.
+    pub fn get_maps_with_depth(&self, max_depth: usize) -> Vec<ZxInfoMaps> {
+        let mut maps = Vec::new();
+        self.collect_maps(0, max_depth, &mut maps);
+        maps
+    }
+
+    fn collect_maps(&self, depth: usize, max_depth: usize, maps: &mut Vec<ZxInfoMaps>) {
+        maps.push(ZxInfoMaps::new(
+            &self.base.name(),
+            self.addr,
+            self.size,
+            depth,
+            ZxInfoMapsType::Vmar,
+        ));
+        if depth >= max_depth {
+            return;
+        }
+        let (mut children, mut mappings) = match self.inner.lock().as_ref() {
+            Some(inner) => (inner.children.clone(), inner.mappings.clone()),
+            None => return,
+        };
+        children.sort_by_key(|child| child.addr);
+        mappings.sort_by_key(|map| map.addr());
+        let mut children = children.iter().peekable();
+        let mut mappings = mappings.iter().peekable();
+        loop {
+            let child_first = match (children.peek(), mappings.peek()) {
+                (Some(child), Some(map)) => child.addr < map.addr(),
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            if child_first {
+                children
+                    .next()
+                    .unwrap()
+                    .collect_maps(depth + 1, max_depth, maps);
+            } else {
+                maps.push(mappings.next().unwrap().get_info(depth + 1));
+            }
+        }
+    }
+
+    /// Get all VMOs mapped in this VMAR and its descendants.
+    ///
+    /// A VMO mapped more than once appears once per mapping.
+    pub fn get_vmos(&self) -> Vec<Arc<VmObject>> {
+        let mut vmos = Vec::new();
+        self.collect_vmos(&mut vmos);
+        vmos
+    }
+
+    fn collect_vmos(&self, vmos: &mut Vec<Arc<VmObject>>) {
+        let children = match self.inner.lock().as_ref() {
+            Some(inner) => {
+                vmos.extend(inner.mappings.iter().map(|map| map.vmo.clone()));
+                inner.children.clone()
+            }
+            None => return,
+        };
+        for child in children {
+            child.collect_vmos(vmos);
+        }
+    }
+
     #[cfg(test)]
     fn count(&self) -> usize {
         let mut guard = self.inner.lock();
@@ -498,15 +748,76 @@ pub struct VmarInfo {
     len: usize,
 }
 
+bitflags! {
+    /// zCore specific flags of a VMAR.
+    pub struct VmarInfoFlags: u32 {
+        /// The root VMAR of an address space.
+        const ROOT = 1;
+    }
+}
+
+/// Describes a VMAR or a mapping in an address space.
+#[repr(C)]
+pub struct ZxInfoMaps {
+    /// The name of the VMAR or the mapped VMO.
+    name: [u8; 32],
+    base: VirtAddr,
+    size: usize,
+    /// The depth of this node in the tree, 0 for the address space.
+    depth: usize,
+    type_: ZxInfoMapsType,
+    /// Only valid if the type is `Mapping`.
+    mapping: ZxInfoMapsMapping,
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZxInfoMapsType {
+    None = 0,
+    Aspace = 1,
+    Vmar = 2,
+    Mapping = 3,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ZxInfoMapsMapping {
+    mmu_flags: u32,
+    padding1: [u8; 4],
+    vmo_koid: KoID,
+    vmo_offset: u64,
+    committed_pages: usize,
+}
+
+impl ZxInfoMaps {
+    fn new(name: &str, base: VirtAddr, size: usize, depth: usize, type_: ZxInfoMapsType) -> Self {
+        let mut info = ZxInfoMaps {
+            name: [0; 32],
+            base,
+            size,
+            depth,
+            type_,
+            mapping: ZxInfoMapsMapping::default(),
+        };
+        // keep the trailing '\0'
+        let length = name.len().min(31);
+        info.name[..length].copy_from_slice(&name.as_bytes()[..length]);
+        info
+    }
+}
+
 /// Virtual Memory Mapping
 pub struct VmMapping {
-    flags: MMUFlags,
+    /// The maximum permissions, which `protect` can not exceed.
+    permissions: MMUFlags,
     vmo: Arc<VmObject>,
     page_table: Arc<Mutex<PageTable>>,
     inner: Mutex<VmMappingInner>,
 }
 
 struct VmMappingInner {
+    /// The current permissions.
+    flags: MMUFlags,
     addr: VirtAddr,
     size: usize,
     vmo_offset: usize,
@@ -527,7 +838,8 @@ impl core::fmt::Debug for VmMapping {
         f.debug_struct("VmMapping")
             .field("addr", &inner.addr)
             .field("size", &inner.size)
-            .field("flags", &self.flags)
+            .field("permissions", &self.permissions)
+            .field("flags", &inner.flags)
             .field("vmo_id", &self.vmo.id())
             .field("vmo_offset", &inner.vmo_offset)
             .finish()
@@ -540,16 +852,18 @@ impl VmMapping {
         size: usize,
         vmo: Arc<VmObject>,
         vmo_offset: usize,
+        permissions: MMUFlags,
         flags: MMUFlags,
         page_table: Arc<Mutex<PageTable>>,
     ) -> Arc<Self> {
         let mapping = Arc::new(VmMapping {
             inner: Mutex::new(VmMappingInner {
+                flags,
                 addr,
                 size,
                 vmo_offset,
             }),
-            flags,
+            permissions,
             page_table,
             vmo: vmo.clone(),
         });
@@ -567,9 +881,9 @@ impl VmMapping {
         let page_num = inner.size / PAGE_SIZE;
         let vmo_offset = inner.vmo_offset / PAGE_SIZE;
         for i in 0..page_num {
-            let paddr = self.vmo.commit_page(vmo_offset + i, self.flags)?;
+            let paddr = self.vmo.commit_page(vmo_offset + i, inner.flags)?;
             page_table
-                .map(inner.addr + i * PAGE_SIZE, paddr, self.flags)
+                .map(inner.addr + i * PAGE_SIZE, paddr, inner.flags)
                 .expect("failed to map");
         }
         Ok(())
@@ -582,19 +896,55 @@ impl VmMapping {
             .unmap_from(&mut page_table, inner.addr, inner.vmo_offset, inner.size);
     }
 
+    fn get_info(&self, depth: usize) -> ZxInfoMaps {
+        let inner = self.inner.lock();
+        let mut info = ZxInfoMaps::new(
+            &self.vmo.name(),
+            inner.addr,
+            inner.size,
+            depth,
+            ZxInfoMapsType::Mapping,
+        );
+        let start_idx = inner.vmo_offset / PAGE_SIZE;
+        let end_idx = start_idx + inner.size / PAGE_SIZE;
+        info.mapping = ZxInfoMapsMapping {
+            mmu_flags: inner.flags.to_vm_perm(),
+            vmo_koid: self.vmo.id(),
+            vmo_offset: inner.vmo_offset as u64,
+            committed_pages: if inner.size == 0 {
+                0
+            } else {
+                self.vmo.committed_pages_in_range(start_idx, end_idx)
+            },
+            ..Default::default()
+        };
+        info
+    }
+
+    /// Add the memory usage of this mapping to `task_stats`.
+    ///
+    /// Pages still shared with a COW clone count as shared even if the VMO
+    /// is mapped only once, with the clone counted as one more sharer.
     fn fill_in_task_status(&self, task_stats: &mut ZxInfoTaskStats) {
         let inner = self.inner.lock();
+        task_stats.mapped_bytes += inner.size as u64;
+        if inner.size == 0 {
+            return;
+        }
         let start_idx = inner.vmo_offset / PAGE_SIZE;
         let end_idx = start_idx + inner.size / PAGE_SIZE;
-        task_stats.mapped_bytes += self.vmo.len() as u64;
-        let committed_pages = self.vmo.committed_pages_in_range(start_idx, end_idx);
-        let share_count = self.vmo.share_count();
+        let committed_bytes = self.vmo.committed_pages_in_range(start_idx, end_idx) * PAGE_SIZE;
+        let cow_shared_bytes = self.vmo.cow_shared_pages_in_range(start_idx, end_idx) * PAGE_SIZE;
+        let share_count = self.vmo.share_count().max(1);
         if share_count == 1 {
-            task_stats.private_bytes += (committed_pages * PAGE_SIZE) as u64;
+            task_stats.private_bytes += (committed_bytes - cow_shared_bytes) as u64;
         } else {
-            task_stats.shared_bytes += (committed_pages * PAGE_SIZE) as u64;
-            task_stats.scaled_shared_bytes += (committed_pages * PAGE_SIZE / share_count) as u64;
+            task_stats.shared_bytes += (committed_bytes - cow_shared_bytes) as u64;
+            task_stats.scaled_shared_bytes +=
+                ((committed_bytes - cow_shared_bytes) / share_count) as u64;
         }
+        task_stats.shared_bytes += cow_shared_bytes as u64;
+        task_stats.scaled_shared_bytes += (cow_shared_bytes / (share_count + 1)) as u64;
     }
 
     /// Cut and unmap regions in `[begin, end)`.
@@ -646,7 +996,8 @@ impl VmMapping {
                 new_len2,
                 self.vmo.clone(),
                 inner.vmo_offset + (end - inner.addr),
-                self.flags,
+                self.permissions,
+                inner.flags,
                 self.page_table.clone(),
             ))
         }
@@ -663,24 +1014,60 @@ impl VmMapping {
     }
 
     fn is_valid_mapping_flags(&self, flags: MMUFlags) -> bool {
-        if !flags.contains(MMUFlags::READ) && self.flags.contains(MMUFlags::READ) {
-            return false;
-        }
-        if !flags.contains(MMUFlags::WRITE) && self.flags.contains(MMUFlags::WRITE) {
-            return false;
-        }
-        if !flags.contains(MMUFlags::EXECUTE) && self.flags.contains(MMUFlags::EXECUTE) {
-            return false;
-        }
-        true
+        self.permissions.contains(flags & MMUFlags::RXW)
     }
 
+    /// Change the read, write and execute permissions of all pages.
+    ///
+    /// Pages shared with a COW clone or the zero frame stay read-only until
+    /// the first write fault, and pages not faulted in yet stay unmapped.
     fn protect(&self, flags: MMUFlags) {
-        let mut pg_table = self.page_table.lock();
-        let inner = self.inner.lock();
-        for i in 0..inner.size {
-            pg_table.protect(inner.addr + i * PAGE_SIZE, flags).unwrap();
-        }
+        let (start, end) = {
+            let mut inner = self.inner.lock();
+            inner.flags.remove(MMUFlags::RXW);
+            inner.flags.insert(flags & MMUFlags::RXW);
+            let start = inner.vmo_offset / PAGE_SIZE;
+            (start, start + pages(inner.size))
+        };
+        // the VMO is locked meanwhile, so no page becomes shared behind us
+        self.vmo
+            .with_writable_pages(start, end, &mut |writable: &[bool]| {
+                let inner = self.inner.lock();
+                // the page table queues the TLB shootdown, done by `protect` of the VMAR
+                let mut pg_table = self.page_table.lock();
+                for (i, &writable) in writable.iter().enumerate() {
+                    let mut flags = inner.flags;
+                    if !writable {
+                        flags.remove(MMUFlags::WRITE);
+                    }
+                    // fails if not mapped
+                    pg_table.protect(inner.addr + i * PAGE_SIZE, flags).ok();
+                }
+            });
+    }
+
+    /// Split the mapping at `vaddr`, keeping the pages mapped.
+    ///
+    /// If `vaddr` is inside, return the new mapping of `[vaddr, end)`.
+    fn split(&self, vaddr: VirtAddr) -> Option<Arc<Self>> {
+        let (len, vmo_offset, flags) = {
+            let mut inner = self.inner.lock();
+            if vaddr <= inner.addr || vaddr >= inner.end_addr() {
+                return None;
+            }
+            let len = inner.end_addr() - vaddr;
+            inner.size = vaddr - inner.addr;
+            (len, inner.vmo_offset + inner.size, inner.flags)
+        };
+        Some(VmMapping::new(
+            vaddr,
+            len,
+            self.vmo.clone(),
+            vmo_offset,
+            self.permissions,
+            flags,
+            self.page_table.clone(),
+        ))
     }
 
     fn size(&self) -> usize {
@@ -695,42 +1082,90 @@ impl VmMapping {
         self.inner.lock().end_addr()
     }
 
-    /// Remove WRITE flag from the mappings for Copy-on-Write.
+    /// Remove WRITE flag from, or unmap, the VMO pages `[offset, offset + len)`
+    /// in this mapping. Both are in pages.
     pub(super) fn range_change(&self, offset: usize, len: usize, op: RangeChangeOp) {
         let inner = self.inner.lock();
-        let start = offset.max(inner.vmo_offset);
-        let end = (inner.vmo_offset + inner.size / PAGE_SIZE).min(offset + len);
-        let mut new_flag = self.flags;
+        let vmo_start = inner.vmo_offset / PAGE_SIZE;
+        let start = offset.max(vmo_start);
+        let end = (vmo_start + inner.size / PAGE_SIZE).min(offset + len);
+        let mut new_flag = inner.flags;
         new_flag.remove(MMUFlags::WRITE);
         if !(start..end).is_empty() {
             let mut pg_table = self.page_table.lock();
-            for i in (start - inner.vmo_offset)..(end - inner.vmo_offset) {
+            for i in (start - vmo_start)..(end - vmo_start) {
                 match op {
-                    RangeChangeOp::RemoveWrite => pg_table
-                        .protect(inner.addr + i * PAGE_SIZE, new_flag)
-                        .unwrap(),
-                    RangeChangeOp::Unmap => pg_table.unmap(inner.addr + i * PAGE_SIZE).unwrap(),
+                    RangeChangeOp::RemoveWrite => {
+                        // fails if not mapped, e.g. not faulted in yet
+                        pg_table.protect(inner.addr + i * PAGE_SIZE, new_flag).ok();
+                    }
+                    RangeChangeOp::Unmap => {
+                        // the page may have been unmapped before, e.g. when a VMO shrinks twice
+                        pg_table.unmap(inner.addr + i * PAGE_SIZE).ok();
+                    }
                 }
             }
         }
     }
 
     fn handle_page_fault(&self, vaddr: VirtAddr, flags: MMUFlags) -> ZxResult {
-        if !self.flags.contains(flags) {
-            return Err(ZxError::ACCESS_DENIED);
-        }
         let vaddr = round_down_pages(vaddr);
-        let page_idx = (vaddr - self.addr()) / PAGE_SIZE;
+        let (page_idx, mut map_flags) = {
+            let inner = self.inner.lock();
+            if !inner.flags.contains(flags) {
+                return Err(ZxError::ACCESS_DENIED);
+            }
+            (
+                (vaddr - inner.addr + inner.vmo_offset) / PAGE_SIZE,
+                inner.flags,
+            )
+        };
         let paddr = self.vmo.commit_page(page_idx, flags)?;
+        // a read may get a page shared with a COW clone or the zero page,
+        // so it is mapped read-only until the first write
+        if !flags.contains(MMUFlags::WRITE) {
+            map_flags.remove(MMUFlags::WRITE);
+        }
         let mut pg_table = self.page_table.lock();
-        pg_table.unmap(vaddr).unwrap();
+        let _ = pg_table.unmap(vaddr);
         pg_table
-            .map(vaddr, paddr, self.flags)
+            .map(vaddr, paddr, map_flags)
             .map_err(|_| ZxError::ACCESS_DENIED)?;
         Ok(())
     }
 }
 
+/// Run `f`, then shoot down the TLB entries it changed on other CPUs.
+///
+/// The page table only queues the shootdown, and it is done here once the
+/// locks taken by `f` are dropped.
+fn with_tlb_shootdown<T>(f: impl FnOnce() -> T) -> T {
+    let ret = f();
+    kernel_hal::flush_tlb_shootdown();
+    ret
+}
+
+trait MMUFlagsExt {
+    fn to_vm_perm(self) -> u32;
+}
+
+impl MMUFlagsExt for MMUFlags {
+    /// Convert to `ZX_VM_PERM_*` bits.
+    fn to_vm_perm(self) -> u32 {
+        let mut perm = 0;
+        if self.contains(MMUFlags::READ) {
+            perm |= 1;
+        }
+        if self.contains(MMUFlags::WRITE) {
+            perm |= 2;
+        }
+        if self.contains(MMUFlags::EXECUTE) {
+            perm |= 4;
+        }
+        perm
+    }
+}
+
 impl VmMappingInner {
     fn end_addr(&self) -> VirtAddr {
         self.addr + self.size
@@ -883,6 +1318,425 @@ mod tests {
         s.child1.unmap(base + 0x8000, 0x1000).unwrap();
     }
 
+    #[test]
+    fn protect() {
+        let vmar = VmAddressRegion::new_root();
+        let vmo = VmObject::new_paged(4);
+        let flags = MMUFlags::READ | MMUFlags::WRITE | MMUFlags::EXECUTE;
+        let addr = vmar.map(None, vmo, 0, 4 * PAGE_SIZE, flags).unwrap();
+        let rw = MMUFlags::READ | MMUFlags::WRITE;
+        let rx = MMUFlags::READ | MMUFlags::EXECUTE;
+
+        assert_eq!(
+            vmar.protect(addr + 1, PAGE_SIZE, rw),
+            Err(ZxError::INVALID_ARGS)
+        );
+        assert_eq!(
+            vmar.protect(addr, 5 * PAGE_SIZE, rw),
+            Err(ZxError::NOT_FOUND)
+        );
+        vmar.protect(addr, 4 * PAGE_SIZE, rw).unwrap();
+        vmar.handle_page_fault(addr, MMUFlags::WRITE).unwrap();
+
+        // protect the middle pages to RX, splitting the mapping
+        vmar.protect(addr + PAGE_SIZE, 2 * PAGE_SIZE, rx).unwrap();
+        assert_eq!(
+            vmar.handle_page_fault(addr + PAGE_SIZE, MMUFlags::WRITE),
+            Err(ZxError::ACCESS_DENIED)
+        );
+        vmar.handle_page_fault(addr + 2 * PAGE_SIZE, MMUFlags::EXECUTE)
+            .unwrap();
+        vmar.handle_page_fault(addr, MMUFlags::WRITE).unwrap();
+        vmar.handle_page_fault(addr + 3 * PAGE_SIZE, MMUFlags::WRITE)
+            .unwrap();
+        assert_eq!(vmar.inner.lock().as_ref().unwrap().mappings.len(), 3);
+
+        // can not exceed the permissions it was mapped with
+        let vmo = VmObject::new_paged(1);
+        let addr = vmar.map(None, vmo, 0, PAGE_SIZE, rw).unwrap();
+        assert_eq!(
+            vmar.protect(addr, PAGE_SIZE, rx),
+            Err(ZxError::ACCESS_DENIED)
+        );
+        vmar.protect(addr, PAGE_SIZE, MMUFlags::READ).unwrap();
+        assert_eq!(
+            vmar.handle_page_fault(addr, MMUFlags::WRITE),
+            Err(ZxError::ACCESS_DENIED)
+        );
+    }
+
+    /// Whether the host maps the page of `addr` writable.
+    fn host_writable(addr: usize) -> bool {
+        let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+        maps.lines().any(|line| {
+            let mut fields = line.split_whitespace();
+            let mut range = fields.next().unwrap().split('-');
+            let begin = usize::from_str_radix(range.next().unwrap(), 16).unwrap();
+            let end = usize::from_str_radix(range.next().unwrap(), 16).unwrap();
+            let perms = fields.next().unwrap();
+            (begin..end).contains(&addr) && perms.as_bytes()[1] == b'w'
+        })
+    }
+
+    /// Write to `addr` like a user thread, which faults on read-only pages.
+    #[allow(unsafe_code)]
+    fn user_write(vmar: &VmAddressRegion, addr: usize, value: u8) {
+        if !host_writable(addr) {
+            vmar.handle_page_fault(addr, MMUFlags::WRITE).unwrap();
+        }
+        unsafe { (addr as *mut u8).write(value) };
+    }
+
+    #[test]
+    fn protect_shared_pages() {
+        let vmar = VmAddressRegion::new_root();
+        let vmo = VmObject::new_paged(3);
+        vmo.write(0, &[1]).unwrap();
+        let rw = MMUFlags::READ | MMUFlags::WRITE;
+        let addr = vmar
+            .map_ext(None, vmo.clone(), 0, 3 * PAGE_SIZE, rw, false, false)
+            .unwrap();
+        vmar.handle_page_fault(addr, MMUFlags::WRITE).unwrap();
+        vmar.handle_page_fault(addr + PAGE_SIZE, MMUFlags::READ)
+            .unwrap();
+        let child = vmo.create_child(false, 0, 3 * PAGE_SIZE);
+
+        // the COW page and the zero page stay read-only, the last page unmapped
+        vmar.protect(addr, 3 * PAGE_SIZE, MMUFlags::READ).unwrap();
+        vmar.protect(addr, 3 * PAGE_SIZE, rw).unwrap();
+        assert!(!host_writable(addr));
+        assert!(!host_writable(addr + PAGE_SIZE));
+
+        user_write(&vmar, addr, 2);
+        user_write(&vmar, addr + PAGE_SIZE, 3);
+        let mut buf = [0u8; 1];
+        child.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [1]);
+        vmo.read(PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [3]);
+        child.read(PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [0]);
+
+        // a private page is writable again
+        vmar.protect(addr, PAGE_SIZE, MMUFlags::READ).unwrap();
+        vmar.protect(addr, PAGE_SIZE, rw).unwrap();
+        assert!(host_writable(addr));
+    }
+
+    #[test]
+    fn protect_nested() {
+        let s = Sample::new();
+        let vmo = VmObject::new_paged(1);
+        let rw = MMUFlags::READ | MMUFlags::WRITE;
+        s.grandson1
+            .map_at(0, vmo.clone(), 0, PAGE_SIZE, rw)
+            .unwrap();
+        s.child2.map_at(0, vmo, 0, PAGE_SIZE, rw).unwrap();
+        let base = s.root.addr();
+
+        // the hole of grandson2 is not covered
+        assert_eq!(
+            s.root.protect(base, 3 * PAGE_SIZE, MMUFlags::READ),
+            Err(ZxError::NOT_FOUND)
+        );
+        s.root.protect(base, PAGE_SIZE, MMUFlags::READ).unwrap();
+        assert_eq!(
+            s.root.handle_page_fault(base, MMUFlags::WRITE),
+            Err(ZxError::ACCESS_DENIED)
+        );
+        s.root
+            .protect(base + 2 * PAGE_SIZE, PAGE_SIZE, MMUFlags::READ)
+            .unwrap();
+        assert_eq!(
+            s.root
+                .handle_page_fault(base + 2 * PAGE_SIZE, MMUFlags::WRITE),
+            Err(ZxError::ACCESS_DENIED)
+        );
+    }
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn zero_page() {
+        let vmar = VmAddressRegion::new_root();
+        let vmo = VmObject::new_paged(1024);
+        let flags = MMUFlags::READ | MMUFlags::WRITE;
+        let addr = vmar
+            .map_ext(None, vmo.clone(), 0, 1024 * PAGE_SIZE, flags, false, false)
+            .unwrap();
+
+        // reads of untouched pages share the zero frame
+        for i in 0..1024 {
+            vmar.handle_page_fault(addr + i * PAGE_SIZE, MMUFlags::READ)
+                .unwrap();
+        }
+        assert_eq!(vmo.committed_bytes(), 0);
+        unsafe {
+            assert_eq!(((addr + 10 * PAGE_SIZE) as *const usize).read(), 0);
+        }
+
+        // a write commits a private frame for the page
+        vmar.handle_page_fault(addr + 10 * PAGE_SIZE, MMUFlags::WRITE)
+            .unwrap();
+        unsafe {
+            ((addr + 10 * PAGE_SIZE) as *mut usize).write(MAGIC);
+            assert_eq!(((addr + 11 * PAGE_SIZE) as *const usize).read(), 0);
+        }
+        assert_eq!(vmo.committed_bytes(), PAGE_SIZE);
+        let mut buf = [0u8; core::mem::size_of::<usize>()];
+        vmo.read(11 * PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(usize::from_ne_bytes(buf), 0);
+    }
+
+    #[test]
+    fn info() {
+        let root = VmAddressRegion::new_root();
+        let info = root.get_info();
+        assert_eq!(info.base, root.addr());
+        assert_eq!(info.len, root.size);
+        assert_eq!(root.get_info_flags(), VmarInfoFlags::ROOT);
+        // the same layout as `zx_info_vmar_t`
+        assert_eq!(core::mem::size_of::<VmarInfo>(), 16);
+
+        let child = root
+            .allocate_at(0x1000, 0x2000, VmarFlags::CAN_MAP_RXW, PAGE_SIZE)
+            .unwrap();
+        let info = child.get_info();
+        assert_eq!(info.base, root.addr() + 0x1000);
+        assert_eq!(info.len, 0x2000);
+        assert!(child.get_info_flags().is_empty());
+    }
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn demand_paging() {
+        let vmar = VmAddressRegion::new_root();
+        let vmo = VmObject::new_paged(1024);
+        let flags = MMUFlags::READ | MMUFlags::WRITE;
+        let addr = vmar
+            .map_ext(None, vmo.clone(), 0, 1024 * PAGE_SIZE, flags, false, false)
+            .unwrap();
+        assert_eq!(vmo.committed_bytes(), 0);
+
+        vmar.handle_page_fault(addr + 0x10, MMUFlags::WRITE)
+            .unwrap();
+        vmar.handle_page_fault(addr + 100 * PAGE_SIZE, MMUFlags::WRITE)
+            .unwrap();
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        // reading an uncommitted page maps the zero page
+        vmar.handle_page_fault(addr + 200 * PAGE_SIZE, MMUFlags::READ)
+            .unwrap();
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+
+        unsafe {
+            ((addr + 100 * PAGE_SIZE) as *mut usize).write(MAGIC);
+        }
+        let mut buf = [0u8; core::mem::size_of::<usize>()];
+        vmo.read(100 * PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(usize::from_ne_bytes(buf), MAGIC);
+
+        // faults of the program
+        assert_eq!(
+            vmar.handle_page_fault(addr + 1024 * PAGE_SIZE, MMUFlags::READ),
+            Err(ZxError::NOT_FOUND)
+        );
+        assert_eq!(
+            vmar.handle_page_fault(addr, MMUFlags::EXECUTE),
+            Err(ZxError::ACCESS_DENIED)
+        );
+    }
+
+    #[test]
+    fn check_access() {
+        let vmar = VmAddressRegion::new_root();
+        let rw = MMUFlags::READ | MMUFlags::WRITE;
+        let addr = vmar
+            .map_at(0, VmObject::new_paged(1), 0, PAGE_SIZE, rw)
+            .unwrap();
+        vmar.map_at(
+            PAGE_SIZE,
+            VmObject::new_paged(1),
+            0,
+            PAGE_SIZE,
+            MMUFlags::READ,
+        )
+        .unwrap();
+
+        // across both mappings, not committed yet
+        assert!(vmar.check_access(addr, 2 * PAGE_SIZE, MMUFlags::READ));
+        assert!(vmar.check_access(addr + 8, PAGE_SIZE - 8, MMUFlags::WRITE));
+        assert!(vmar.check_access(addr, 0, MMUFlags::WRITE));
+        // read-only
+        assert!(!vmar.check_access(addr, PAGE_SIZE + 1, MMUFlags::WRITE));
+        // unmapped
+        assert!(!vmar.check_access(addr, 2 * PAGE_SIZE + 1, MMUFlags::READ));
+        assert!(!vmar.check_access(addr + 4 * PAGE_SIZE, 1, MMUFlags::READ));
+        assert!(!vmar.check_access(usize::max_value(), 2, MMUFlags::READ));
+    }
+
+    #[test]
+    fn task_stats() {
+        let root_vmar = VmAddressRegion::new_root();
+        let vmo = VmObject::new_paged(2);
+        vmo.write(0, &[1u8; 2 * PAGE_SIZE]).unwrap();
+        root_vmar
+            .map(None, vmo.clone(), 0, 2 * PAGE_SIZE, MMUFlags::READ)
+            .unwrap();
+        let stats = root_vmar.get_task_stats();
+        assert_eq!(stats.mapped_bytes, 2 * PAGE_SIZE as u64);
+        assert_eq!(stats.private_bytes, 2 * PAGE_SIZE as u64);
+        assert_eq!(stats.shared_bytes, 0);
+
+        // pages become shared with the COW clone
+        let _child = vmo.create_child(false, 0, 2 * PAGE_SIZE);
+        let stats = root_vmar.get_task_stats();
+        assert_eq!(stats.private_bytes, 0);
+        assert_eq!(stats.shared_bytes, 2 * PAGE_SIZE as u64);
+        assert_eq!(stats.scaled_shared_bytes, PAGE_SIZE as u64);
+    }
+
+    #[test]
+    fn get_maps() {
+        let s = Sample::new();
+        let vmo = VmObject::new_paged(1);
+        let flags = MMUFlags::READ | MMUFlags::WRITE;
+        s.grandson1
+            .map_at(0, vmo.clone(), 0, 0x1000, flags)
+            .unwrap();
+        s.child2.map_at(0, vmo.clone(), 0, 0x1000, flags).unwrap();
+
+        let maps = s.root.get_maps();
+        let expected = [
+            (ZxInfoMapsType::Aspace, s.root.addr(), 0),
+            (ZxInfoMapsType::Vmar, s.root.addr(), 1),
+            (ZxInfoMapsType::Vmar, s.child1.addr(), 2),
+            (ZxInfoMapsType::Vmar, s.grandson1.addr(), 3),
+            (ZxInfoMapsType::Mapping, s.grandson1.addr(), 4),
+            (ZxInfoMapsType::Vmar, s.grandson2.addr(), 3),
+            (ZxInfoMapsType::Vmar, s.child2.addr(), 2),
+            (ZxInfoMapsType::Mapping, s.child2.addr(), 3),
+        ];
+        assert_eq!(maps.len(), expected.len());
+        for (info, &(type_, base, depth)) in maps.iter().zip(expected.iter()) {
+            assert_eq!(info.type_, type_);
+            assert_eq!(info.base, base);
+            assert_eq!(info.depth, depth);
+        }
+        assert_eq!(maps[4].mapping.vmo_koid, vmo.id());
+        assert_eq!(maps[4].mapping.mmu_flags, 3);
+        assert_eq!(maps[4].mapping.committed_pages, 1);
+    }
+
+    #[test]
+    fn get_maps_with_depth() {
+        let root = VmAddressRegion::new_root();
+        let mut vmars = vec![root.clone()];
+        for _ in 0..4 {
+            let child = vmars
+                .last()
+                .unwrap()
+                .allocate_at(0, 0x1000, VmarFlags::CAN_MAP_RXW, PAGE_SIZE)
+                .unwrap();
+            vmars.push(child);
+        }
+        let vmo = VmObject::new_paged(1);
+        vmars[4].map_at(0, vmo, 0, 0x1000, MMUFlags::READ).unwrap();
+
+        let maps = vmars[1].get_maps_with_depth(usize::MAX);
+        assert_eq!(maps.len(), 5);
+        assert_eq!(maps[4].type_, ZxInfoMapsType::Mapping);
+        assert_eq!(maps[4].depth, 4);
+
+        // the walk stops at depth 2
+        let maps = vmars[1].get_maps_with_depth(2);
+        assert_eq!(maps.len(), 3);
+        for (depth, info) in maps.iter().enumerate() {
+            assert_eq!(info.type_, ZxInfoMapsType::Vmar);
+            assert_eq!(info.base, vmars[depth + 1].addr());
+            assert_eq!(info.depth, depth);
+        }
+    }
+
+    #[test]
+    fn map_with_guard() {
+        let root = VmAddressRegion::new_root();
+        let vmo = VmObject::new_paged(2);
+        let flags = MMUFlags::READ | MMUFlags::WRITE;
+        let addr = root
+            .map_with_guard(
+                Some(0),
+                vmo.clone(),
+                0,
+                2 * PAGE_SIZE,
+                flags,
+                PAGE_SIZE,
+                true,
+            )
+            .unwrap();
+        assert_eq!(addr, root.addr() + PAGE_SIZE);
+        assert_eq!(root.handle_page_fault(addr, MMUFlags::WRITE), Ok(()));
+
+        // the guard faults, takes no frame, and nothing else can be mapped there
+        let guard = addr - PAGE_SIZE;
+        assert_eq!(
+            root.handle_page_fault(guard, MMUFlags::WRITE),
+            Err(ZxError::NOT_FOUND)
+        );
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        assert_eq!(
+            root.map_at(0, VmObject::new_paged(1), 0, PAGE_SIZE, flags),
+            Err(ZxError::INVALID_ARGS)
+        );
+
+        // a failed mapping leaves no region behind
+        assert_eq!(
+            root.map_with_guard(None, vmo.clone(), 0, 3 * PAGE_SIZE, flags, PAGE_SIZE, true),
+            Err(ZxError::INVALID_ARGS)
+        );
+        assert_eq!(root.inner.lock().as_ref().unwrap().children.len(), 1);
+    }
+
+    #[test]
+    fn map_specific() {
+        let root = VmAddressRegion::new_root();
+        let vmo = VmObject::new_paged(2);
+        let vmo1 = VmObject::new_paged(1);
+        vmo1.write(0, &[1]).unwrap();
+        let flags = MMUFlags::READ | MMUFlags::WRITE;
+
+        // at a fixed offset, failing if occupied
+        let addr = root.map_at(PAGE_SIZE, vmo.clone(), 0, 2 * PAGE_SIZE, flags);
+        assert_eq!(addr, Ok(root.addr() + PAGE_SIZE));
+        assert_eq!(
+            root.map_at(2 * PAGE_SIZE, vmo1.clone(), 0, PAGE_SIZE, flags),
+            Err(ZxError::INVALID_ARGS)
+        );
+        assert_eq!(
+            root.map_at(root.size, vmo1.clone(), 0, PAGE_SIZE, flags),
+            Err(ZxError::INVALID_ARGS)
+        );
+
+        // overwriting replaces part of the old mapping
+        let addr = root.map_at_ext(2 * PAGE_SIZE, vmo1.clone(), 0, PAGE_SIZE, flags, true, true);
+        assert_eq!(addr, Ok(root.addr() + 2 * PAGE_SIZE));
+        let mappings = root.inner.lock().as_ref().unwrap().mappings.clone();
+        assert_eq!(mappings.len(), 2);
+        assert!(mappings
+            .iter()
+            .any(|map| map.addr() == root.addr() + PAGE_SIZE && map.size() == PAGE_SIZE));
+
+        // but not out of the VMAR or over a sub-region
+        assert_eq!(
+            root.map_at_ext(root.size, vmo1.clone(), 0, PAGE_SIZE, flags, true, true),
+            Err(ZxError::INVALID_ARGS)
+        );
+        root.allocate_at(4 * PAGE_SIZE, PAGE_SIZE, VmarFlags::CAN_MAP_RXW, PAGE_SIZE)
+            .unwrap();
+        assert_eq!(
+            root.map_at_ext(4 * PAGE_SIZE, vmo1, 0, PAGE_SIZE, flags, true, true),
+            Err(ZxError::INVALID_ARGS)
+        );
+    }
+
     #[test]
     fn destroy() {
         let s = Sample::new();
@@ -8,6 +8,7 @@ use {
     },
     bitflags::bitflags,
     core::ops::Deref,
+    core::sync::atomic::{AtomicUsize, Ordering},
     kernel_hal::{CachePolicy, PageTable},
     spin::Mutex,
 };
@@ -22,6 +23,9 @@ pub fn vmo_page_bytes() -> usize {
     (VMO_PAGE_ALLOC.get() - VMO_PAGE_DEALLOC.get()) * PAGE_SIZE
 }
 
+/// A hook called after pages of a VMO may have been committed or decommitted.
+pub type CommitHook = Arc<dyn Fn() + Send + Sync>;
+
 /// Virtual Memory Object Trait
 #[allow(clippy::len_without_is_empty)]
 pub trait VMObjectTrait: Sync + Send {
@@ -31,11 +35,29 @@ pub trait VMObjectTrait: Sync + Send {
     /// Write memory from `buf` to VMO at `offset`.
     fn write(&self, offset: usize, buf: &[u8]) -> ZxResult;
 
+    /// Read several `(offset, buffer)` ranges, atomically if the VMO has a lock.
+    fn read_vector(&self, segments: &mut [(usize, &mut [u8])]) -> ZxResult {
+        for (offset, buf) in segments.iter_mut() {
+            self.read(*offset, buf)?;
+        }
+        Ok(())
+    }
+
+    /// Write several `(offset, buffer)` ranges, atomically if the VMO has a lock.
+    fn write_vector(&self, segments: &[(usize, &[u8])]) -> ZxResult {
+        for (offset, buf) in segments.iter() {
+            self.write(*offset, buf)?;
+        }
+        Ok(())
+    }
+
     /// Get the length of VMO.
     fn len(&self) -> usize;
 
     /// Set the length of VMO.
-    fn set_len(&self, len: usize);
+    ///
+    /// Pages beyond the new length are freed, and read as zero if it grows again.
+    fn set_len(&self, len: usize) -> ZxResult;
 
     /// Unmap physical memory from `page_table`.
     fn unmap_from(&self, page_table: &mut PageTable, vaddr: VirtAddr, _offset: usize, len: usize) {
@@ -53,8 +75,18 @@ pub trait VMObjectTrait: Sync + Send {
     fn commit(&self, offset: usize, len: usize) -> ZxResult;
 
     /// Decommit allocated physical memory.
+    ///
+    /// Pinned pages are kept committed.
     fn decommit(&self, offset: usize, len: usize) -> ZxResult;
 
+    /// Commit and pin pages, so they are not decommitted or moved.
+    ///
+    /// Return the physical address of each page.
+    fn pin(&self, offset: usize, len: usize) -> ZxResult<Vec<PhysAddr>>;
+
+    /// Unpin pages pinned by `pin`.
+    fn unpin(&self, offset: usize, len: usize);
+
     /// Create a child VMO.
     fn create_child(&self, offset: usize, len: usize, user_id: KoID) -> Arc<dyn VMObjectTrait>;
 
@@ -71,6 +103,28 @@ pub trait VMObjectTrait: Sync + Send {
     fn share_count(&self) -> usize;
 
     fn committed_pages_in_range(&self, start_idx: usize, end_idx: usize) -> usize;
+
+    /// Count committed pages in range which are still shared with a COW clone.
+    fn cow_shared_pages_in_range(&self, start_idx: usize, end_idx: usize) -> usize;
+
+    /// Call `f` with whether each page in `[start_idx, end_idx)` can be mapped
+    /// writable, with the VMO locked meanwhile.
+    ///
+    /// Pages shared with a COW clone, or not committed and read as the zero
+    /// frame, must be mapped read-only.
+    fn with_writable_pages(&self, start_idx: usize, end_idx: usize, f: &mut dyn FnMut(&[bool])) {
+        f(&alloc::vec![true; end_idx - start_idx]);
+    }
+
+    /// Whether the VMO is paged, which supports copy-on-write children.
+    fn is_paged(&self) -> bool {
+        false
+    }
+
+    /// Set or remove the hook called when the committed pages may change.
+    ///
+    /// VMOs whose pages are always committed ignore it.
+    fn set_commit_hook(&self, _hook: Option<CommitHook>) {}
 }
 
 pub struct VmObject {
@@ -79,6 +133,11 @@ pub struct VmObject {
     children: Mutex<Vec<Weak<VmObject>>>,
     _counter: CountHelper,
     resizable: bool,
+    /// The logical size of the content, independent of the VMO size.
+    content_size: AtomicUsize,
+    /// Assert `VMO_COMMIT_THRESHOLD` when at least this many bytes are
+    /// committed. Zero means disabled.
+    commit_threshold: AtomicUsize,
     inner: Arc<dyn VMObjectTrait>,
 }
 
@@ -98,6 +157,8 @@ impl VmObject {
             children: Mutex::new(Vec::new()),
             resizable,
             _counter: CountHelper::new(),
+            content_size: AtomicUsize::new(pages * PAGE_SIZE),
+            commit_threshold: AtomicUsize::new(0),
             inner: VMObjectPaged::new(base.id, pages),
             base,
         })
@@ -116,6 +177,8 @@ impl VmObject {
             children: Mutex::new(Vec::new()),
             resizable: true,
             _counter: CountHelper::new(),
+            content_size: AtomicUsize::new(pages * PAGE_SIZE),
+            commit_threshold: AtomicUsize::new(0),
             inner: VMObjectPhysical::new(paddr, pages),
         })
     }
@@ -129,6 +192,8 @@ impl VmObject {
             children: Mutex::new(Vec::new()),
             resizable,
             _counter: CountHelper::new(),
+            content_size: AtomicUsize::new(len),
+            commit_threshold: AtomicUsize::new(0),
             inner: self.inner.create_child(offset, len, base.id),
             base,
         });
@@ -144,13 +209,75 @@ impl VmObject {
             return Err(ZxError::OUT_OF_RANGE);
         }
         if self.resizable {
-            self.inner.set_len(size);
+            self.inner.set_len(size)?;
+            self.content_size.store(len, Ordering::SeqCst);
             Ok(())
         } else {
             Err(ZxError::UNAVAILABLE)
         }
     }
 
+    /// Get the size of the content stored in this VMO in bytes.
+    pub fn content_size(&self) -> usize {
+        self.content_size.load(Ordering::SeqCst)
+    }
+
+    /// Set the size of the content stored in this VMO in bytes.
+    ///
+    /// This doesn't change the size of the VMO or its pages, so `size` can not
+    /// be larger than the VMO.
+    pub fn set_content_size(&self, size: usize) -> ZxResult {
+        if size > self.len() {
+            return Err(ZxError::OUT_OF_RANGE);
+        }
+        self.content_size.store(size, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Get the size of committed pages in bytes.
+    ///
+    /// It's the same as `committed_bytes` in `get_info`, without filling the rest.
+    pub fn committed_bytes(&self) -> usize {
+        let pages = self.inner.len() / PAGE_SIZE;
+        if pages == 0 {
+            return 0;
+        }
+        self.inner.committed_pages_in_range(0, pages) * PAGE_SIZE
+    }
+
+    /// Get the committed bytes threshold.
+    pub fn commit_threshold(&self) -> usize {
+        self.commit_threshold.load(Ordering::SeqCst)
+    }
+
+    /// Set the committed bytes threshold. Zero disables it.
+    ///
+    /// `VMO_COMMIT_THRESHOLD` is asserted while at least `threshold` bytes are committed.
+    pub fn set_commit_threshold(self: &Arc<Self>, threshold: usize) {
+        self.commit_threshold.store(threshold, Ordering::SeqCst);
+        let hook: Option<CommitHook> = if threshold == 0 {
+            None
+        } else {
+            let vmo = Arc::downgrade(self);
+            Some(Arc::new(move || {
+                if let Some(vmo) = vmo.upgrade() {
+                    vmo.update_commit_signal();
+                }
+            }))
+        };
+        self.inner.set_commit_hook(hook);
+        self.update_commit_signal();
+    }
+
+    fn update_commit_signal(&self) {
+        let threshold = self.commit_threshold.load(Ordering::SeqCst);
+        if threshold != 0 && self.committed_bytes() >= threshold {
+            self.base.signal_set(Signal::VMO_COMMIT_THRESHOLD);
+        } else {
+            self.base.signal_clear(Signal::VMO_COMMIT_THRESHOLD);
+        }
+    }
+
     /// Get information of this VMO.
     pub fn get_info(&self) -> ZxInfoVmo {
         let mut ret = ZxInfoVmo {
@@ -169,9 +296,16 @@ impl VmObject {
             } else {
                 VmoInfoFlags::empty()
             },
+            cache_policy: self.inner.get_cache_policy() as u32,
             ..Default::default()
         };
         self.inner.complete_info(&mut ret);
+        ret.num_children = self
+            .children
+            .lock()
+            .iter()
+            .filter(|child| child.strong_count() != 0)
+            .count() as u64;
         ret
     }
 
@@ -179,6 +313,35 @@ impl VmObject {
         self.inner.set_cache_policy(policy)
     }
 
+    /// Read several `(offset, buffer)` ranges of this VMO.
+    ///
+    /// All ranges are checked before any is read, so on error no buffer is filled.
+    /// The ranges of a paged VMO are read under one lock, so no write is seen halfway.
+    pub fn read_vector(&self, segments: &mut [(usize, &mut [u8])]) -> ZxResult {
+        self.check_ranges(segments.iter().map(|(offset, buf)| (*offset, buf.len())))?;
+        self.inner.read_vector(segments)
+    }
+
+    /// Write several `(offset, buffer)` ranges of this VMO.
+    ///
+    /// All ranges are checked before any is written, so on error the VMO is unchanged.
+    /// The ranges of a paged VMO are written under one lock, so no read sees them halfway.
+    pub fn write_vector(&self, segments: &[(usize, &[u8])]) -> ZxResult {
+        self.check_ranges(segments.iter().map(|(offset, buf)| (*offset, buf.len())))?;
+        self.inner.write_vector(segments)
+    }
+
+    fn check_ranges(&self, ranges: impl Iterator<Item = (usize, usize)>) -> ZxResult {
+        let size = self.inner.len();
+        for (offset, len) in ranges {
+            // in case integer addition overflows
+            if offset > size || len > size - offset {
+                return Err(ZxError::OUT_OF_RANGE);
+            }
+        }
+        Ok(())
+    }
+
     pub fn is_resizable(&self) -> bool {
         self.resizable
     }
@@ -269,4 +432,60 @@ mod tests {
         vmo.read(0, &mut buf).unwrap();
         assert_eq!(&buf, &[0, 1, 2, 3]);
     }
+
+    #[test]
+    fn commit_threshold() {
+        let vmo = VmObject::new_paged(4);
+        vmo.set_commit_threshold(2 * PAGE_SIZE);
+        assert!(!vmo.signal().contains(Signal::VMO_COMMIT_THRESHOLD));
+
+        vmo.commit(0, PAGE_SIZE).unwrap();
+        assert!(!vmo.signal().contains(Signal::VMO_COMMIT_THRESHOLD));
+        vmo.write(PAGE_SIZE, &[1]).unwrap();
+        assert!(vmo.signal().contains(Signal::VMO_COMMIT_THRESHOLD));
+
+        vmo.decommit(0, PAGE_SIZE).unwrap();
+        assert!(!vmo.signal().contains(Signal::VMO_COMMIT_THRESHOLD));
+
+        // disabling clears the signal and stops the notifications
+        vmo.commit(0, PAGE_SIZE).unwrap();
+        assert!(vmo.signal().contains(Signal::VMO_COMMIT_THRESHOLD));
+        vmo.set_commit_threshold(0);
+        assert!(!vmo.signal().contains(Signal::VMO_COMMIT_THRESHOLD));
+        vmo.commit(2 * PAGE_SIZE, PAGE_SIZE).unwrap();
+        assert!(!vmo.signal().contains(Signal::VMO_COMMIT_THRESHOLD));
+    }
+
+    #[test]
+    fn vector() {
+        let vmo = VmObject::new_paged(2);
+        vmo.write_vector(&[(0, &[1, 2]), (PAGE_SIZE - 1, &[3, 4]), (10, &[5])])
+            .unwrap();
+
+        let mut a = [0u8; 3];
+        let mut b = [0u8; 2];
+        vmo.read_vector(&mut [(0, &mut a), (PAGE_SIZE - 1, &mut b)])
+            .unwrap();
+        assert_eq!(a, [1, 2, 0]);
+        assert_eq!(b, [3, 4]);
+
+        // an out-of-range segment fails the whole call
+        assert_eq!(
+            vmo.write_vector(&[(0, &[9]), (2 * PAGE_SIZE - 1, &[9, 9])]),
+            Err(ZxError::OUT_OF_RANGE)
+        );
+        assert_eq!(
+            vmo.write_vector(&[(0, &[9]), (usize::MAX, &[9])]),
+            Err(ZxError::OUT_OF_RANGE)
+        );
+        let mut a = [0u8; 1];
+        let mut b = [0xffu8; 1];
+        assert_eq!(
+            vmo.read_vector(&mut [(0, &mut a), (2 * PAGE_SIZE, &mut b)]),
+            Err(ZxError::OUT_OF_RANGE)
+        );
+        assert_eq!(b, [0xff]);
+        vmo.read(0, &mut a).unwrap();
+        assert_eq!(a, [1]);
+    }
 }
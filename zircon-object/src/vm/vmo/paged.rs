@@ -54,8 +54,14 @@ impl VMOType {
 }
 
 /// The main VM object type, holding a list of pages.
+///
+/// Pages are committed lazily. Until a page is written, reading it gets
+/// the shared zero frame, which is never written and is not counted as
+/// committed.
 pub struct VMObjectPaged {
     inner: Mutex<VMObjectPagedInner>,
+    /// Called without the inner lock after committing or decommitting pages.
+    commit_hook: Mutex<Option<CommitHook>>,
 }
 
 type WeakRef = Weak<VMObjectPaged>;
@@ -87,6 +93,8 @@ struct VMObjectPagedInner {
 struct PageState {
     frame: PhysFrame,
     tag: PageStateTag,
+    /// The number of times the page is pinned. Pinned pages are never decommitted.
+    pin_count: usize,
 }
 
 /// The owner tag of pages in the node.
@@ -120,6 +128,7 @@ impl PageState {
         PageState {
             frame,
             tag: PageStateTag::Owned,
+            pin_count: 0,
         }
     }
     #[allow(unsafe_code)]
@@ -158,64 +167,61 @@ impl VMObjectPaged {
     fn wrap(inner: VMObjectPagedInner) -> Arc<Self> {
         let obj = Arc::new(VMObjectPaged {
             inner: Mutex::new(inner),
+            commit_hook: Mutex::new(None),
         });
         obj.inner.lock().self_ref = Arc::downgrade(&obj);
         obj
     }
 
-    /// Helper function to split range into sub-ranges within pages.
-    ///
-    /// All covered pages will be committed implicitly.
-    ///
-    /// ```text
-    /// VMO range:
-    /// |----|----|----|----|----|
-    ///
-    /// buf:
-    ///            [====len====]
-    /// |--offset--|
-    ///
-    /// sub-ranges:
-    ///            [===]
-    ///                [====]
-    ///                     [==]
-    /// ```
-    ///
-    /// `f` is a function to process in-page ranges.
-    /// It takes 2 arguments:
-    /// * `paddr`: the start physical address of the in-page range.
-    /// * `buf_range`: the range in view of the input buffer.
-    fn for_each_page(
-        &self,
-        offset: usize,
-        buf_len: usize,
-        flags: MMUFlags,
-        mut f: impl FnMut(PhysAddr, Range<usize>),
-    ) -> ZxResult {
-        let iter = BlockIter {
-            begin: offset,
-            end: offset + buf_len,
-            block_size_log2: 12,
-        };
-        for block in iter {
-            let paddr = self.commit_page(block.block, flags)?;
-            let buf_range = block.origin_begin() - offset..block.origin_end() - offset;
-            f(paddr + block.begin, buf_range);
+    /// Internal: Call the commit hook if there is one.
+    fn notify_commit(&self) {
+        let hook = self.commit_hook.lock().clone();
+        if let Some(hook) = hook {
+            hook();
         }
-        Ok(())
+    }
+
+    /// Internal: Run `f` with the inner locked, then call the commit hook
+    /// if it has changed the number of frames, i.e. committed or decommitted pages.
+    fn update_inner<T>(&self, f: impl FnOnce(&mut VMObjectPagedInner) -> T) -> T {
+        let mut inner = self.inner.lock();
+        let frames = inner.frames.len();
+        let ret = f(&mut inner);
+        let changed = inner.frames.len() != frames;
+        drop(inner);
+        if changed {
+            self.notify_commit();
+        }
+        ret
     }
 }
 
 impl VMObjectTrait for VMObjectPaged {
     fn read(&self, offset: usize, buf: &mut [u8]) -> ZxResult {
-        self.for_each_page(offset, buf.len(), MMUFlags::READ, |paddr, buf_range| {
-            kernel_hal::pmem_read(paddr, &mut buf[buf_range]);
-        })
+        self.read_vector(&mut [(offset, buf)])
     }
 
     fn write(&self, offset: usize, buf: &[u8]) -> ZxResult {
-        self.for_each_page(offset, buf.len(), MMUFlags::WRITE, |paddr, buf_range| {
-            kernel_hal::pmem_write(paddr, &buf[buf_range]);
+        self.write_vector(&[(offset, buf)])
+    }
+
+    fn read_vector(&self, segments: &mut [(usize, &mut [u8])]) -> ZxResult {
+        self.update_inner(|inner| {
+            segments.iter_mut().try_for_each(|(offset, buf)| {
+                inner.for_each_page(*offset, buf.len(), MMUFlags::READ, |paddr, buf_range| {
+                    kernel_hal::pmem_read(paddr, &mut buf[buf_range]);
+                })
+            })
+        })
+    }
+
+    fn write_vector(&self, segments: &[(usize, &[u8])]) -> ZxResult {
+        self.update_inner(|inner| {
+            segments.iter().try_for_each(|(offset, buf)| {
+                inner.for_each_page(*offset, buf.len(), MMUFlags::WRITE, |paddr, buf_range| {
+                    kernel_hal::pmem_write(paddr, &buf[buf_range]);
+                })
+            })
         })
     }
 
@@ -223,40 +229,111 @@ impl VMObjectTrait for VMObjectPaged {
         self.inner.lock().size
     }
 
-    fn set_len(&self, len: usize) {
+    fn set_len(&self, len: usize) -> ZxResult {
         assert!(page_aligned(len));
-        let mut inner = self.inner.lock();
-        inner.resize(len);
+        self.update_inner(|inner| {
+            if len < inner.size {
+                let (start, end) = (len / PAGE_SIZE, inner.size / PAGE_SIZE);
+                if inner
+                    .frames
+                    .range(start..end)
+                    .any(|(_, page)| page.pin_count != 0)
+                {
+                    return Err(ZxError::BAD_STATE);
+                }
+                // unmap first, so that no mapping refers to the freed frames
+                for map in inner.mappings.iter().filter_map(|map| map.upgrade()) {
+                    map.range_change(start, end - start, RangeChangeOp::Unmap);
+                }
+            }
+            inner.resize(len);
+            Ok(())
+        })
     }
 
     fn commit_page(&self, page_idx: usize, flags: MMUFlags) -> ZxResult<PhysAddr> {
-        match self.commit_page_internal(page_idx, flags, &Weak::new())? {
-            CommitResult::Ref(paddr) => Ok(paddr),
-            _ => unreachable!(),
-        }
+        let paddr =
+            match self.update_inner(|inner| inner.commit_page(page_idx, flags, &Weak::new()))? {
+                CommitResult::Ref(paddr) => paddr,
+                _ => unreachable!(),
+            };
+        debug_assert!(
+            !flags.contains(MMUFlags::WRITE) || paddr != PhysFrame::zero_frame_addr(),
+            "the zero frame must not be written"
+        );
+        Ok(paddr)
     }
 
     fn commit(&self, offset: usize, len: usize) -> ZxResult {
         let start_page = offset / PAGE_SIZE;
         let pages = len / PAGE_SIZE;
-        for i in 0..pages {
-            self.commit_page(start_page + i, MMUFlags::WRITE)?;
-        }
-        Ok(())
+        self.update_inner(|inner| {
+            inner.commit_range(start_page, pages, |inner, i| {
+                inner.commit_page(i, MMUFlags::WRITE, &Weak::new())?;
+                Ok(())
+            })
+        })
     }
 
     fn decommit(&self, offset: usize, len: usize) -> ZxResult {
-        let mut inner = self.inner.lock();
-        // non-slice child VMOs do not support decommit.
-        if inner.parent.is_some() {
-            return Err(ZxError::NOT_SUPPORTED);
+        self.update_inner(|inner| {
+            // non-slice child VMOs do not support decommit.
+            if inner.parent.is_some() {
+                return Err(ZxError::NOT_SUPPORTED);
+            }
+            let start_page = offset / PAGE_SIZE;
+            let pages = len / PAGE_SIZE;
+            // pinned pages are skipped
+            let unpinned: Vec<usize> = (start_page..start_page + pages)
+                .filter(|i| {
+                    inner
+                        .frames
+                        .get(i)
+                        .map_or(false, |page| page.pin_count == 0)
+                })
+                .collect();
+            // unmap first, so that no mapping refers to the freed frames
+            for map in inner.mappings.iter().filter_map(|map| map.upgrade()) {
+                for &i in unpinned.iter() {
+                    map.range_change(i, 1, RangeChangeOp::Unmap);
+                }
+            }
+            for i in unpinned {
+                inner.decommit(i);
+            }
+            Ok(())
+        })
+    }
+
+    fn pin(&self, offset: usize, len: usize) -> ZxResult<Vec<PhysAddr>> {
+        let start_page = offset / PAGE_SIZE;
+        let pages = len / PAGE_SIZE;
+        let mut paddrs = Vec::with_capacity(pages);
+        for i in start_page..start_page + pages {
+            let pinned = self
+                .commit_page(i, MMUFlags::WRITE)
+                .and_then(|paddr| self.inner.lock().pin(i, paddr));
+            match pinned {
+                Ok(paddr) => paddrs.push(paddr),
+                Err(err) => {
+                    self.unpin(offset, paddrs.len() * PAGE_SIZE);
+                    return Err(err);
+                }
+            }
         }
+        Ok(paddrs)
+    }
+
+    fn unpin(&self, offset: usize, len: usize) {
         let start_page = offset / PAGE_SIZE;
         let pages = len / PAGE_SIZE;
-        for i in 0..pages {
-            inner.decommit(start_page + i);
+        let mut inner = self.inner.lock();
+        for i in start_page..start_page + pages {
+            if let Some(page) = inner.frames.get_mut(&i) {
+                assert_ne!(page.pin_count, 0, "unpin a page not pinned");
+                page.pin_count -= 1;
+            }
         }
-        Ok(())
     }
 
     fn create_child(&self, offset: usize, len: usize, user_id: KoID) -> Arc<dyn VMObjectTrait> {
@@ -323,6 +400,27 @@ impl VMObjectTrait for VMObjectPaged {
             .committed_pages_in_range(start_idx, end_idx)
     }
 
+    fn cow_shared_pages_in_range(&self, start_idx: usize, end_idx: usize) -> usize {
+        self.inner.lock().count_pages_in_range(start_idx, end_idx).1
+    }
+
+    fn with_writable_pages(&self, start_idx: usize, end_idx: usize, f: &mut dyn FnMut(&[bool])) {
+        let inner = self.inner.lock();
+        // pages of a leaf are owned by itself, shared ones are in hidden parents
+        let writable: Vec<bool> = (start_idx..end_idx)
+            .map(|i| inner.frames.contains_key(&i))
+            .collect();
+        f(&writable);
+    }
+
+    fn is_paged(&self) -> bool {
+        true
+    }
+
+    fn set_commit_hook(&self, hook: Option<CommitHook>) {
+        *self.commit_hook.lock() = hook;
+    }
+
     fn share_count(&self) -> usize {
         self.inner.lock().mappings.len()
     }
@@ -345,78 +443,7 @@ impl VMObjectPaged {
         flags: MMUFlags,
         child: &WeakRef,
     ) -> ZxResult<CommitResult> {
-        let mut inner = self.inner.lock();
-        // special case
-        let no_parent = inner.parent.is_none();
-        let no_frame = !inner.frames.contains_key(&page_idx);
-        let out_of_range = if inner.type_.is_hidden() || inner.parent.is_none() {
-            page_idx >= inner.size / PAGE_SIZE
-        } else {
-            (inner.parent_offset + page_idx * PAGE_SIZE) >= inner.parent_limit
-        };
-        if no_frame {
-            // if out_of_range
-            if out_of_range || no_parent {
-                if !flags.contains(MMUFlags::WRITE) {
-                    // read-only, just return zero frame
-                    return Ok(CommitResult::Ref(PhysFrame::zero_frame_addr()));
-                }
-                // lazy allocate zero frame
-                let target_frame = PhysFrame::alloc().ok_or(ZxError::NO_MEMORY)?;
-                kernel_hal::frame_zero(target_frame.addr());
-                if out_of_range {
-                    // can never be a hidden vmo
-                    assert!(!inner.type_.is_hidden());
-                }
-                if inner.type_.is_hidden() {
-                    return Ok(CommitResult::NewPage(target_frame));
-                }
-                inner.frames.insert(page_idx, PageState::new(target_frame));
-            } else {
-                // recursively find a frame in parent
-                let parent = inner.parent.as_ref().unwrap();
-                let parent_idx = page_idx + inner.parent_offset / PAGE_SIZE;
-                match parent.commit_page_internal(parent_idx, flags, &inner.self_ref)? {
-                    CommitResult::NewPage(frame) if !inner.type_.is_hidden() => {
-                        inner.frames.insert(page_idx, PageState::new(frame));
-                    }
-                    CommitResult::CopyOnWrite(frame) => {
-                        inner.frames.insert(page_idx, PageState::new(frame));
-                    }
-                    r => return Ok(r),
-                }
-            }
-        }
-
-        // now the page must hit on this VMO
-        let (child_tag, other_child) = inner.type_.get_tag_and_other(child);
-        if inner.type_.is_hidden() {
-            let arc_other = other_child.upgrade().unwrap();
-            let locked_other = arc_other.inner.lock();
-            let in_range = {
-                let start = locked_other.parent_offset / PAGE_SIZE;
-                let end = locked_other.parent_limit / PAGE_SIZE;
-                page_idx >= start && page_idx < end
-            };
-            if !in_range {
-                let frame = inner.frames.remove(&page_idx).unwrap().take();
-                return Ok(CommitResult::CopyOnWrite(frame));
-            }
-        }
-        let frame = inner.frames.get_mut(&page_idx).unwrap();
-        if frame.tag.is_split() {
-            // has split, take out
-            let target_frame = inner.frames.remove(&page_idx).unwrap().take();
-            return Ok(CommitResult::CopyOnWrite(target_frame));
-        } else if flags.contains(MMUFlags::WRITE) && child_tag.is_split() {
-            // copy-on-write
-            let target_frame = PhysFrame::alloc().ok_or(ZxError::NO_MEMORY)?;
-            kernel_hal::frame_copy(frame.frame.addr(), target_frame.addr());
-            frame.tag = child_tag;
-            return Ok(CommitResult::CopyOnWrite(target_frame));
-        }
-        // otherwise already committed
-        Ok(CommitResult::Ref(frame.frame.addr()))
+        self.inner.lock().commit_page(page_idx, flags, child)
     }
 
     /// Replace a child of the hidden node.
@@ -505,10 +532,186 @@ impl VMObjectPaged {
 }
 
 impl VMObjectPagedInner {
+    /// Helper function to split range into sub-ranges within pages.
+    ///
+    /// All covered pages will be committed implicitly.
+    ///
+    /// ```text
+    /// VMO range:
+    /// |----|----|----|----|----|
+    ///
+    /// buf:
+    ///            [====len====]
+    /// |--offset--|
+    ///
+    /// sub-ranges:
+    ///            [===]
+    ///                [====]
+    ///                     [==]
+    /// ```
+    ///
+    /// `f` is a function to process in-page ranges.
+    /// It takes 2 arguments:
+    /// * `paddr`: the start physical address of the in-page range.
+    /// * `buf_range`: the range in view of the input buffer.
+    fn for_each_page(
+        &mut self,
+        offset: usize,
+        buf_len: usize,
+        flags: MMUFlags,
+        mut f: impl FnMut(PhysAddr, Range<usize>),
+    ) -> ZxResult {
+        let iter = BlockIter {
+            begin: offset,
+            end: offset + buf_len,
+            block_size_log2: 12,
+        };
+        for block in iter {
+            let paddr = match self.commit_page(block.block, flags, &Weak::new())? {
+                CommitResult::Ref(paddr) => paddr,
+                _ => unreachable!(),
+            };
+            let buf_range = block.origin_begin() - offset..block.origin_end() - offset;
+            f(paddr + block.begin, buf_range);
+        }
+        Ok(())
+    }
+
+    /// Commit the page at `page_idx` for an access of `flags`, see `commit_page_internal`.
+    fn commit_page(
+        &mut self,
+        page_idx: usize,
+        flags: MMUFlags,
+        child: &WeakRef,
+    ) -> ZxResult<CommitResult> {
+        // special case
+        let no_parent = self.parent.is_none();
+        let no_frame = !self.frames.contains_key(&page_idx);
+        let out_of_range = if self.type_.is_hidden() || self.parent.is_none() {
+            page_idx >= self.size / PAGE_SIZE
+        } else {
+            (self.parent_offset + page_idx * PAGE_SIZE) >= self.parent_limit
+        };
+        if no_frame {
+            // if out_of_range
+            if out_of_range || no_parent {
+                if !flags.contains(MMUFlags::WRITE) {
+                    // read-only, just return zero frame
+                    return Ok(CommitResult::Ref(PhysFrame::zero_frame_addr()));
+                }
+                // lazy allocate zero frame
+                let target_frame = PhysFrame::alloc().ok_or(ZxError::NO_MEMORY)?;
+                kernel_hal::frame_zero(target_frame.addr());
+                if out_of_range {
+                    // can never be a hidden vmo
+                    assert!(!self.type_.is_hidden());
+                }
+                if self.type_.is_hidden() {
+                    return Ok(CommitResult::NewPage(target_frame));
+                }
+                self.frames.insert(page_idx, PageState::new(target_frame));
+            } else {
+                // recursively find a frame in parent
+                let parent = self.parent.as_ref().unwrap();
+                let parent_idx = page_idx + self.parent_offset / PAGE_SIZE;
+                match parent.commit_page_internal(parent_idx, flags, &self.self_ref)? {
+                    CommitResult::NewPage(frame) if !self.type_.is_hidden() => {
+                        self.frames.insert(page_idx, PageState::new(frame));
+                    }
+                    CommitResult::CopyOnWrite(frame) => {
+                        self.frames.insert(page_idx, PageState::new(frame));
+                    }
+                    r => return Ok(r),
+                }
+            }
+        }
+
+        // now the page must hit on this VMO
+        let (child_tag, other_child) = self.type_.get_tag_and_other(child);
+        if self.type_.is_hidden() {
+            let arc_other = other_child.upgrade().unwrap();
+            let locked_other = arc_other.inner.lock();
+            let in_range = {
+                let start = locked_other.parent_offset / PAGE_SIZE;
+                let end = locked_other.parent_limit / PAGE_SIZE;
+                page_idx >= start && page_idx < end
+            };
+            if !in_range {
+                let frame = self.frames.remove(&page_idx).unwrap().take();
+                return Ok(CommitResult::CopyOnWrite(frame));
+            }
+        }
+        let frame = self.frames.get_mut(&page_idx).unwrap();
+        if frame.tag.is_split() {
+            // has split, take out
+            let target_frame = self.frames.remove(&page_idx).unwrap().take();
+            return Ok(CommitResult::CopyOnWrite(target_frame));
+        } else if flags.contains(MMUFlags::WRITE) && child_tag.is_split() {
+            // copy-on-write
+            let target_frame = PhysFrame::alloc().ok_or(ZxError::NO_MEMORY)?;
+            kernel_hal::frame_copy(frame.frame.addr(), target_frame.addr());
+            frame.tag = child_tag;
+            return Ok(CommitResult::CopyOnWrite(target_frame));
+        }
+        // otherwise already committed
+        Ok(CommitResult::Ref(frame.frame.addr()))
+    }
+
     fn decommit(&mut self, page_idx: usize) {
         self.frames.remove(&page_idx);
     }
 
+    /// Commit `pages` pages from `start_page` one by one with `commit_page`.
+    ///
+    /// If it fails, e.g. on NO_MEMORY, the pages committed by this call are
+    /// decommitted before returning the error.
+    fn commit_range(
+        &mut self,
+        start_page: usize,
+        pages: usize,
+        mut commit_page: impl FnMut(&mut Self, usize) -> ZxResult,
+    ) -> ZxResult {
+        let mut new_pages = Vec::new();
+        for i in start_page..start_page + pages {
+            let committed = self.frames.contains_key(&i);
+            if let Err(err) = commit_page(self, i) {
+                self.rollback_commit(&new_pages);
+                return Err(err);
+            }
+            if !committed {
+                new_pages.push(i);
+            }
+        }
+        Ok(())
+    }
+
+    /// Decommit the pages committed by a failed `commit`.
+    fn rollback_commit(&mut self, page_indices: &[usize]) {
+        let maps: Vec<_> = self
+            .mappings
+            .iter()
+            .filter_map(|map| map.upgrade())
+            .collect();
+        for &i in page_indices {
+            for map in maps.iter() {
+                map.range_change(i, 1, RangeChangeOp::Unmap);
+            }
+            self.decommit(i);
+        }
+    }
+
+    /// Pin the committed page, if it is still the frame at `paddr`.
+    fn pin(&mut self, page_idx: usize, paddr: PhysAddr) -> ZxResult<PhysAddr> {
+        match self.frames.get_mut(&page_idx) {
+            Some(page) if page.frame.addr() == paddr => {
+                page.pin_count += 1;
+                Ok(paddr)
+            }
+            // decommitted before we got the lock
+            _ => Err(ZxError::BAD_STATE),
+        }
+    }
+
     #[allow(dead_code)]
     fn range_change(&self, parent_offset: usize, parent_limit: usize, op: RangeChangeOp) {
         let mut start = self.parent_offset.max(parent_offset);
@@ -533,6 +736,19 @@ impl VMObjectPagedInner {
 
     /// Count committed pages of the VMO.
     fn committed_pages_in_range(&self, start_idx: usize, end_idx: usize) -> usize {
+        if self.parent.is_none() && start_idx == 0 && end_idx == self.size / PAGE_SIZE {
+            // without a parent, all pages are its own frames
+            return self.frames.len();
+        }
+        let (private, shared) = self.count_pages_in_range(start_idx, end_idx);
+        private + shared
+    }
+
+    /// Count committed pages of the VMO, returns `(private, shared)`.
+    ///
+    /// A page is shared if it is attributed to this VMO but still lives in
+    /// a hidden parent which is visible to the other child.
+    fn count_pages_in_range(&self, start_idx: usize, end_idx: usize) -> (usize, usize) {
         assert!(
             start_idx < self.size / PAGE_SIZE,
             "start_idx {:#x}, self.size {:#x}",
@@ -545,10 +761,11 @@ impl VMObjectPagedInner {
             end_idx,
             self.size
         );
-        let mut count = 0;
+        let mut private = 0;
+        let mut shared = 0;
         for i in start_idx..end_idx {
             if self.frames.contains_key(&i) {
-                count += 1;
+                private += 1;
                 continue;
             }
             if self.parent_limit <= i * PAGE_SIZE {
@@ -559,8 +776,12 @@ impl VMObjectPagedInner {
             while let Some(locked) = current {
                 let inner = locked.inner.lock();
                 if let Some(frame) = inner.frames.get(&current_idx) {
-                    if frame.tag.is_split() || inner.user_id == self.user_id {
-                        count += 1;
+                    if frame.tag.is_split() {
+                        private += 1;
+                        break;
+                    }
+                    if inner.user_id == self.user_id {
+                        shared += 1;
                         break;
                     }
                 }
@@ -571,7 +792,7 @@ impl VMObjectPagedInner {
                 current = inner.parent.clone();
             }
         }
-        count
+        (private, shared)
     }
 
     /// Remove one child and contract hidden node.
@@ -677,12 +898,12 @@ impl VMObjectPagedInner {
         if let VMOType::Snapshot = self.type_ {
             info.flags |= VmoInfoFlags::IS_COW_CLONE;
         }
-        info.num_children = if self.type_.is_hidden() { 2 } else { 0 };
         info.num_mappings = self.mappings.len() as u64; // FIXME remove weak ptr
         info.share_count = self.mappings.len() as u64; // FIXME share_count should be the count of unique aspace
-        info.committed_bytes =
-            (self.committed_pages_in_range(0, self.size / PAGE_SIZE) * PAGE_SIZE) as u64;
-        // TODO cache_policy should be set up.
+        if self.size != 0 {
+            info.committed_bytes =
+                (self.committed_pages_in_range(0, self.size / PAGE_SIZE) * PAGE_SIZE) as u64;
+        }
     }
 
     fn release_unwanted_pages(&mut self, mut unwanted: VecDeque<usize>) {
@@ -735,6 +956,8 @@ impl VMObjectPagedInner {
                 }
             }
             self.release_unwanted_pages(unwanted);
+            // parent pages beyond it must not show up if it grows again
+            self.parent_limit = self.parent_limit.min(self.parent_offset + new_size);
         }
         self.size = new_size;
     }
@@ -771,6 +994,38 @@ mod tests {
         super::super::tests::read_write(&*vmo);
     }
 
+    #[test]
+    fn commit_hook() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        let vmo = VMObjectPaged::new(0, 2);
+        let calls = Arc::new(AtomicUsize::new(0));
+        vmo.set_commit_hook(Some(Arc::new({
+            let calls = calls.clone();
+            move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+            }
+        })));
+        let calls = move || calls.load(Ordering::SeqCst);
+
+        // reading the zero frame commits nothing
+        vmo.read(0, &mut [0u8; 8]).unwrap();
+        assert_eq!(calls(), 0);
+        // only the first write to a page commits it
+        vmo.write(0, &[1u8; 8]).unwrap();
+        assert_eq!(calls(), 1);
+        vmo.write(8, &[2u8; 8]).unwrap();
+        vmo.commit_page(0, MMUFlags::WRITE).unwrap();
+        assert_eq!(calls(), 1);
+        vmo.commit(0, 2 * PAGE_SIZE).unwrap();
+        assert_eq!(calls(), 2);
+        assert_eq!(vmo.committed_pages_in_range(0, 2), 2);
+        vmo.decommit(0, 2 * PAGE_SIZE).unwrap();
+        assert_eq!(calls(), 3);
+        vmo.decommit(0, 2 * PAGE_SIZE).unwrap();
+        assert_eq!(calls(), 3);
+        assert_eq!(vmo.committed_pages_in_range(0, 2), 0);
+    }
+
     #[test]
     fn create_child() {
         let vmo = VmObject::new_paged(1);
@@ -787,6 +1042,55 @@ mod tests {
         assert_eq!(child_vmo.test_read(0), 2);
     }
 
+    #[test]
+    fn decommit() {
+        let vmo = VmObject::new_paged(4);
+        vmo.commit(0, 4 * PAGE_SIZE).unwrap();
+        vmo.test_write(1, 1);
+        assert_eq!(vmo.committed_bytes(), 4 * PAGE_SIZE);
+
+        let dealloc = VMO_PAGE_DEALLOC.get();
+        vmo.decommit(PAGE_SIZE, 2 * PAGE_SIZE).unwrap();
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        assert!(VMO_PAGE_DEALLOC.get() >= dealloc + 2);
+
+        // decommitted pages read as zero, and are committed again on write
+        assert_eq!(vmo.test_read(1), 0);
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        vmo.test_write(1, 2);
+        assert_eq!(vmo.test_read(1), 2);
+        assert_eq!(vmo.committed_bytes(), 3 * PAGE_SIZE);
+
+        // children don't support decommit
+        let child = vmo.create_child(false, 0, PAGE_SIZE);
+        assert_eq!(child.decommit(0, PAGE_SIZE), Err(ZxError::NOT_SUPPORTED));
+    }
+
+    #[test]
+    fn snapshot_committed_pages() {
+        let vmo = VmObject::new_paged(2);
+        vmo.test_write(0, 1);
+        vmo.test_write(1, 1);
+        let child = vmo.create_child(false, 0, 2 * PAGE_SIZE);
+
+        // shared pages are counted once, by the parent
+        assert_eq!(child.test_read(0), 1);
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        assert_eq!(child.committed_bytes(), 0);
+
+        // a write to the child copies the page
+        child.test_write(0, 2);
+        assert_eq!(vmo.test_read(0), 1);
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        assert_eq!(child.committed_bytes(), PAGE_SIZE);
+
+        // a write to the parent leaves the child with the old page
+        vmo.test_write(1, 3);
+        assert_eq!(child.test_read(1), 1);
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        assert_eq!(child.committed_bytes(), 2 * PAGE_SIZE);
+    }
+
     #[test]
     #[ignore] // FIXME
     fn zero_page_write() {
@@ -828,6 +1132,125 @@ mod tests {
         assert_eq!(vmo2.get_info().committed_bytes as usize, PAGE_SIZE);
     }
 
+    #[test]
+    fn info() {
+        let vmo = VmObject::new_paged(2);
+        let info = vmo.get_info();
+        assert_eq!(info.size as usize, 2 * PAGE_SIZE);
+        assert_eq!(info.committed_bytes, 0);
+        assert!(info.flags.contains(VmoInfoFlags::TYPE_PAGED));
+
+        vmo.test_write(1, 1);
+        assert_eq!(vmo.get_info().committed_bytes as usize, PAGE_SIZE);
+
+        let child = vmo.create_child(false, 0, PAGE_SIZE);
+        assert_eq!(vmo.get_info().num_children, 1);
+        assert_eq!(child.get_info().parent_koid, vmo.id());
+        drop(child);
+        assert_eq!(vmo.get_info().num_children, 0);
+
+        let empty = VmObject::new_paged(0);
+        assert_eq!(empty.get_info().committed_bytes, 0);
+    }
+
+    #[test]
+    fn content_size() {
+        let vmo = VmObject::new_paged(2);
+        assert_eq!(vmo.content_size(), 2 * PAGE_SIZE);
+        vmo.set_content_size(100).unwrap();
+        assert_eq!(vmo.content_size(), 100);
+        assert_eq!(vmo.len(), 2 * PAGE_SIZE);
+        vmo.set_content_size(2 * PAGE_SIZE).unwrap();
+        assert_eq!(
+            vmo.set_content_size(2 * PAGE_SIZE + 1),
+            Err(ZxError::OUT_OF_RANGE)
+        );
+        assert_eq!(vmo.content_size(), 2 * PAGE_SIZE);
+
+        let vmo = VmObject::new_paged_with_resizable(true, 1);
+        vmo.set_len(10).unwrap();
+        assert_eq!(vmo.content_size(), 10);
+    }
+
+    #[test]
+    fn commit() {
+        let vmo = VmObject::new_paged(4);
+        vmo.test_write(0, 1);
+        assert_eq!(vmo.committed_bytes(), PAGE_SIZE);
+        vmo.commit(PAGE_SIZE, 2 * PAGE_SIZE).unwrap();
+        assert_eq!(vmo.committed_bytes(), 3 * PAGE_SIZE);
+        // committed pages are counted once
+        vmo.commit(0, 2 * PAGE_SIZE).unwrap();
+        assert_eq!(vmo.committed_bytes(), 3 * PAGE_SIZE);
+        assert_eq!(vmo.test_read(1), 0);
+    }
+
+    #[test]
+    fn commit_no_memory() {
+        let vmo = VMObjectPaged::new(0, 4);
+        vmo.write(PAGE_SIZE, &[1]).unwrap();
+        let mut inner = vmo.inner.lock();
+        let ret = inner.commit_range(0, 4, |inner, i| {
+            if i == 2 {
+                return Err(ZxError::NO_MEMORY);
+            }
+            inner.commit_page(i, MMUFlags::WRITE, &Weak::new())?;
+            Ok(())
+        });
+        assert_eq!(ret, Err(ZxError::NO_MEMORY));
+        // only the page committed before is left
+        assert_eq!(inner.frames.keys().copied().collect::<Vec<_>>(), [1]);
+        drop(inner);
+        let mut buf = [0u8; 1];
+        vmo.read(PAGE_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, [1]);
+    }
+
+    #[test]
+    fn set_len() {
+        let vmo = VmObject::new_paged(1);
+        assert_eq!(vmo.set_len(2 * PAGE_SIZE), Err(ZxError::UNAVAILABLE));
+
+        let vmo = VmObject::new_paged_with_resizable(true, 2);
+        vmo.test_write(0, 1);
+        vmo.test_write(1, 2);
+
+        // frames beyond the new size are freed
+        vmo.set_len(PAGE_SIZE).unwrap();
+        assert_eq!(vmo.len(), PAGE_SIZE);
+        assert_eq!(vmo.committed_bytes(), PAGE_SIZE);
+        let mut buf = [0u8; 1];
+        assert_eq!(
+            vmo.read_vector(&mut [(PAGE_SIZE, &mut buf)]),
+            Err(ZxError::OUT_OF_RANGE)
+        );
+
+        // new pages read as zero
+        vmo.set_len(3 * PAGE_SIZE).unwrap();
+        assert_eq!(vmo.test_read(0), 1);
+        assert_eq!(vmo.test_read(1), 0);
+        assert_eq!(vmo.test_read(2), 0);
+        assert_eq!(vmo.committed_bytes(), PAGE_SIZE);
+
+        // pinned pages can not be removed
+        vmo.pin(0, PAGE_SIZE).unwrap();
+        assert_eq!(vmo.set_len(0), Err(ZxError::BAD_STATE));
+        vmo.unpin(0, PAGE_SIZE);
+        vmo.set_len(0).unwrap();
+        assert_eq!(vmo.committed_bytes(), 0);
+    }
+
+    #[test]
+    fn committed_bytes() {
+        let vmo = VmObject::new_paged(4);
+        assert_eq!(vmo.committed_bytes(), 0);
+        vmo.test_write(0, 1);
+        vmo.test_write(2, 1);
+        assert_eq!(vmo.committed_bytes(), 2 * PAGE_SIZE);
+        assert_eq!(vmo.get_info().committed_bytes, 2 * PAGE_SIZE as u64);
+        assert_eq!(VmObject::new_paged(0).committed_bytes(), 0);
+    }
+
     impl VmObject {
         fn test_write(&self, page: usize, value: u8) {
             self.write(page * PAGE_SIZE, &[value]).unwrap();
@@ -1,6 +1,9 @@
 use {
     super::*,
-    alloc::sync::{Arc, Weak},
+    alloc::{
+        sync::{Arc, Weak},
+        vec::Vec,
+    },
     spin::Mutex,
 };
 
@@ -64,7 +67,7 @@ impl VMObjectTrait for VMObjectPhysical {
         self.pages * PAGE_SIZE
     }
 
-    fn set_len(&self, _len: usize) {
+    fn set_len(&self, _len: usize) -> ZxResult {
         unimplemented!()
     }
 
@@ -82,6 +85,16 @@ impl VMObjectTrait for VMObjectPhysical {
         Ok(())
     }
 
+    fn pin(&self, offset: usize, len: usize) -> ZxResult<Vec<PhysAddr>> {
+        // physical memory never moves
+        let start = self.paddr + offset;
+        Ok((0..len / PAGE_SIZE)
+            .map(|i| start + i * PAGE_SIZE)
+            .collect())
+    }
+
+    fn unpin(&self, _offset: usize, _len: usize) {}
+
     fn create_child(&self, _offset: usize, _len: usize, _user_id: KoID) -> Arc<dyn VMObjectTrait> {
         unimplemented!()
     }
@@ -98,8 +111,11 @@ impl VMObjectTrait for VMObjectPhysical {
         inner.mapping_count -= 1;
     }
 
-    fn complete_info(&self, _info: &mut ZxInfoVmo) {
-        unimplemented!()
+    fn complete_info(&self, info: &mut ZxInfoVmo) {
+        let inner = self.inner.lock();
+        info.flags |= VmoInfoFlags::TYPE_PHYSICAL | VmoInfoFlags::CONTIGUOUS;
+        info.num_mappings = inner.mapping_count as u64;
+        info.share_count = inner.mapping_count as u64;
     }
 
     fn get_cache_policy(&self) -> CachePolicy {
@@ -122,11 +138,16 @@ impl VMObjectTrait for VMObjectPhysical {
     }
 
     fn share_count(&self) -> usize {
-        unimplemented!()
+        self.inner.lock().mapping_count as usize
     }
 
     fn committed_pages_in_range(&self, _start_idx: usize, _end_idx: usize) -> usize {
-        unimplemented!()
+        // physical memory is not committed on demand
+        0
+    }
+
+    fn cow_shared_pages_in_range(&self, _start_idx: usize, _end_idx: usize) -> usize {
+        0
     }
 }
 
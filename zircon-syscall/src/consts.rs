@@ -173,5 +173,9 @@ pub enum SyscallType {
     FUTEX_WAKE_HANDLE_CLOSE_THREAD_EXIT = 164,
     VMAR_UNMAP_HANDLE_CLOSE_THREAD_EXIT = 165,
     COUNT = 166,
+    VMO_READ_VECTOR = 4096,
+    VMO_WRITE_VECTOR = 4097,
+    TICKS_PER_SECOND = 4098,
+    VMAR_GET_MAPS = 4099,
 }
 }
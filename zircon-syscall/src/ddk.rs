@@ -1,8 +1,10 @@
 use {
     super::*,
+    bitflags::bitflags,
     zircon_object::{
-        dev::{Bti, Iommu},
+        dev::{Bti, DevVAddr, Iommu, Pmt},
         resource::*,
+        vm::*,
     },
 };
 
@@ -59,6 +61,83 @@ impl Syscall<'_> {
         out.write(handle)?;
         Ok(())
     }
+
+    /// Pin pages of a VMO for device access, and get their device addresses.
+    #[allow(clippy::too_many_arguments)]
+    pub fn sys_bti_pin(
+        &self,
+        bti: HandleValue,
+        options: u32,
+        vmo: HandleValue,
+        offset: usize,
+        size: usize,
+        mut addrs: UserOutPtr<DevVAddr>,
+        addrs_count: usize,
+        mut pmt_out: UserOutPtr<HandleValue>,
+    ) -> ZxResult {
+        info!(
+            "bti.pin: bti={:#x}, options={:#x}, vmo={:#x}, offset={:#x}, size={:#x}, addrs_count={:#x}",
+            bti, options, vmo, offset, size, addrs_count
+        );
+        let options = BtiOptions::from_bits(options).ok_or(ZxError::INVALID_ARGS)?;
+        let proc = self.thread.proc();
+        let bti = proc.get_object_with_rights::<Bti>(bti, Rights::MAP)?;
+        let (vmo, vmo_rights) = proc.get_object_and_rights::<VmObject>(vmo)?;
+        if !vmo_rights.contains(Rights::MAP) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let perms = BtiOptions::PERM_READ | BtiOptions::PERM_WRITE | BtiOptions::PERM_EXECUTE;
+        if !options.intersects(perms) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        if options.contains(BtiOptions::PERM_WRITE) && !vmo_rights.contains(Rights::WRITE) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        if options.intersects(BtiOptions::PERM_READ | BtiOptions::PERM_EXECUTE)
+            && !vmo_rights.contains(Rights::READ)
+        {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let compress = options.contains(BtiOptions::COMPRESS);
+        let contiguous = options.contains(BtiOptions::CONTIGUOUS);
+        if compress && contiguous {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let pmt = bti.pin(vmo, offset, size)?;
+        // the PMT is unpinned when dropped on error
+        let encoded_addrs = pmt.encode_addrs(compress, contiguous)?;
+        if encoded_addrs.len() != addrs_count {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        addrs.write_array(&encoded_addrs)?;
+        let handle = proc.add_handle(Handle::new(pmt, Rights::DEFAULT_PMT));
+        pmt_out.write(handle)?;
+        Ok(())
+    }
+
+    /// Unpin pages and close the PMT handle.
+    pub fn sys_pmt_unpin(&self, pmt: HandleValue) -> ZxResult {
+        info!("pmt.unpin: pmt={:#x}", pmt);
+        let proc = self.thread.proc();
+        let pmt = proc
+            .remove_handle(pmt)?
+            .object
+            .downcast_arc::<Pmt>()
+            .map_err(|_| ZxError::WRONG_TYPE)?;
+        pmt.unpin();
+        Ok(())
+    }
+}
+
+bitflags! {
+    struct BtiOptions: u32 {
+        #[allow(clippy::identity_op)]
+        const PERM_READ     = 1 << 0;
+        const PERM_WRITE    = 1 << 1;
+        const PERM_EXECUTE  = 1 << 2;
+        const COMPRESS      = 1 << 3;
+        const CONTIGUOUS    = 1 << 4;
+    }
 }
 
 const IOMMU_MAX_DESC_LEN: usize = 4096;
@@ -1,10 +1,11 @@
 use {
     super::*,
-    zircon_object::{debuglog::DebugLog, resource::ResourceKind},
+    zircon_object::{
+        debuglog::{DebugLog, FLAG_READABLE},
+        resource::ResourceKind,
+    },
 };
 
-const FLAG_READABLE: u32 = 0x4000_0000u32;
-
 impl Syscall<'_> {
     pub fn sys_debuglog_create(
         &self,
@@ -52,4 +53,28 @@ impl Syscall<'_> {
             .write(flags, &data, tid, pid)?;
         Ok(())
     }
+
+    /// Read the next record into `buf`, and return its length.
+    pub fn sys_debuglog_read(
+        &self,
+        handle_value: HandleValue,
+        options: u32,
+        mut buf: UserOutPtr<u8>,
+        len: usize,
+    ) -> ZxResult<usize> {
+        info!(
+            "debuglog.read: handle={:#x?}, options={:#x?}, buf=({:#x?}; {:#x?})",
+            handle_value, options, buf, len,
+        );
+        if options != 0 {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let proc = self.thread.proc();
+        let record = proc
+            .get_object_with_rights::<DebugLog>(handle_value, Rights::READ)?
+            .read()?;
+        let len = len.min(record.len());
+        buf.write_array(&record[..len])?;
+        Ok(len)
+    }
 }
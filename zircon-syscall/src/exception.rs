@@ -19,21 +19,34 @@ impl Syscall<'_> {
             task,
             Rights::INSPECT | Rights::DUPLICATE | Rights::TRANSFER | Rights::MANAGE_THREAD,
         )?;
-        let exceptionate = task
-            .clone()
-            .downcast_arc::<Job>()
-            .map(|x| x.get_exceptionate())
-            .or_else(|_| {
-                task.clone()
-                    .downcast_arc::<Process>()
-                    .map(|x| x.get_exceptionate())
-            })
-            .or_else(|_| {
-                task.clone()
-                    .downcast_arc::<Thread>()
-                    .map(|x| x.get_exceptionate())
-            })
-            .map_err(|_| ZxError::WRONG_TYPE)?;
+        let exceptionate = if option == EXCEPTION_CHANNEL_DEBUGGER {
+            task.clone()
+                .downcast_arc::<Job>()
+                .map(|x| x.get_debug_exceptionate())
+                .or_else(|_| {
+                    task.clone()
+                        .downcast_arc::<Process>()
+                        .map(|x| x.get_debug_exceptionate())
+                })
+                .map_err(|_| ZxError::INVALID_ARGS)?
+        } else if option == 0 {
+            task.clone()
+                .downcast_arc::<Job>()
+                .map(|x| x.get_exceptionate())
+                .or_else(|_| {
+                    task.clone()
+                        .downcast_arc::<Process>()
+                        .map(|x| x.get_exceptionate())
+                })
+                .or_else(|_| {
+                    task.clone()
+                        .downcast_arc::<Thread>()
+                        .map(|x| x.get_exceptionate())
+                })
+                .map_err(|_| ZxError::WRONG_TYPE)?
+        } else {
+            return Err(ZxError::INVALID_ARGS);
+        };
         let (end0, end1) = Channel::create();
         exceptionate.set_channel(end0);
         let user_end = proc.add_handle(Handle::new(end1, Rights::DEFAULT_CHANNEL));
@@ -41,3 +54,5 @@ impl Syscall<'_> {
         Ok(())
     }
 }
+
+const EXCEPTION_CHANNEL_DEBUGGER: u32 = 1;
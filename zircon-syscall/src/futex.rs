@@ -1,5 +1,13 @@
 use {super::*, core::sync::atomic::*, zircon_object::task::ThreadState};
 
+/// A futex must be a non-null, 4-byte aligned pointer.
+fn check_futex_ptr(ptr: &UserInPtr<AtomicI32>) -> ZxResult {
+    if ptr.is_null() || ptr.as_ptr() as usize % 4 != 0 {
+        return Err(ZxError::INVALID_ARGS);
+    }
+    Ok(())
+}
+
 impl Syscall<'_> {
     pub async fn sys_futex_wait(
         &self,
@@ -12,9 +20,7 @@ impl Syscall<'_> {
             "futex.wait: value_ptr={:#x?}, current_value={:#x}, new_futex_owner={:#x}, deadline={:?}",
             value_ptr, current_value, new_futex_owner, deadline
         );
-        if value_ptr.is_null() || value_ptr.as_ptr() as usize % 4 != 0 {
-            return Err(ZxError::INVALID_ARGS);
-        }
+        check_futex_ptr(&value_ptr)?;
         let value = value_ptr.as_ref()?;
         let proc = self.thread.proc();
         let futex = proc.get_futex(value);
@@ -43,11 +49,13 @@ impl Syscall<'_> {
             "futex.requeue: value_ptr={:?}, wake_count={:#x}, current_value={:#x}, requeue_ptr={:?}, requeue_count={:#x}, new_requeue_owner={:?}",
             value_ptr, wake_count, current_value, requeue_ptr, requeue_count, new_requeue_owner
         );
-        let value = value_ptr.as_ref()?;
-        let requeue = requeue_ptr.as_ref()?;
+        check_futex_ptr(&value_ptr)?;
+        check_futex_ptr(&requeue_ptr)?;
         if value_ptr.as_ptr() == requeue_ptr.as_ptr() {
             return Err(ZxError::INVALID_ARGS);
         }
+        let value = value_ptr.as_ref()?;
+        let requeue = requeue_ptr.as_ref()?;
         let proc = self.thread.proc();
         let new_requeue_owner = if new_requeue_owner == INVALID_HANDLE {
             None
@@ -68,6 +76,7 @@ impl Syscall<'_> {
 
     pub fn sys_futex_wake(&self, value_ptr: UserInPtr<AtomicI32>, count: u32) -> ZxResult {
         info!("futex.wake: value_ptr={:?}, count={:#x}", value_ptr, count);
+        check_futex_ptr(&value_ptr)?;
         let value = value_ptr.as_ref()?;
         let proc = self.thread.proc();
         let futex = proc.get_futex(value);
@@ -83,3 +92,35 @@ impl Syscall<'_> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zircon_object::task::*;
+
+    #[test]
+    fn bad_futex_ptr() {
+        let proc = Process::create(&Job::root(), "proc", 0).unwrap();
+        let thread = Thread::create(&proc, "thread", 0).unwrap();
+        let syscall = syscall_for(thread);
+        let value = AtomicI32::new(0);
+        let good = &value as *const AtomicI32 as usize;
+        let ptr = |addr: usize| UserInPtr::<AtomicI32>::from(addr);
+        // null or not aligned to 4 bytes
+        for &bad in [0, good + 2].iter() {
+            assert_eq!(
+                syscall.sys_futex_wake(ptr(bad), 1),
+                Err(ZxError::INVALID_ARGS)
+            );
+            assert_eq!(
+                syscall.sys_futex_requeue(ptr(good), 1, 0, ptr(bad), 1, INVALID_HANDLE),
+                Err(ZxError::INVALID_ARGS)
+            );
+            assert_eq!(
+                syscall.sys_futex_requeue(ptr(bad), 1, 0, ptr(good), 1, INVALID_HANDLE),
+                Err(ZxError::INVALID_ARGS)
+            );
+        }
+        assert_eq!(syscall.sys_futex_wake(ptr(good), 1), Ok(()));
+    }
+}
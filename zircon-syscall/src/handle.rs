@@ -17,14 +17,8 @@ impl Syscall<'_> {
             if !handle_rights.contains(Rights::DUPLICATE) {
                 return Err(ZxError::ACCESS_DENIED);
             }
-            if !rights.contains(Rights::SAME_RIGHTS) {
-                if (handle_rights & rights).bits() != rights.bits() {
-                    return Err(ZxError::INVALID_ARGS);
-                }
-                Ok(rights)
-            } else {
-                Ok(handle_rights)
-            }
+            // rights beyond the handle's are invalid arguments here
+            reduce_rights(handle_rights, rights).map_err(|_| ZxError::INVALID_ARGS)
         })?;
         new_handle_value.write(new_value)?;
         Ok(())
@@ -72,16 +66,56 @@ impl Syscall<'_> {
             handle_value, rights
         );
         let proc = self.thread.proc();
-        let new_value = proc.dup_handle_operating_rights(handle_value, |handle_rights| {
-            if !rights.contains(Rights::SAME_RIGHTS)
-                && (handle_rights & rights).bits() != rights.bits()
-            {
-                return Err(ZxError::INVALID_ARGS);
-            }
-            Ok(rights)
+        let new_value = proc.replace_handle_operating_rights(handle_value, |handle_rights| {
+            reduce_rights(handle_rights, rights).map_err(|_| ZxError::INVALID_ARGS)
         })?;
-        proc.remove_handle(handle_value)?;
         out.write(new_value)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zircon_object::task::*;
+
+    #[test]
+    fn duplicate() {
+        let proc = Process::create(&Job::root(), "proc", 0).unwrap();
+        let thread = Thread::create(&proc, "thread", 0).unwrap();
+        let syscall = syscall_for(thread);
+        let dup = |handle: HandleValue, rights: Rights| -> ZxResult<HandleValue> {
+            let mut new_handle = INVALID_HANDLE;
+            syscall.sys_handle_duplicate(
+                handle,
+                rights.bits(),
+                UserOutPtr::from(&mut new_handle as *mut HandleValue as usize),
+            )?;
+            Ok(new_handle)
+        };
+        let rights = Rights::DUPLICATE | Rights::READ | Rights::WRITE;
+        let handle = proc.add_handle(Handle::new(proc.clone(), rights));
+
+        // `Rights::SAME_RIGHTS` copies the rights
+        let same = dup(handle, Rights::SAME_RIGHTS).unwrap();
+        assert_ne!(same, handle);
+        assert_eq!(proc.get_handle(same).unwrap().rights, rights);
+
+        // a strict subset is kept, and the old handle is not changed
+        let subset = dup(handle, Rights::READ).unwrap();
+        assert_eq!(proc.get_handle(subset).unwrap().rights, Rights::READ);
+        assert_eq!(proc.get_handle(handle).unwrap().rights, rights);
+
+        // rights beyond the handle's are invalid arguments
+        assert_eq!(
+            dup(handle, rights | Rights::EXECUTE),
+            Err(ZxError::INVALID_ARGS)
+        );
+
+        // a handle without `Rights::DUPLICATE` can not be duplicated
+        assert_eq!(
+            dup(subset, Rights::SAME_RIGHTS),
+            Err(ZxError::ACCESS_DENIED)
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use {
+    super::*,
+    zircon_object::{ktrace::SyscallRecord, resource::ResourceKind},
+};
+
+impl Syscall<'_> {
+    /// Take the syscall trace records out of the kernel.
+    ///
+    /// Records are consumed by reading, so `offset` must be zero.
+    /// Only whole records are written. If `data` is null, the number of
+    /// bytes available is written to `actual` and nothing is consumed.
+    pub fn sys_ktrace_read(
+        &self,
+        handle: HandleValue,
+        data: usize,
+        offset: u32,
+        len: usize,
+        mut actual: UserOutPtr<usize>,
+    ) -> ZxResult {
+        info!(
+            "ktrace.read: handle={:#x}, data=({:#x}; {:#x}), offset={:#x}",
+            handle, data, len, offset
+        );
+        let proc = self.thread.proc();
+        proc.validate_resource(handle, ResourceKind::ROOT)?;
+        if offset != 0 {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let record_size = core::mem::size_of::<SyscallRecord>();
+        if data == 0 {
+            actual.write(zircon_object::ktrace::len() * record_size)?;
+            return Ok(());
+        }
+        let mut records = zircon_object::ktrace::drain();
+        // records which don't fit are lost, as they have been taken out
+        records.truncate(len / record_size);
+        UserOutPtr::<SyscallRecord>::from(data).write_array(&records)?;
+        actual.write(records.len() * record_size)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "trace"))]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use futures::FutureExt;
+    use zircon_object::{
+        resource::{Resource, ResourceFlags},
+        signal::Event,
+        task::*,
+    };
+
+    #[test]
+    fn read_syscall_records() {
+        let proc = Process::create(&Job::root(), "proc", 0).unwrap();
+        let thread = Thread::create(&proc, "thread", 0).unwrap();
+        let resource = Resource::create("root", ResourceKind::ROOT, 0, 0, ResourceFlags::empty());
+        let root = proc.add_handle(Handle::new(resource, Rights::DEFAULT_RESOURCE));
+        let event = proc.add_handle(Handle::new(Event::new(), Rights::DEFAULT_EVENT));
+        let mut syscall = syscall_for(thread.clone());
+
+        // closing the event succeeds once, then fails
+        let close = Sys::HANDLE_CLOSE as u32;
+        let args = [event as usize, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(syscall.syscall(close, args).now_or_never(), Some(0));
+        let bad_handle = ZxError::BAD_HANDLE as isize;
+        assert_eq!(
+            syscall.syscall(close, args).now_or_never(),
+            Some(bad_handle)
+        );
+
+        let record_size = core::mem::size_of::<SyscallRecord>();
+        let mut records = vec![SyscallRecord::default(); 64];
+        let mut actual = 0usize;
+        syscall
+            .sys_ktrace_read(
+                root,
+                records.as_mut_ptr() as usize,
+                0,
+                records.len() * record_size,
+                UserOutPtr::from(&mut actual as *mut usize as usize),
+            )
+            .unwrap();
+        records.truncate(actual / record_size);
+        // other tests may make syscalls at the same time
+        let mine: Vec<_> = records.iter().filter(|r| r.thread == thread.id()).collect();
+        assert_eq!(mine.len(), 2);
+        assert!(mine
+            .iter()
+            .all(|r| r.num == close && r.args[0] == event as u64));
+        assert_eq!(mine[0].result, 0);
+        assert_eq!(mine[1].result, bad_handle as i64);
+        assert!(mine[0].start <= mine[1].start);
+    }
+}
@@ -9,6 +9,9 @@ extern crate alloc;
 #[macro_use]
 extern crate log;
 
+#[cfg(test)]
+extern crate kernel_hal_unix;
+
 use {
     self::time::Deadline,
     alloc::sync::Arc,
@@ -29,11 +32,14 @@ mod exception;
 mod fifo;
 mod futex;
 mod handle;
+mod ktrace;
 mod object;
 mod port;
+mod profile;
 mod resource;
 mod signal;
 mod socket;
+mod stream;
 mod system;
 mod task;
 mod time;
@@ -42,6 +48,8 @@ mod vmo;
 
 use consts::SyscallType as Sys;
 
+zircon_object::kcounter!(SYSCALLS, "syscall.dispatched");
+
 pub struct Syscall<'a> {
     pub regs: &'a mut GeneralRegs,
     pub thread: Arc<Thread>,
@@ -49,6 +57,17 @@ pub struct Syscall<'a> {
     pub exit: bool,
 }
 
+/// Create the syscall context of `thread`, for tests.
+#[cfg(test)]
+fn syscall_for(thread: Arc<Thread>) -> Syscall<'static> {
+    Syscall {
+        regs: alloc::boxed::Box::leak(alloc::boxed::Box::new(GeneralRegs::default())),
+        thread,
+        spawn_fn: |_| {},
+        exit: false,
+    }
+}
+
 impl Syscall<'_> {
     pub async fn syscall(&mut self, num: u32, args: [usize; 8]) -> isize {
         let thread_name = self.thread.name();
@@ -64,7 +83,12 @@ impl Syscall<'_> {
             "{}|{} {:?} => args={:x?}",
             proc_name, thread_name, sys_type, args
         );
+        #[cfg(feature = "trace")]
+        let start = kernel_hal::timer_now();
         let [a0, a1, a2, a3, a4, a5, a6, a7] = args;
+        SYSCALLS.add(1);
+        // the positive return value of a few syscalls
+        let mut value = 0;
         let ret = match sys_type {
             Sys::HANDLE_CLOSE => self.sys_handle_close(a0 as _),
             Sys::HANDLE_CLOSE_MANY => self.sys_handle_close_many(a0.into(), a1 as _),
@@ -89,6 +113,8 @@ impl Syscall<'_> {
                 self.sys_object_wait_many(a0.into(), a1 as _, a2.into())
                     .await
             }
+            Sys::OBJECT_SET_PROFILE => self.sys_object_set_profile(a0 as _, a1 as _, a2 as _),
+            Sys::PROFILE_CREATE => self.sys_profile_create(a0 as _, a1 as _, a2.into(), a3.into()),
             Sys::OBJECT_WAIT_ASYNC => {
                 self.sys_object_wait_async(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _)
             }
@@ -96,6 +122,9 @@ impl Syscall<'_> {
                 self.sys_thread_create(a0 as _, a1.into(), a2 as _, a3 as _, a4.into())
             }
             Sys::THREAD_START => self.sys_thread_start(a0 as _, a1 as _, a2 as _, a3 as _, a4 as _),
+            Sys::THREAD_READ_STATE => {
+                self.sys_thread_read_state(a0 as _, a1 as _, a2.into(), a3 as _)
+            }
             Sys::THREAD_WRITE_STATE => {
                 self.sys_thread_write_state(a0 as _, a1 as _, a2.into(), a3 as _)
             }
@@ -110,7 +139,9 @@ impl Syscall<'_> {
             Sys::JOB_CREATE => self.sys_job_create(a0 as _, a1 as _, a2.into()),
             Sys::JOB_SET_POLICY => self.sys_job_set_policy(a0 as _, a1 as _, a2 as _, a3, a4 as _),
             Sys::JOB_SET_CRITICAL => self.sys_job_set_critical(a0 as _, a1 as _, a2 as _),
-            Sys::TASK_SUSPEND_TOKEN => self.sys_task_suspend_token(a0 as _, a1.into()),
+            Sys::TASK_SUSPEND | Sys::TASK_SUSPEND_TOKEN => {
+                self.sys_task_suspend_token(a0 as _, a1.into())
+            }
             Sys::CHANNEL_CREATE => self.sys_channel_create(a0 as _, a1.into(), a2.into()),
             Sys::CHANNEL_READ => self.sys_channel_read(
                 a0 as _,
@@ -152,6 +183,8 @@ impl Syscall<'_> {
                 self.sys_channel_call_finish(a0.into(), a1.into(), a2.into(), a3.into())
             }
             Sys::SOCKET_CREATE => self.sys_socket_create(a0 as _, a1.into(), a2.into()),
+            Sys::STREAM_CREATE => self.sys_stream_create(a0 as _, a1 as _, a2 as _, a3.into()),
+            Sys::STREAM_SEEK => self.sys_stream_seek(a0 as _, a1 as _, a2 as _, a3.into()),
             Sys::FIFO_CREATE => {
                 self.sys_fifo_create(a0 as _, a1 as _, a2 as _, a3.into(), a4.into())
             }
@@ -162,6 +195,7 @@ impl Syscall<'_> {
             Sys::PORT_CREATE => self.sys_port_create(a0 as _, a1.into()),
             Sys::PORT_WAIT => self.sys_port_wait(a0 as _, a1.into(), a2.into()).await,
             Sys::PORT_QUEUE => self.sys_port_queue(a0 as _, a1.into()),
+            Sys::PORT_CANCEL => self.sys_port_cancel(a0 as _, a1 as _, a2 as _),
             Sys::FUTEX_WAIT => {
                 self.sys_futex_wait(a0.into(), a1 as _, a2 as _, a3.into())
                     .await
@@ -174,6 +208,8 @@ impl Syscall<'_> {
             Sys::VMO_CREATE => self.sys_vmo_create(a0 as _, a1 as _, a2.into()),
             Sys::VMO_READ => self.sys_vmo_read(a0 as _, a1.into(), a2 as _, a3 as _),
             Sys::VMO_WRITE => self.sys_vmo_write(a0 as _, a1.into(), a2 as _, a3 as _),
+            Sys::VMO_READ_VECTOR => self.sys_vmo_read_vector(a0 as _, a1.into(), a2 as _),
+            Sys::VMO_WRITE_VECTOR => self.sys_vmo_write_vector(a0 as _, a1.into(), a2 as _),
             Sys::VMO_GET_SIZE => self.sys_vmo_get_size(a0 as _, a1.into()),
             Sys::VMO_SET_SIZE => self.sys_vmo_set_size(a0 as _, a1 as _),
             Sys::VMO_OP_RANGE => {
@@ -204,13 +240,26 @@ impl Syscall<'_> {
             }
             Sys::VMAR_PROTECT => self.sys_vmar_protect(a0 as _, a1 as _, a2 as _, a3 as _),
             Sys::VMAR_DESTROY => self.sys_vmar_destroy(a0 as _),
+            Sys::VMAR_GET_MAPS => {
+                self.sys_vmar_get_maps(a0 as _, a1 as _, a2 as _, a3 as _, a4.into(), a5.into())
+            }
             Sys::CPRNG_DRAW_ONCE => self.sys_cprng_draw_once(a0 as _, a1 as _),
             Sys::NANOSLEEP => self.sys_nanosleep(a0.into()).await,
             Sys::CLOCK_GET => self.sys_clock_get(a0 as _, a1.into()),
+            Sys::CLOCK_GET_MONOTONIC_VIA_KERNEL => self
+                .sys_clock_get_monotonic()
+                .map(|time| value = time as usize),
+            Sys::TICKS_GET_VIA_KERNEL => self.sys_ticks_get().map(|ticks| value = ticks as usize),
+            Sys::TICKS_PER_SECOND => self
+                .sys_ticks_per_second()
+                .map(|ticks| value = ticks as usize),
             Sys::TIMER_CREATE => self.sys_timer_create(a0 as _, a1 as _, a2.into()),
             Sys::DEBUG_WRITE => self.sys_debug_write(a0.into(), a1 as _),
             Sys::DEBUGLOG_CREATE => self.sys_debuglog_create(a0 as _, a1 as _, a2.into()),
             Sys::DEBUGLOG_WRITE => self.sys_debuglog_write(a0 as _, a1 as _, a2.into(), a3 as _),
+            Sys::DEBUGLOG_READ => self
+                .sys_debuglog_read(a0 as _, a1 as _, a2.into(), a3 as _)
+                .map(|len| value = len),
             Sys::RESOURCE_CREATE => self.sys_resource_create(
                 a0 as _,
                 a1 as _,
@@ -233,12 +282,24 @@ impl Syscall<'_> {
                 self.sys_iommu_create(a0 as _, a1 as _, a2.into(), a3 as _, a4.into())
             }
             Sys::BTI_CREATE => self.sys_bti_create(a0 as _, a1 as _, a2 as _, a3.into()),
+            Sys::BTI_PIN => self.sys_bti_pin(
+                a0 as _,
+                a1 as _,
+                a2 as _,
+                a3 as _,
+                a4 as _,
+                a5.into(),
+                a6 as _,
+                a7.into(),
+            ),
+            Sys::PMT_UNPIN => self.sys_pmt_unpin(a0 as _),
             Sys::VMAR_UNMAP_HANDLE_CLOSE_THREAD_EXIT => self
                 .sys_vmar_unmap(a0 as _, a1 as _, a2 as _)
                 .and_then(|_| {
                     let _ = self.sys_handle_close(a3 as _);
                     self.sys_thread_exit()
                 }),
+            Sys::KTRACE_READ => self.sys_ktrace_read(a0 as _, a1 as _, a2 as _, a3 as _, a4.into()),
             Sys::OBJECT_GET_CHILD => {
                 self.sys_object_get_child(a0 as _, a1 as _, a2 as _, a3.into())
             }
@@ -260,9 +321,18 @@ impl Syscall<'_> {
             sys_type,
             ret
         );
-        match ret {
-            Ok(_) => 0,
+        let ret = match ret {
+            Ok(_) => value as isize,
             Err(err) => err as isize,
-        }
+        };
+        #[cfg(feature = "trace")]
+        zircon_object::ktrace::record(zircon_object::ktrace::SyscallRecord::new(
+            num,
+            self.thread.id(),
+            args,
+            ret,
+            start,
+        ));
+        ret
     }
 }
@@ -3,7 +3,15 @@ use {
     alloc::vec::Vec,
     core::convert::TryFrom,
     numeric_enum_macro::numeric_enum,
-    zircon_object::{signal::Port, task::*, vm::*},
+    zircon_object::{
+        dev::Bti,
+        ipc::Socket,
+        resource::{Resource, ResourceKind},
+        signal::{Port, Timer, WaitAsyncOptions},
+        task::*,
+        util::kcounter::{KCounterDescriptorArray, KCounterInfo},
+        vm::*,
+    },
 };
 
 impl Syscall<'_> {
@@ -23,14 +31,36 @@ impl Syscall<'_> {
         let object = proc.get_dyn_object_with_rights(handle_value, Rights::GET_PROPERTY)?;
         match property {
             Property::Name => {
-                if buffer_size < MAX_NAME_LEN {
+                if (buffer_size as usize) < MAX_NAME_LEN {
                     return Err(ZxError::BUFFER_TOO_SMALL);
                 }
                 let s = object.name();
                 info!("name={:?}", s);
-                UserOutPtr::<u8>::from(ptr)
-                    .write_cstring(s.as_str())
-                    .expect("failed to write cstring");
+                UserOutPtr::<u8>::from(ptr).write_cstring(s.as_str())?;
+                Ok(())
+            }
+            // `ZX_PROP_NUM_STATE_KINDS` shares the value with the write-only `ZX_PROP_REGISTER_GS`
+            Property::RegisterGs => {
+                if buffer_size < 4 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                proc.get_object_with_rights::<Thread>(handle_value, Rights::GET_PROPERTY)?;
+                UserOutPtr::<u32>::from(ptr).write(NUM_STATE_KINDS)?;
+                Ok(())
+            }
+            Property::RegisterFs => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let thread = proc.get_object::<Thread>(handle_value)?;
+                let fsbase = if Arc::ptr_eq(&thread, &self.thread) {
+                    self.regs.fsbase
+                } else {
+                    // other threads are read like `thread_read_state`
+                    proc.get_object_with_rights::<Thread>(handle_value, Rights::READ)?
+                        .fsbase()?
+                };
+                UserOutPtr::<u64>::from(ptr).write(fsbase as u64)?;
                 Ok(())
             }
             Property::ProcessDebugAddr => {
@@ -62,6 +92,66 @@ impl Syscall<'_> {
                 UserOutPtr::<usize>::from(ptr).write(break_on_load)?;
                 Ok(())
             }
+            Property::VmoContentSize => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let content_size = proc
+                    .get_object_with_rights::<VmObject>(handle_value, Rights::GET_PROPERTY)?
+                    .content_size();
+                UserOutPtr::<u64>::from(ptr).write(content_size as u64)?;
+                Ok(())
+            }
+            Property::VmoCommitThreshold => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let threshold = proc
+                    .get_object_with_rights::<VmObject>(handle_value, Rights::GET_PROPERTY)?
+                    .commit_threshold();
+                UserOutPtr::<u64>::from(ptr).write(threshold as u64)?;
+                Ok(())
+            }
+            Property::SocketRxThreshold => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let threshold = proc
+                    .get_object_with_rights::<Socket>(handle_value, Rights::GET_PROPERTY)?
+                    .rx_threshold();
+                UserOutPtr::<usize>::from(ptr).write(threshold)?;
+                Ok(())
+            }
+            Property::SocketTxThreshold => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let threshold = proc
+                    .get_object_with_rights::<Socket>(handle_value, Rights::GET_PROPERTY)?
+                    .tx_threshold();
+                UserOutPtr::<usize>::from(ptr).write(threshold)?;
+                Ok(())
+            }
+            Property::JobKillOnOom => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let kill_on_oom = proc
+                    .get_object_with_rights::<Job>(handle_value, Rights::GET_PROPERTY)?
+                    .get_kill_on_oom();
+                UserOutPtr::<usize>::from(ptr).write(kill_on_oom as usize)?;
+                Ok(())
+            }
+            Property::StreamModeAppend => {
+                if buffer_size < 1 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let append = proc
+                    .get_object_with_rights::<Stream>(handle_value, Rights::GET_PROPERTY)?
+                    .is_append();
+                UserOutPtr::<u8>::from(ptr).write(append as u8)?;
+                Ok(())
+            }
             _ => {
                 warn!("unknown property");
                 Err(ZxError::INVALID_ARGS)
@@ -85,10 +175,19 @@ impl Syscall<'_> {
         let object = proc.get_dyn_object_with_rights(handle_value, Rights::SET_PROPERTY)?;
         match property {
             Property::Name => {
-                let length = buffer_size.min(MAX_NAME_LEN) as usize;
-                let s = UserInPtr::<u8>::from(ptr).read_string(length)?;
+                let length = (buffer_size as usize).min(MAX_NAME_LEN - 1);
+                let bytes = UserInPtr::<u8>::from(ptr).read_array(length)?;
+                let bytes = bytes.split(|&b| b == 0).next().unwrap();
+                let s = match core::str::from_utf8(bytes) {
+                    Ok(s) => s,
+                    // the last character may be cut off by truncation
+                    Err(e) if e.error_len().is_none() => {
+                        core::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap()
+                    }
+                    Err(_) => return Err(ZxError::INVALID_ARGS),
+                };
                 info!("set name={:?}", s);
-                object.set_name(&s);
+                object.set_name(s);
                 Ok(())
             }
             Property::ProcessDebugAddr => {
@@ -105,12 +204,27 @@ impl Syscall<'_> {
                     return Err(ZxError::BUFFER_TOO_SMALL);
                 }
                 let thread = proc.get_object::<Thread>(handle_value)?;
-                assert!(Arc::ptr_eq(&thread, &self.thread));
+                if !Arc::ptr_eq(&thread, &self.thread) {
+                    return Err(ZxError::ACCESS_DENIED);
+                }
                 let fsbase = UserInPtr::<u64>::from(ptr).read()?;
                 info!("set fsbase = {:#x}", fsbase);
                 self.regs.fsbase = fsbase as usize;
                 Ok(())
             }
+            Property::RegisterGs => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let thread = proc.get_object::<Thread>(handle_value)?;
+                if !Arc::ptr_eq(&thread, &self.thread) {
+                    return Err(ZxError::ACCESS_DENIED);
+                }
+                let gsbase = UserInPtr::<u64>::from(ptr).read()?;
+                info!("set gsbase = {:#x}", gsbase);
+                self.regs.gsbase = gsbase as usize;
+                Ok(())
+            }
             Property::ProcessBreakOnLoad => {
                 if buffer_size < 8 {
                     return Err(ZxError::BUFFER_TOO_SMALL);
@@ -120,6 +234,61 @@ impl Syscall<'_> {
                     .set_dyn_break_on_load(addr);
                 Ok(())
             }
+            Property::VmoContentSize => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let content_size = UserInPtr::<u64>::from(ptr).read()?;
+                proc.get_object_with_rights::<VmObject>(handle_value, Rights::SET_PROPERTY)?
+                    .set_content_size(content_size as usize)
+            }
+            Property::VmoCommitThreshold => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let threshold = UserInPtr::<u64>::from(ptr).read()?;
+                proc.get_object_with_rights::<VmObject>(handle_value, Rights::SET_PROPERTY)?
+                    .set_commit_threshold(threshold as usize);
+                Ok(())
+            }
+            Property::SocketRxThreshold => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let threshold = UserInPtr::<usize>::from(ptr).read()?;
+                proc.get_object_with_rights::<Socket>(handle_value, Rights::SET_PROPERTY)?
+                    .set_rx_threshold(threshold)
+            }
+            Property::SocketTxThreshold => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let threshold = UserInPtr::<usize>::from(ptr).read()?;
+                proc.get_object_with_rights::<Socket>(handle_value, Rights::SET_PROPERTY)?
+                    .set_tx_threshold(threshold)
+            }
+            Property::JobKillOnOom => {
+                if buffer_size < 8 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let kill_on_oom = match UserInPtr::<usize>::from(ptr).read()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(ZxError::INVALID_ARGS),
+                };
+                proc.get_object_with_rights::<Job>(handle_value, Rights::SET_PROPERTY)?
+                    .set_kill_on_oom(kill_on_oom);
+                Ok(())
+            }
+            Property::StreamModeAppend => {
+                if buffer_size < 1 {
+                    return Err(ZxError::BUFFER_TOO_SMALL);
+                }
+                let append = UserInPtr::<u8>::from(ptr).read()? != 0;
+                proc.get_object_with_rights::<Stream>(handle_value, Rights::SET_PROPERTY)?
+                    .set_append(append);
+                Ok(())
+            }
             _ => {
                 warn!("unknown property");
                 Err(ZxError::INVALID_ARGS)
@@ -147,6 +316,16 @@ impl Syscall<'_> {
         })?;
         let proc = self.thread.proc();
         let object = proc.get_dyn_object_with_rights(handle, Rights::WAIT)?;
+        // fast path: no need to block if already satisfied or the deadline has passed
+        let current = object.signal();
+        if current.intersects(signals) {
+            observed.write_if_not_null(current)?;
+            return Ok(());
+        }
+        if !deadline.is_positive() {
+            observed.write_if_not_null(current)?;
+            return Err(ZxError::TIMED_OUT);
+        }
         let cancel_token = proc.get_cancel_token(handle)?;
         let future = object.wait_signal(signals);
         let signal = self
@@ -168,7 +347,6 @@ impl Syscall<'_> {
         Ok(())
     }
 
-    #[allow(unsafe_code)]
     pub fn sys_object_get_info(
         &self,
         handle: HandleValue,
@@ -185,109 +363,219 @@ impl Syscall<'_> {
         );
         let proc = self.thread.proc();
         match topic {
+            // used for probing, nothing to report
+            Topic::None => {}
             Topic::HandleValid => {
-                let _ = proc.get_dyn_object_with_rights(handle, Rights::empty())?;
+                if !proc.has_handle(handle) {
+                    return Err(ZxError::BAD_HANDLE);
+                }
             }
             Topic::Process => {
                 let proc = proc.get_object_with_rights::<Process>(handle, Rights::INSPECT)?;
-                UserOutPtr::<ProcessInfo>::from(buffer).write(proc.get_info())?;
+                write_record(proc.get_info(), buffer, buffer_size, actual, avail)?;
             }
             Topic::Vmar => {
                 let vmar =
                     proc.get_object_with_rights::<VmAddressRegion>(handle, Rights::INSPECT)?;
-                UserOutPtr::<VmarInfo>::from(buffer).write(vmar.get_info())?;
+                write_record(vmar.get_info(), buffer, buffer_size, actual, avail)?;
+            }
+            Topic::VmarFlags => {
+                let vmar =
+                    proc.get_object_with_rights::<VmAddressRegion>(handle, Rights::INSPECT)?;
+                let flags = vmar.get_info_flags().bits();
+                write_record(flags, buffer, buffer_size, actual, avail)?;
             }
             Topic::HandleBasic => {
                 let info = proc.get_handle_info(handle)?;
-                UserOutPtr::<HandleBasicInfo>::from(buffer).write(info)?;
+                write_record(info, buffer, buffer_size, actual, avail)?;
             }
             Topic::Thread => {
                 let thread = proc.get_object_with_rights::<Thread>(handle, Rights::INSPECT)?;
-                UserOutPtr::<ThreadInfo>::from(buffer).write(thread.get_thread_info())?;
+                write_record(thread.get_thread_info(), buffer, buffer_size, actual, avail)?;
+            }
+            Topic::ThreadExceptionReport => {
+                let thread = proc.get_object_with_rights::<Thread>(handle, Rights::INSPECT)?;
+                let report = thread.get_exception_report()?;
+                write_record(report, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::ThreadStats => {
+                let thread = proc.get_object_with_rights::<Thread>(handle, Rights::INSPECT)?;
+                write_record(
+                    thread.get_thread_stats(),
+                    buffer,
+                    buffer_size,
+                    actual,
+                    avail,
+                )?;
             }
             Topic::HandleCount => {
                 let object = proc.get_dyn_object_with_rights(handle, Rights::INSPECT)?;
-                // FIXME: count Handle instead of Arc
-                UserOutPtr::<u32>::from(buffer).write(Arc::strong_count(&object) as u32 - 1)?;
+                write_record(object.handle_count(), buffer, buffer_size, actual, avail)?;
+            }
+            Topic::ProcessHandleStats => {
+                let stats = proc
+                    .get_object_with_rights::<Process>(handle, Rights::INSPECT)?
+                    .get_handle_stats();
+                write_record(stats, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::HandleTable => {
+                let proc =
+                    proc.get_object_with_rights::<Process>(handle, Rights::MANAGE_PROCESS)?;
+                write_records(&proc.get_handle_table(), buffer, buffer_size, actual, avail)?;
+            }
+            Topic::VmoCommittedBytes => {
+                // a lightweight alternative to `Topic::Vmo` for memory monitors
+                let vmo = proc.get_object_with_rights::<VmObject>(handle, Rights::GET_PROPERTY)?;
+                write_record(
+                    vmo.committed_bytes() as u64,
+                    buffer,
+                    buffer_size,
+                    actual,
+                    avail,
+                )?;
             }
             Topic::Job => {
                 let job = proc.get_object_with_rights::<Job>(handle, Rights::INSPECT)?;
-                UserOutPtr::<JobInfo>::from(buffer).write(job.get_info())?;
+                write_record(job.get_info(), buffer, buffer_size, actual, avail)?;
             }
             Topic::Vmo => {
                 let (vmo, rights) = proc.get_object_and_rights::<VmObject>(handle)?;
+                if !rights.contains(Rights::INSPECT) {
+                    return Err(ZxError::ACCESS_DENIED);
+                }
                 let mut info = vmo.get_info();
                 info.flags |= VmoInfoFlags::VIA_HANDLE;
                 info.rights |= rights;
-                UserOutPtr::<ZxInfoVmo>::from(buffer).write(info)?;
+                write_record(info, buffer, buffer_size, actual, avail)?;
             }
             Topic::KmemStats => {
-                let mut kmem = ZxInfoKmem::default();
-                kmem.vmo_bytes = vmo_page_bytes() as u64;
-                UserOutPtr::<ZxInfoKmem>::from(buffer).write(kmem)?;
+                proc.validate_resource(handle, ResourceKind::ROOT)?;
+                let stats = kernel_hal::kmem_stats();
+                let vmo_bytes = vmo_page_bytes() as u64;
+                let used_bytes = (stats.total_bytes - stats.free_bytes) as u64;
+                let kmem = ZxInfoKmem {
+                    total_bytes: stats.total_bytes as u64,
+                    free_bytes: stats.free_bytes as u64,
+                    wired_bytes: used_bytes.saturating_sub(vmo_bytes),
+                    total_heap_bytes: stats.total_heap_bytes as u64,
+                    free_heap_bytes: stats.free_heap_bytes as u64,
+                    vmo_bytes,
+                    ..Default::default()
+                };
+                write_record(kmem, buffer, buffer_size, actual, avail)?;
             }
-            Topic::JobProcess => {
-                let job = proc.get_object_with_rights::<Job>(handle, Rights::ENUMERATE)?;
-                let (mut count, mut avail_count) = (0usize, 0usize);
-                let ptr = UserOutPtr::<KoID>::from(buffer).as_ptr();
-                let item_size = core::mem::size_of::<KoID>();
-                job.enumerate_process(|id| {
-                    if count < buffer_size / item_size {
-                        unsafe {
-                            ptr.add(count).write(id);
+            Topic::SignalObservers => {
+                if !cfg!(debug_assertions) {
+                    return Err(ZxError::NOT_SUPPORTED);
+                }
+                let object = proc.get_dyn_object_with_rights(handle, Rights::INSPECT)?;
+                let count = object.signal_callback_count() as u64;
+                write_record(count, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::KernelCounters => {
+                proc.validate_resource(handle, ResourceKind::ROOT)?;
+                let counters: Vec<KCounterInfo> = KCounterDescriptorArray::get()
+                    .0
+                    .iter()
+                    .map(KCounterInfo::from)
+                    .collect();
+                write_records(&counters, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::KmemFragmentation => {
+                proc.validate_resource(handle, ResourceKind::ROOT)?;
+                let report = kernel_hal::frame_fragmentation();
+                write_record(report, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::CpuStats => {
+                let cpu_num = (kernel_hal::vdso_constants().max_num_cpus as usize)
+                    .min(kernel_hal::MAX_CPU_NUM);
+                let stats: Vec<ZxInfoCpuStats> = (0..cpu_num)
+                    .map(|cpu| {
+                        let stats = kernel_hal::CpuStats::get(cpu).unwrap().snapshot();
+                        ZxInfoCpuStats {
+                            cpu_number: cpu as u32,
+                            flags: CPU_STATS_FLAG_ONLINE,
+                            idle_time: stats.idle_time.as_nanos() as i64,
+                            reschedules: stats.reschedules,
+                            context_switches: stats.context_switches,
+                            ints: stats.interrupts,
+                            syscalls: stats.syscalls,
+                            ..Default::default()
                         }
-                        count += 1;
-                    }
-                    avail_count += 1;
-                    true
-                });
-                actual.write(count)?;
-                avail.write(avail_count)?;
+                    })
+                    .collect();
+                write_records(&stats, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::JobProcess => {
+                let mut processes = Vec::new();
+                proc.get_object_with_rights::<Job>(handle, Rights::ENUMERATE)?
+                    .enumerate_process(|id| {
+                        processes.push(id);
+                        true
+                    });
+                write_records(&processes, buffer, buffer_size, actual, avail)?;
             }
             Topic::TaskStats => {
-                assert_eq!(core::mem::size_of::<ZxInfoTaskStats>(), buffer_size);
-                let vmar = proc
+                let task_stats = proc
                     .get_object_with_rights::<Process>(handle, Rights::INSPECT)?
-                    .vmar();
-                //let mut task_stats = ZxInfoTaskStats::default();
-                let task_stats = vmar.get_task_stats();
-                UserOutPtr::<ZxInfoTaskStats>::from(buffer).write(task_stats)?;
+                    .vmar()
+                    .get_task_stats();
+                write_record(task_stats, buffer, buffer_size, actual, avail)?;
             }
             Topic::ProcessThreads => {
-                let (mut count, mut avail_count) = (0usize, 0usize);
-                let ptr = UserOutPtr::<KoID>::from(buffer).as_ptr();
-                let item_size = core::mem::size_of::<KoID>();
-                proc.get_object_with_rights::<Process>(handle, Rights::ENUMERATE)?
-                    .enumerate_thread(|id| {
-                        if count < buffer_size / item_size {
-                            unsafe {
-                                ptr.add(count).write(id);
-                            }
-                            count += 1;
-                        }
-                        avail_count += 1;
-                        true
-                    });
-                actual.write(count)?;
-                avail.write(avail_count)?;
+                // only copy what fits in the buffer, the lock is not held while writing
+                let (threads, count) = proc
+                    .get_object_with_rights::<Process>(handle, Rights::ENUMERATE)?
+                    .thread_ids(buffer_size / core::mem::size_of::<KoID>());
+                write_capped_records(&threads, count, buffer, actual, avail)?;
+            }
+            Topic::ProcessMaps => {
+                let maps = proc
+                    .get_object_with_rights::<Process>(handle, Rights::INSPECT)?
+                    .vmar()
+                    .get_maps();
+                write_records(&maps, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::VmarMaps => {
+                let maps = proc
+                    .get_object_with_rights::<VmAddressRegion>(handle, Rights::INSPECT)?
+                    .get_maps();
+                write_records(&maps, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::ProcessVmos => {
+                let vmos = proc
+                    .get_object_with_rights::<Process>(handle, Rights::INSPECT)?
+                    .get_vmos_info();
+                write_records(&vmos, buffer, buffer_size, actual, avail)?;
+            }
+            Topic::Socket => {
+                let socket = proc.get_object_with_rights::<Socket>(handle, Rights::INSPECT)?;
+                write_record(socket.get_info(), buffer, buffer_size, actual, avail)?;
+            }
+            Topic::Timer => {
+                let timer = proc.get_object_with_rights::<Timer>(handle, Rights::INSPECT)?;
+                write_record(timer.get_info(), buffer, buffer_size, actual, avail)?;
+            }
+            Topic::Stream => {
+                let stream = proc.get_object_with_rights::<Stream>(handle, Rights::INSPECT)?;
+                write_record(stream.get_info(), buffer, buffer_size, actual, avail)?;
+            }
+            Topic::Bti => {
+                let bti = proc.get_object_with_rights::<Bti>(handle, Rights::INSPECT)?;
+                write_record(bti.get_info(), buffer, buffer_size, actual, avail)?;
+            }
+            Topic::Resource => {
+                let resource = proc.get_object_with_rights::<Resource>(handle, Rights::INSPECT)?;
+                write_record(resource.get_info(), buffer, buffer_size, actual, avail)?;
             }
             Topic::JobChildren => {
-                let (mut count, mut avail_count) = (0usize, 0usize);
-                let ptr = UserOutPtr::<KoID>::from(buffer).as_ptr();
-                let item_size = core::mem::size_of::<KoID>();
+                let mut children = Vec::new();
                 proc.get_object_with_rights::<Job>(handle, Rights::ENUMERATE)?
                     .enumerate_children(|id| {
-                        if count < buffer_size / item_size {
-                            unsafe {
-                                ptr.add(count).write(id);
-                            }
-                            count += 1;
-                        }
-                        avail_count += 1;
+                        children.push(id);
                         true
                     });
-                actual.write(count)?;
-                avail.write(avail_count)?;
+                write_records(&children, buffer, buffer_size, actual, avail)?;
             }
             _ => {
                 error!("not supported info topic: {:?}", topic);
@@ -316,6 +604,26 @@ impl Syscall<'_> {
         Ok(())
     }
 
+    pub fn sys_object_set_profile(
+        &self,
+        handle: HandleValue,
+        profile: HandleValue,
+        options: u32,
+    ) -> ZxResult {
+        info!(
+            "object.set_profile: handle={:#x}, profile={:#x}, options={:#x}",
+            handle, profile, options
+        );
+        if options != 0 {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let proc = self.thread.proc();
+        let thread = proc.get_object_with_rights::<Thread>(handle, Rights::MANAGE_THREAD)?;
+        let profile = proc.get_object_with_rights::<Profile>(profile, Rights::APPLY_PROFILE)?;
+        thread.set_profile(&profile);
+        Ok(())
+    }
+
     pub fn sys_object_wait_async(
         &self,
         handle_value: HandleValue,
@@ -329,14 +637,12 @@ impl Syscall<'_> {
             "object.wait_async: handle={:#x}, port={:#x}, key={:#x}, signal={:?}, options={:#X}",
             handle_value, port_handle_value, key, signals, options
         );
-        if options != 0 {
-            unimplemented!()
-        }
-        // TODO filter `options`
+        let options = WaitAsyncOptions::from_bits(options).ok_or(ZxError::INVALID_ARGS)?;
         let proc = self.thread.proc();
         let object = proc.get_dyn_object_with_rights(handle_value, Rights::WAIT)?;
         let port = proc.get_object_with_rights::<Port>(port_handle_value, Rights::WRITE)?;
-        object.send_signal_to_port_async(signals, &port, key);
+        let cancel_token = proc.get_cancel_token(handle_value)?;
+        object.cancelable_send_signal_to_port_async(signals, &port, key, options, cancel_token);
         Ok(())
     }
 
@@ -381,12 +687,17 @@ impl Syscall<'_> {
         let res = self
             .thread
             .blocking_run(future, ThreadState::BlockedWaitMany, deadline.into())
-            .await?;
-        for (i, item) in items.iter_mut().enumerate() {
-            item.observed = res[i];
+            .await;
+        // the observed signals are reported even if the wait timed out
+        let observed = match &res {
+            Ok(signals) => signals.clone(),
+            Err(_) => waiters.iter().map(|(object, _)| object.signal()).collect(),
+        };
+        for (item, signal) in items.iter_mut().zip(observed) {
+            item.observed = signal;
         }
         user_items.write_array(&items)?;
-        Ok(())
+        res.map(|_| ())
     }
 
     pub fn sys_object_get_child(
@@ -400,17 +711,13 @@ impl Syscall<'_> {
             "object.get_child: handle={:#x}, koid={:#x}, rights={:#x}",
             handle, koid, rights
         );
-        let mut rights = Rights::from_bits(rights).ok_or(ZxError::INVALID_ARGS)?;
+        let rights = Rights::from_bits(rights).ok_or(ZxError::INVALID_ARGS)?;
         let proc = self.thread.proc();
         let (task, parent_rights) = proc.get_dyn_object_and_rights(handle)?;
         if !parent_rights.contains(Rights::ENUMERATE) {
             return Err(ZxError::ACCESS_DENIED);
         }
-        if rights == Rights::SAME_RIGHTS {
-            rights = parent_rights;
-        } else if (rights & parent_rights) != rights {
-            return Err(ZxError::ACCESS_DENIED);
-        }
+        let rights = reduce_rights(parent_rights, rights)?;
         let child = task.get_child(koid)?;
         let child_handle = proc.add_handle(Handle::new(child, rights));
         out.write(child_handle)?;
@@ -418,6 +725,52 @@ impl Syscall<'_> {
     }
 }
 
+/// Write a single info record to the user buffer, and report one record
+/// written (`actual`) and available (`avail`).
+fn write_record<T>(
+    record: T,
+    buffer: usize,
+    buffer_size: usize,
+    mut actual: UserOutPtr<usize>,
+    mut avail: UserOutPtr<usize>,
+) -> ZxResult {
+    if buffer_size < core::mem::size_of::<T>() {
+        return Err(ZxError::BUFFER_TOO_SMALL);
+    }
+    UserOutPtr::<T>::from(buffer).write(record)?;
+    actual.write_if_not_null(1)?;
+    avail.write_if_not_null(1)?;
+    Ok(())
+}
+
+/// Write as many records as fit in the user buffer, and report how many
+/// were written (`actual`) and how many exist (`avail`).
+pub(super) fn write_records<T>(
+    records: &[T],
+    buffer: usize,
+    buffer_size: usize,
+    actual: UserOutPtr<usize>,
+    avail: UserOutPtr<usize>,
+) -> ZxResult {
+    let count = records.len().min(buffer_size / core::mem::size_of::<T>());
+    write_capped_records(&records[..count], records.len(), buffer, actual, avail)
+}
+
+/// Like `write_records`, for records already capped to the buffer size out
+/// of `avail_count` ones.
+fn write_capped_records<T>(
+    records: &[T],
+    avail_count: usize,
+    buffer: usize,
+    mut actual: UserOutPtr<usize>,
+    mut avail: UserOutPtr<usize>,
+) -> ZxResult {
+    UserOutPtr::<T>::from(buffer).write_array(records)?;
+    actual.write_if_not_null(records.len())?;
+    avail.write_if_not_null(avail_count)?;
+    Ok(())
+}
+
 numeric_enum! {
     #[repr(u32)]
     #[derive(Debug)]
@@ -447,6 +800,24 @@ numeric_enum! {
         Job = 24,
         Timer = 26,
         Stream = 27,
+        HandleTable = 31,
+        /// zCore specific: the committed bytes of a VMO as a `u64`,
+        /// requires `Rights::GET_PROPERTY` instead of `Rights::INSPECT`.
+        VmoCommittedBytes = 0x1000,
+        /// zCore specific: the free runs of physical frames as a
+        /// `kernel_hal::FragReport`. It scans all frames, so it is for debugging only.
+        KmemFragmentation = 0x1001,
+        /// zCore specific, debug builds only: the number of signal observers
+        /// of an object as a `u64`, to find async waits that are never canceled.
+        SignalObservers = 0x1002,
+        /// zCore specific: kcounters as `KCounterInfo` records, on the root resource.
+        KernelCounters = 0x1003,
+        /// zCore specific: the tree rooted at a VMAR as `ZxInfoMaps` records.
+        /// See `sys_vmar_get_maps` to limit the depth of the tree.
+        VmarMaps = 0x1004,
+        /// zCore specific: `VmarInfoFlags` of a VMAR as a `u32`, e.g. whether
+        /// it is the root of an address space.
+        VmarFlags = 0x1005,
     }
 }
 
@@ -454,15 +825,23 @@ numeric_enum! {
     #[repr(u32)]
     #[derive(Debug)]
     enum Property {
+        RegisterGs = 2,
         Name = 3,
         RegisterFs = 4,
         ProcessDebugAddr = 5,
         ProcessVdsoBaseAddress = 6,
         ProcessBreakOnLoad = 7,
+        SocketRxThreshold = 12,
+        SocketTxThreshold = 13,
+        JobKillOnOom = 15,
+        VmoContentSize = 17,
+        StreamModeAppend = 19,
+        /// zCore specific: the committed bytes at which the VMO asserts
+        /// `VMO_COMMIT_THRESHOLD`, as a `u64`. Zero disables it.
+        VmoCommitThreshold = 0x1000,
     }
 }
 
-const MAX_NAME_LEN: u32 = 32;
 const MAX_WAIT_MANY_ITEMS: u32 = 32;
 
 #[derive(Debug)]
@@ -473,6 +852,29 @@ pub struct UserWaitItem {
     observed: Signal,
 }
 
+const CPU_STATS_FLAG_ONLINE: u32 = 1;
+
+#[repr(C)]
+#[derive(Default)]
+struct ZxInfoCpuStats {
+    cpu_number: u32,
+    flags: u32,
+    idle_time: i64,
+    reschedules: u64,
+    context_switches: u64,
+    irq_preempts: u64,
+    preempts: u64,
+    yields: u64,
+    ints: u64,
+    timer_ints: u64,
+    timers: u64,
+    page_faults: u64,
+    exceptions: u64,
+    syscalls: u64,
+    reschedule_ipis: u64,
+    generic_ipis: u64,
+}
+
 #[repr(C)]
 #[derive(Default)]
 struct ZxInfoKmem {
@@ -486,3 +888,189 @@ struct ZxInfoKmem {
     ipc_bytes: u64,
     other_bytes: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use core::mem::size_of;
+
+    /// Create a thread in a new process, with a handle to it in the process.
+    fn new_thread() -> (Arc<Thread>, HandleValue) {
+        let proc = Process::create(&Job::root(), "proc", 0).unwrap();
+        let thread = Thread::create(&proc, "thread", 0).unwrap();
+        let handle = proc.add_handle(Handle::new(thread.clone(), Rights::DEFAULT_THREAD));
+        (thread, handle)
+    }
+
+    #[test]
+    fn get_name_property() {
+        let (thread, handle) = new_thread();
+        let syscall = syscall_for(thread);
+        let get_name = |ptr: usize| {
+            syscall.sys_object_get_property(handle, Property::Name as u32, ptr, MAX_NAME_LEN as u32)
+        };
+        let mut name = [0xffu8; MAX_NAME_LEN];
+        get_name(name.as_mut_ptr() as usize).unwrap();
+        assert_eq!(&name[..7], b"thread\0");
+        // a bad buffer fails the syscall instead of panicking
+        assert_eq!(get_name(0), Err(ZxError::INVALID_ARGS));
+    }
+
+    #[test]
+    fn set_fs_property() {
+        let (thread, handle) = new_thread();
+        let proc = thread.proc();
+        let other = Thread::create(&proc, "other", 0).unwrap();
+        let other = proc.add_handle(Handle::new(other, Rights::DEFAULT_THREAD));
+        let mut syscall = syscall_for(thread);
+        let fsbase = 0x1000u64;
+        let ptr = &fsbase as *const u64 as usize;
+        let fs = Property::RegisterFs as u32;
+        syscall.sys_object_set_property(handle, fs, ptr, 8).unwrap();
+        assert_eq!(syscall.regs.fsbase, 0x1000);
+        // only the current thread can set its own registers
+        assert_eq!(
+            syscall.sys_object_set_property(other, fs, ptr, 8),
+            Err(ZxError::ACCESS_DENIED)
+        );
+    }
+
+    #[test]
+    fn set_gs_property() {
+        let (thread, handle) = new_thread();
+        let proc = thread.proc();
+        let other = Thread::create(&proc, "other", 0).unwrap();
+        let other = proc.add_handle(Handle::new(other, Rights::DEFAULT_THREAD));
+        let mut syscall = syscall_for(thread);
+        let gsbase = 0x2000u64;
+        let ptr = &gsbase as *const u64 as usize;
+        let gs = Property::RegisterGs as u32;
+        assert_eq!(
+            syscall.sys_object_set_property(handle, gs, ptr, 4),
+            Err(ZxError::BUFFER_TOO_SMALL)
+        );
+        syscall.sys_object_set_property(handle, gs, ptr, 8).unwrap();
+        assert_eq!(syscall.regs.gsbase, 0x2000);
+        assert_eq!(
+            syscall.sys_object_set_property(other, gs, ptr, 8),
+            Err(ZxError::ACCESS_DENIED)
+        );
+    }
+
+    #[test]
+    fn wait_one_asserted() {
+        use futures::FutureExt;
+        use zircon_object::signal::Event;
+
+        let (thread, _) = new_thread();
+        let event = Event::new();
+        let handle = thread
+            .proc()
+            .add_handle(Handle::new(event.clone(), Rights::DEFAULT_EVENT));
+        let syscall = syscall_for(thread);
+        let mut observed = Signal::empty();
+        let observed_ptr = UserOutPtr::from(&mut observed as *mut Signal as usize);
+        let signals = Signal::USER_SIGNAL_0.bits();
+
+        // not asserted, and the deadline has passed
+        let ret = syscall
+            .sys_object_wait_one(handle, signals, Deadline::from(0), observed_ptr)
+            .now_or_never();
+        assert_eq!(ret, Some(Err(ZxError::TIMED_OUT)));
+
+        // already asserted: done on the first poll, even if it could wait forever
+        event.signal_set(Signal::USER_SIGNAL_0);
+        let observed_ptr = UserOutPtr::from(&mut observed as *mut Signal as usize);
+        let ret = syscall
+            .sys_object_wait_one(handle, signals, Deadline::forever(), observed_ptr)
+            .now_or_never();
+        assert_eq!(ret, Some(Ok(())));
+        assert!(observed.contains(Signal::USER_SIGNAL_0));
+    }
+
+    #[test]
+    fn kernel_counters() {
+        use futures::FutureExt;
+        use zircon_object::resource::ResourceFlags;
+        use zircon_object::util::kcounter::KCounterDescriptor;
+
+        // the linker does not collect kcounters in libos
+        static DESCRIPTORS: [KCounterDescriptor; 1] = [KCounterDescriptor {
+            counter: &SYSCALLS,
+            name: "syscall.dispatched",
+        }];
+        KCounterDescriptorArray::register(&DESCRIPTORS);
+
+        let (thread, _) = new_thread();
+        let proc = thread.proc();
+        let resource = Resource::create("root", ResourceKind::ROOT, 0, 0, ResourceFlags::empty());
+        let root = proc.add_handle(Handle::new(resource, Rights::DEFAULT_RESOURCE));
+        let mut syscall = syscall_for(thread);
+        let dispatched = |syscall: &Syscall| -> u64 {
+            let mut info = KCounterInfo {
+                name: [0; 56],
+                value: 0,
+            };
+            let mut actual = 0usize;
+            syscall
+                .sys_object_get_info(
+                    root,
+                    Topic::KernelCounters as u32,
+                    &mut info as *mut KCounterInfo as usize,
+                    size_of::<KCounterInfo>(),
+                    UserOutPtr::from(&mut actual as *mut usize as usize),
+                    UserOutPtr::from(0),
+                )
+                .unwrap();
+            assert_eq!(actual, 1);
+            assert_eq!(&info.name[..18], b"syscall.dispatched");
+            info.value
+        };
+
+        let before = dispatched(&syscall);
+        let close = Sys::HANDLE_CLOSE as u32;
+        for _ in 0..10 {
+            let args = [INVALID_HANDLE as usize, 0, 0, 0, 0, 0, 0, 0];
+            syscall.syscall(close, args).now_or_never().unwrap();
+        }
+        // other tests may dispatch syscalls at the same time
+        assert!(dispatched(&syscall) >= before + 10);
+    }
+
+    #[test]
+    fn process_threads() {
+        let (thread, _) = new_thread();
+        let proc = thread.proc();
+        let others: Vec<_> = (0..2)
+            .map(|_| Thread::create(&proc, "other", 0).unwrap())
+            .collect();
+        let handle = proc.add_handle(Handle::new(proc.clone(), Rights::DEFAULT_PROCESS));
+        let syscall = syscall_for(thread.clone());
+        let get_threads = |buffer: &mut [KoID]| -> (usize, usize) {
+            let (mut actual, mut avail) = (0usize, 0usize);
+            syscall
+                .sys_object_get_info(
+                    handle,
+                    Topic::ProcessThreads as u32,
+                    buffer.as_mut_ptr() as usize,
+                    buffer.len() * size_of::<KoID>(),
+                    UserOutPtr::from(&mut actual as *mut usize as usize),
+                    UserOutPtr::from(&mut avail as *mut usize as usize),
+                )
+                .unwrap();
+            (actual, avail)
+        };
+        let ids = [thread.id(), others[0].id(), others[1].id()];
+
+        let mut buffer = [0 as KoID; 4];
+        assert_eq!(get_threads(&mut buffer), (3, 3));
+        assert!(ids.iter().all(|id| buffer[..3].contains(id)));
+        // the records are truncated to the buffer, but all of them are available
+        let mut buffer = [0 as KoID; 4];
+        assert_eq!(get_threads(&mut buffer[..2]), (2, 3));
+        assert!(buffer[..2].iter().all(|id| ids.contains(id)));
+        assert_eq!(buffer[2], 0);
+        assert_eq!(get_threads(&mut []), (0, 3));
+    }
+}
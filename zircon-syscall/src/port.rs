@@ -54,4 +54,22 @@ impl Syscall<'_> {
         port.push(packet);
         Ok(())
     }
+
+    /// Cancel the pending async waits on `source` with `key`,
+    /// and remove their packets from the port.
+    pub fn sys_port_cancel(
+        &self,
+        handle_value: HandleValue,
+        source: HandleValue,
+        key: u64,
+    ) -> ZxResult {
+        info!(
+            "port.cancel: handle={:#x}, source={:#x}, key={:#x}",
+            handle_value, source, key
+        );
+        let proc = self.thread.proc();
+        let port = proc.get_object_with_rights::<Port>(handle_value, Rights::WRITE)?;
+        let object = proc.get_dyn_object_with_rights(source, Rights::WAIT)?;
+        port.cancel(object.id(), key)
+    }
 }
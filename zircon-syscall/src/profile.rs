@@ -0,0 +1,27 @@
+use {super::*, zircon_object::task::*};
+
+impl Syscall<'_> {
+    pub fn sys_profile_create(
+        &self,
+        root_job: HandleValue,
+        options: u32,
+        info: UserInPtr<ProfileInfo>,
+        mut out: UserOutPtr<HandleValue>,
+    ) -> ZxResult {
+        info!(
+            "profile.create: root_job={:#x}, options={:#x}",
+            root_job, options
+        );
+        if options != 0 {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let proc = self.thread.proc();
+        proc.check_policy(PolicyCondition::NewProfile)?;
+        proc.get_object_with_rights::<Job>(root_job, Rights::MANAGE_PROCESS)?
+            .check_root_job()?;
+        let profile = Profile::create(info.read()?)?;
+        let handle_value = proc.add_handle(Handle::new(profile, Rights::DEFAULT_PROFILE));
+        out.write(handle_value)?;
+        Ok(())
+    }
+}
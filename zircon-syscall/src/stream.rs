@@ -0,0 +1,55 @@
+use {super::*, core::convert::TryFrom, zircon_object::vm::*};
+
+impl Syscall<'_> {
+    pub fn sys_stream_create(
+        &self,
+        options: u32,
+        vmo_handle: HandleValue,
+        seek: usize,
+        mut out: UserOutPtr<HandleValue>,
+    ) -> ZxResult {
+        info!(
+            "stream.create: options={:#x?}, vmo_handle = {:#x?}, seek={:#x?}",
+            options, vmo_handle, seek
+        );
+        let options = StreamOptions::from_bits(options).ok_or(ZxError::INVALID_ARGS)?;
+        let mut vmo_rights = Rights::empty();
+        let mut rights = Rights::DEFAULT_STREAM;
+        if options.contains(StreamOptions::MODE_READ) {
+            vmo_rights |= Rights::READ;
+            rights |= Rights::READ;
+        }
+        if options.contains(StreamOptions::MODE_WRITE) {
+            vmo_rights |= Rights::WRITE;
+            rights |= Rights::WRITE;
+        }
+        let proc = self.thread.proc();
+        let vmo = proc.get_object_with_rights::<VmObject>(vmo_handle, vmo_rights)?;
+        let stream = Stream::create(options, vmo, seek);
+        let handle_value = proc.add_handle(Handle::new(stream, rights));
+        out.write(handle_value)?;
+        Ok(())
+    }
+
+    pub fn sys_stream_seek(
+        &self,
+        handle_value: HandleValue,
+        whence: u32,
+        offset: i64,
+        mut out_seek: UserOutPtr<usize>,
+    ) -> ZxResult {
+        info!(
+            "stream.seek: handle={:#x?}, whence={:#x?}, offset={:#x?}",
+            handle_value, whence, offset
+        );
+        let proc = self.thread.proc();
+        let (stream, rights) = proc.get_object_and_rights::<Stream>(handle_value)?;
+        if !rights.intersects(Rights::READ | Rights::WRITE) {
+            return Err(ZxError::ACCESS_DENIED);
+        }
+        let whence = SeekOrigin::try_from(whence).map_err(|_| ZxError::INVALID_ARGS)?;
+        let seek = stream.seek(whence, offset as isize)?;
+        out_seek.write_if_not_null(seek)?;
+        Ok(())
+    }
+}
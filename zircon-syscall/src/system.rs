@@ -2,7 +2,10 @@
 use {
     super::*,
     alloc::boxed::Box,
-    zircon_object::{signal::Event, task::Job},
+    zircon_object::{
+        signal::{Event, MemoryPressureEvent, PressureLevel},
+        task::Job,
+    },
 };
 
 impl Syscall<'_> {
@@ -29,6 +32,23 @@ impl Syscall<'_> {
                 out.write(event_handle)?;
                 Ok(())
             }
+            EVENT_MEMORY_PRESSURE_CRITICAL
+            | EVENT_MEMORY_PRESSURE_WARNING
+            | EVENT_MEMORY_PRESSURE_NORMAL => {
+                let proc = self.thread.proc();
+                proc.get_object_with_rights::<Job>(root_job, Rights::MANAGE_PROCESS)?
+                    .check_root_job()?;
+                let level = match kind {
+                    EVENT_MEMORY_PRESSURE_CRITICAL => PressureLevel::Critical,
+                    EVENT_MEMORY_PRESSURE_WARNING => PressureLevel::Warning,
+                    _ => PressureLevel::Normal,
+                };
+                let event = MemoryPressureEvent::get().event(level);
+                let event_handle =
+                    proc.add_handle(Handle::new(event, Rights::DEFAULT_SYSTEM_EVENT_LOW_MEMORY));
+                out.write(event_handle)?;
+                Ok(())
+            }
             _ => unimplemented!(),
         }
     }
@@ -98,6 +98,26 @@ impl Syscall<'_> {
         }
     }
 
+    pub fn sys_thread_read_state(
+        &self,
+        handle: HandleValue,
+        kind: u32,
+        mut buffer: UserOutPtr<u8>,
+        buffer_size: usize,
+    ) -> ZxResult {
+        let kind = ThreadStateKind::try_from(kind).map_err(|_| ZxError::INVALID_ARGS)?;
+        info!(
+            "thread.read_state: handle={:#x?}, kind={:#x?}, buf=({:#x?}; {:#x?})",
+            handle, kind, buffer, buffer_size,
+        );
+        let proc = self.thread.proc();
+        let thread = proc.get_object_with_rights::<Thread>(handle, Rights::READ)?;
+        let mut buf = vec![0; buffer_size.min(MAX_STATE_SIZE)];
+        let len = thread.read_state(kind, &mut buf)?;
+        buffer.write_array(&buf[..len])?;
+        Ok(())
+    }
+
     pub fn sys_thread_write_state(
         &self,
         handle: HandleValue,
@@ -112,6 +132,10 @@ impl Syscall<'_> {
         );
         let proc = self.thread.proc();
         let thread = proc.get_object_with_rights::<Thread>(handle, Rights::WRITE)?;
+        // no state is larger, so don't copy in an oversized buffer
+        if buffer_size > MAX_STATE_SIZE {
+            return Err(ZxError::INVALID_ARGS);
+        }
         let buf = buffer.read_array(buffer_size)?;
         thread.write_state(kind, &buf)?;
         Ok(())
@@ -173,21 +197,22 @@ impl Syscall<'_> {
     ) -> ZxResult {
         info!("task.suspend_token: handle={:?}, token={:?}", handle, token);
         let proc = self.thread.proc();
-        if let Ok(thread) = proc.get_object_with_rights::<Thread>(handle, Rights::WRITE) {
+        let task = proc.get_dyn_object_with_rights(handle, Rights::WRITE)?;
+        let suspend_token = if let Ok(thread) = task.clone().downcast_arc::<Thread>() {
             if Arc::ptr_eq(&thread, &self.thread) {
                 return Err(ZxError::NOT_SUPPORTED);
             }
-            let token_handle =
-                Handle::new(SuspendToken::create(&thread), Rights::DEFAULT_SUSPEND_TOKEN);
-            token.write(proc.add_handle(token_handle))?;
-            return Ok(());
-        }
-        if let Ok(process) = proc.get_object_with_rights::<Process>(handle, Rights::WRITE) {
+            SuspendToken::create(&thread)
+        } else if let Ok(process) = task.downcast_arc::<Process>() {
             if Arc::ptr_eq(&process, &proc) {
                 return Err(ZxError::NOT_SUPPORTED);
             }
-            unimplemented!()
-        }
+            SuspendToken::create_for_process(&process)
+        } else {
+            return Err(ZxError::WRONG_TYPE);
+        };
+        let token_handle = Handle::new(suspend_token, Rights::DEFAULT_SUSPEND_TOKEN);
+        token.write(proc.add_handle(token_handle))?;
         Ok(())
     }
 
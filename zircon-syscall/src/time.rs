@@ -5,7 +5,7 @@ use {
         sync::atomic::{AtomicU64, Ordering},
         time::Duration,
     },
-    kernel_hal::{sleep_until, timer_now, yield_now},
+    kernel_hal::{clock_monotonic, timer_ticks, vdso_constants, yield_now},
     zircon_object::{resource::*, task::*},
 };
 
@@ -20,11 +20,13 @@ impl Syscall<'_> {
         info!("clock.get: id={}", clock_id);
         match clock_id {
             ZX_CLOCK_MONOTONIC => {
-                time.write(timer_now().as_nanos() as u64)?;
+                time.write(clock_monotonic().as_nanos() as u64)?;
                 Ok(())
             }
             ZX_CLOCK_UTC => {
-                time.write(timer_now().as_nanos() as u64 + UTC_OFFSET.load(Ordering::Relaxed))?;
+                time.write(
+                    clock_monotonic().as_nanos() as u64 + UTC_OFFSET.load(Ordering::Relaxed),
+                )?;
                 Ok(())
             }
             ZX_CLOCK_THREAD => unimplemented!(),
@@ -32,6 +34,21 @@ impl Syscall<'_> {
         }
     }
 
+    /// Get the monotonic time in nanoseconds.
+    pub fn sys_clock_get_monotonic(&self) -> ZxResult<u64> {
+        Ok(clock_monotonic().as_nanos() as u64)
+    }
+
+    /// Get the raw ticks of the high-resolution timer.
+    pub fn sys_ticks_get(&self) -> ZxResult<u64> {
+        Ok(timer_ticks())
+    }
+
+    /// Get the number of ticks in a second.
+    pub fn sys_ticks_per_second(&self) -> ZxResult<u64> {
+        Ok(vdso_constants().ticks_per_second)
+    }
+
     pub fn sys_clock_adjust(&self, hrsrc: HandleValue, clock_id: u32, offset: u64) -> ZxResult {
         info!(
             "clock.adjust: hrsrc={:#x?}, id={:#x}, offset={:#x}",
@@ -55,14 +72,7 @@ impl Syscall<'_> {
         if deadline.0 <= 0 {
             yield_now().await;
         } else {
-            self.thread
-                .blocking_run(
-                    sleep_until(deadline.into()),
-                    ThreadState::BlockedSleeping,
-                    Duration::from_nanos(u64::max_value()),
-                )
-                .await
-                .unwrap();
+            self.thread.sleep_until(deadline.into()).await?;
         }
         Ok(())
     }
@@ -73,11 +83,19 @@ pub struct Deadline(i64);
 
 impl From<usize> for Deadline {
     fn from(x: usize) -> Self {
+        // Treat an all-ones deadline as `ZX_TIME_INFINITE` rather than -1.
+        if x == usize::max_value() {
+            return Deadline::forever();
+        }
         Deadline(x as i64)
     }
 }
 
 impl Deadline {
+    pub fn forever() -> Self {
+        Deadline(i64::max_value())
+    }
+
     pub fn is_positive(&self) -> bool {
         self.0.is_positive()
     }
@@ -127,8 +127,30 @@ impl Syscall<'_> {
         );
         let len = pages(len) * PAGE_SIZE;
         let overwrite = options.contains(VmOptions::SPECIFIC_OVERWRITE);
-        let map_range = options.contains(VmOptions::MAP_RANGE);
-        let vaddr = if is_specific {
+        // Without page fault handling, e.g. in the libos, user mappings are
+        // always populated, as if `MAP_RANGE` is given.
+        let map_range =
+            options.contains(VmOptions::MAP_RANGE) || !kernel_hal::user_page_fault_supported();
+        let vaddr = if options.contains(VmOptions::GUARD) {
+            if overwrite {
+                return Err(ZxError::INVALID_ARGS);
+            }
+            let region_offset = if is_specific {
+                let offset = vmar_offset.checked_sub(PAGE_SIZE);
+                Some(offset.ok_or(ZxError::INVALID_ARGS)?)
+            } else {
+                None
+            };
+            vmar.map_with_guard(
+                region_offset,
+                vmo,
+                vmo_offset,
+                len,
+                mapping_flags,
+                PAGE_SIZE,
+                map_range,
+            )?
+        } else if is_specific {
             vmar.map_at_ext(
                 vmar_offset,
                 vmo,
@@ -162,26 +184,37 @@ impl Syscall<'_> {
         Ok(())
     }
 
+    /// Change the permissions of mappings in `[addr, addr + len)`.
     pub fn sys_vmar_protect(
         &self,
         handle_value: HandleValue,
         options: u32,
-        addr: u64,
-        len: u64,
+        addr: usize,
+        len: usize,
     ) -> ZxResult {
         let options = VmOptions::from_bits(options).ok_or(ZxError::INVALID_ARGS)?;
-        let rights = options.to_rights();
         info!(
-            "vmar.protect: handle={:#x}, options={:#x}, addr={:#x}, len={:#x}",
+            "vmar.protect: handle={:#x}, options={:?}, addr={:#x}, len={:#x}",
             handle_value, options, addr, len
         );
+        let perms = VmOptions::PERM_READ | VmOptions::PERM_WRITE | VmOptions::PERM_EXECUTE;
+        if !perms.contains(options) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        if options.contains(VmOptions::PERM_WRITE) && !options.contains(VmOptions::PERM_READ) {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let mut rights = Rights::empty();
+        rights.set(Rights::READ, options.contains(VmOptions::PERM_READ));
+        rights.set(Rights::WRITE, options.contains(VmOptions::PERM_WRITE));
+        rights.set(Rights::EXECUTE, options.contains(VmOptions::PERM_EXECUTE));
         let proc = self.thread.proc();
         let vmar = proc.get_object_with_rights::<VmAddressRegion>(handle_value, rights)?;
         let mut mapping_flags = MMUFlags::empty();
         mapping_flags.set(MMUFlags::READ, options.contains(VmOptions::PERM_READ));
         mapping_flags.set(MMUFlags::WRITE, options.contains(VmOptions::PERM_WRITE));
         mapping_flags.set(MMUFlags::EXECUTE, options.contains(VmOptions::PERM_EXECUTE));
-        vmar.protect(addr as usize, len as usize, mapping_flags)?;
+        vmar.protect(addr, pages(len) * PAGE_SIZE, mapping_flags)?;
         Ok(())
     }
 
@@ -195,6 +228,35 @@ impl Syscall<'_> {
         vmar.unmap(addr, pages(len) * PAGE_SIZE)?;
         Ok(())
     }
+
+    /// zCore specific: the tree rooted at a VMAR as `ZxInfoMaps` records, like
+    /// `ZX_INFO_VMAR_MAPS`, down to `max_depth` levels below the VMAR.
+    ///
+    /// A `max_depth` of 0 means no limit. `avail` counts the records within
+    /// the depth.
+    pub fn sys_vmar_get_maps(
+        &self,
+        handle_value: HandleValue,
+        max_depth: u32,
+        buffer: usize,
+        buffer_size: usize,
+        actual: UserOutPtr<usize>,
+        avail: UserOutPtr<usize>,
+    ) -> ZxResult {
+        info!(
+            "vmar.get_maps: handle_value={:#x}, max_depth={}, buffer=({:#x}; {:#x})",
+            handle_value, max_depth, buffer, buffer_size
+        );
+        let max_depth = match max_depth {
+            0 => usize::MAX,
+            depth => depth as usize,
+        };
+        let proc = self.thread.proc();
+        let maps = proc
+            .get_object_with_rights::<VmAddressRegion>(handle_value, Rights::INSPECT)?
+            .get_maps_with_depth(max_depth);
+        super::object::write_records(&maps, buffer, buffer_size, actual, avail)
+    }
 }
 
 bitflags! {
@@ -213,6 +275,8 @@ bitflags! {
         const MAP_RANGE             = 1 << 10;
         const REQUIRE_NON_RESIZABLE = 1 << 11;
         const ALLOW_FAULTS          = 1 << 12;
+        /// zCore specific: leave an unmapped guard page below the mapping, e.g. for a stack.
+        const GUARD                 = 1 << 30;
         const CAN_MAP_RXW           = Self::CAN_MAP_READ.bits | Self::CAN_MAP_EXECUTE.bits | Self::CAN_MAP_WRITE.bits;
     }
 }
@@ -264,3 +328,46 @@ impl VmOptions {
         flags
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use core::mem::size_of;
+    use zircon_object::task::*;
+
+    #[test]
+    fn get_maps_depth() {
+        let proc = Process::create(&Job::root(), "proc", 0).unwrap();
+        let thread = Thread::create(&proc, "thread", 0).unwrap();
+        let mut vmar = proc.vmar();
+        for _ in 0..4 {
+            vmar = vmar
+                .allocate(None, 0x1000, VmarFlags::CAN_MAP_RXW, PAGE_SIZE)
+                .unwrap();
+        }
+        let vmo = VmObject::new_paged(1);
+        vmar.map_at(0, vmo, 0, 0x1000, MMUFlags::READ).unwrap();
+        let handle = proc.add_handle(Handle::new(proc.vmar(), Rights::DEFAULT_VMAR));
+
+        let syscall = syscall_for(thread);
+        // the root, 4 VMARs and a mapping
+        let mut buffer: Vec<u8> = vec![0; 8 * size_of::<ZxInfoMaps>()];
+        let get_maps = |max_depth: u32, buffer: &mut [u8]| -> ZxResult<(usize, usize)> {
+            let (mut actual, mut avail) = (0usize, 0usize);
+            syscall.sys_vmar_get_maps(
+                handle,
+                max_depth,
+                buffer.as_mut_ptr() as usize,
+                buffer.len(),
+                UserOutPtr::from(&mut actual as *mut usize as usize),
+                UserOutPtr::from(&mut avail as *mut usize as usize),
+            )?;
+            Ok((actual, avail))
+        };
+        assert_eq!(get_maps(0, &mut buffer), Ok((6, 6)));
+        assert_eq!(get_maps(2, &mut buffer), Ok((3, 3)));
+        // `avail` is within the depth even if the buffer is too small
+        assert_eq!(get_maps(2, &mut buffer[..1]), Ok((0, 3)));
+    }
+}
@@ -71,6 +71,68 @@ impl Syscall<'_> {
         Ok(())
     }
 
+    pub fn sys_vmo_read_vector(
+        &self,
+        handle_value: HandleValue,
+        vecs: UserInPtr<VmoIoVec>,
+        count: usize,
+    ) -> ZxResult {
+        info!(
+            "vmo.read_vector: handle={:#x?}, vecs=({:#x?}; {:#x?})",
+            handle_value, vecs, count,
+        );
+        if count > MAX_VMO_IO_VECS {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let proc = self.thread.proc();
+        let vmo = proc.get_object_with_rights::<VmObject>(handle_value, Rights::READ)?;
+        let vecs = vecs.read_array(count)?;
+        // check lengths before allocating, the offsets are checked by the VMO
+        check_io_vecs(&vecs, vmo.len())?;
+        let mut buffers: Vec<Vec<u8>> = vecs.iter().map(|v| vec![0u8; v.len]).collect();
+        let mut segments: Vec<(usize, &mut [u8])> = vecs
+            .iter()
+            .zip(buffers.iter_mut())
+            .map(|(v, buf)| (v.vmo_offset as usize, buf.as_mut_slice()))
+            .collect();
+        vmo.read_vector(&mut segments)?;
+        for (v, buf) in vecs.iter().zip(buffers.iter()) {
+            UserOutPtr::<u8>::from(v.buffer).write_array(buf)?;
+        }
+        Ok(())
+    }
+
+    pub fn sys_vmo_write_vector(
+        &self,
+        handle_value: HandleValue,
+        vecs: UserInPtr<VmoIoVec>,
+        count: usize,
+    ) -> ZxResult {
+        info!(
+            "vmo.write_vector: handle={:#x?}, vecs=({:#x?}; {:#x?})",
+            handle_value, vecs, count,
+        );
+        if count > MAX_VMO_IO_VECS {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        let proc = self.thread.proc();
+        let vmo = proc.get_object_with_rights::<VmObject>(handle_value, Rights::WRITE)?;
+        let vecs = vecs.read_array(count)?;
+        check_io_vecs(&vecs, vmo.len())?;
+        // copy in all buffers first, so a bad user pointer leaves the VMO unchanged
+        let buffers = vecs
+            .iter()
+            .map(|v| UserInPtr::<u8>::from(v.buffer).read_array(v.len))
+            .collect::<Result<Vec<_>, _>>()?;
+        let segments: Vec<(usize, &[u8])> = vecs
+            .iter()
+            .zip(buffers.iter())
+            .map(|(v, buf)| (v.vmo_offset as usize, buf.as_slice()))
+            .collect();
+        vmo.write_vector(&segments)?;
+        Ok(())
+    }
+
     pub fn sys_vmo_replace_as_executable(
         &self,
         handle: HandleValue,
@@ -116,12 +178,24 @@ impl Syscall<'_> {
             "vmo_create_child: handle={:#x}, options={:?}, offset={:#x}, size={:#x}",
             handle_value, options, offset, size
         );
-        if !options.contains(VmoCloneFlags::SNAPSHOT_AT_LEAST_ON_WRITE) {
+        // exactly one type of child must be given
+        let child_type = options
+            & (VmoCloneFlags::SNAPSHOT
+                | VmoCloneFlags::SNAPSHOT_AT_LEAST_ON_WRITE
+                | VmoCloneFlags::SLICE);
+        if child_type.bits().count_ones() != 1 {
+            return Err(ZxError::INVALID_ARGS);
+        }
+        // both snapshot types are full copy-on-write clones
+        if child_type == VmoCloneFlags::SLICE {
             return Err(ZxError::NOT_SUPPORTED);
         }
         let proc = self.thread.proc();
 
         let (vmo, parent_rights) = proc.get_object_and_rights::<VmObject>(handle_value)?;
+        if !vmo.is_paged() {
+            return Err(ZxError::NOT_SUPPORTED);
+        }
         if !parent_rights.contains(Rights::DUPLICATE | Rights::READ) {
             return Err(ZxError::ACCESS_DENIED);
         }
@@ -206,6 +280,9 @@ impl Syscall<'_> {
         if !page_aligned(offset) || !page_aligned(len) {
             return Err(ZxError::INVALID_ARGS);
         }
+        if offset > vmo.len() || len > vmo.len() - offset {
+            return Err(ZxError::OUT_OF_RANGE);
+        }
         match op {
             VMO_OP_COMMIT => {
                 if !rights.contains(Rights::WRITE) {
@@ -220,7 +297,10 @@ impl Syscall<'_> {
                 }
                 vmo.decommit(offset, len)
             }
-            _ => unimplemented!(),
+            _ => {
+                warn!("unsupported vmo op: {:#x}", op);
+                Err(ZxError::NOT_SUPPORTED)
+            }
         }
     }
 
@@ -246,3 +326,33 @@ bitflags! {
 /// VMO Opcodes (for vmo_op_range)
 const VMO_OP_COMMIT: u32 = 1;
 const VMO_OP_DECOMMIT: u32 = 2;
+
+/// Maximum number of segments in one vectored VMO read or write.
+const MAX_VMO_IO_VECS: usize = 1024;
+
+/// Maximum total length of the segments in one vectored VMO read or write,
+/// which are buffered in the kernel.
+const MAX_VMO_IO_BYTES: usize = 16 * 1024 * 1024; // 16 MB
+
+/// Check the lengths of `vecs` on a VMO of `vmo_size` bytes.
+fn check_io_vecs(vecs: &[VmoIoVec], vmo_size: usize) -> ZxResult {
+    if vecs.iter().any(|v| v.len > vmo_size) {
+        return Err(ZxError::OUT_OF_RANGE);
+    }
+    let total = vecs
+        .iter()
+        .try_fold(0usize, |total, v| total.checked_add(v.len));
+    match total {
+        Some(total) if total <= MAX_VMO_IO_BYTES => Ok(()),
+        _ => Err(ZxError::INVALID_ARGS),
+    }
+}
+
+/// A segment of `sys_vmo_read_vector` and `sys_vmo_write_vector`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VmoIoVec {
+    vmo_offset: u64,
+    buffer: usize,
+    len: usize,
+}
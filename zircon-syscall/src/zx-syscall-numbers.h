@@ -171,3 +171,8 @@
 #define ZX_SYS_futex_wake_handle_close_thread_exit 164
 #define ZX_SYS_vmar_unmap_handle_close_thread_exit 165
 #define ZX_SYS_COUNT 166
+// zCore extensions, outside the range of Zircon syscalls
+#define ZX_SYS_vmo_read_vector 4096
+#define ZX_SYS_vmo_write_vector 4097
+#define ZX_SYS_ticks_per_second 4098
+#define ZX_SYS_vmar_get_maps 4099